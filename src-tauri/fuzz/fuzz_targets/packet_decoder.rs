@@ -0,0 +1,14 @@
+//! Decodes arbitrary bytes received in a single read.
+#![no_main]
+
+use babara_project_desktop::comm_proto::PacketDecoder;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut decoder = PacketDecoder::new();
+    decoder.push(data);
+    while decoder.next_packet().is_some() {}
+    while decoder.resync() {
+        while decoder.next_packet().is_some() {}
+    }
+});
@@ -0,0 +1,28 @@
+//! Decodes arbitrary bytes received over several reads.
+//!
+//! The first byte of the input is the number of bytes received by each read.
+#![no_main]
+
+use babara_project_desktop::comm_proto::{PacketDecoder, MAX_PACKET_SIZE};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let (chunk_size, data) = match data.split_first() {
+        Some((&chunk_size, data)) => (usize::from(chunk_size).max(1), data),
+        None => return,
+    };
+
+    let mut decoder = PacketDecoder::new();
+    for chunk in data.chunks(chunk_size) {
+        decoder.push(chunk);
+        while decoder.next_packet().is_some() {}
+
+        // Only an incomplete packet is kept between reads
+        assert!(decoder.buffered() < MAX_PACKET_SIZE + 10);
+    }
+
+    // The line goes quiet after the last read
+    while decoder.resync() {
+        while decoder.next_packet().is_some() {}
+    }
+});
@@ -1,24 +1,32 @@
 //! Implementations of communication protocol between the boat and desktop application.
 
-use std::{
-    collections::HashMap,
-    fmt::Debug,
-    io::{ErrorKind, Read, Write},
-    sync::Mutex,
-    time::Duration,
-};
+mod decoder;
+
+use std::{collections::HashMap, fmt::Debug, io::Write, sync::Mutex, time::Duration};
 
+use chrono::Utc;
 use prost::Message;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use serde::Serialize;
 use serialport::{SerialPort, SerialPortInfo};
 use tauri::Manager;
 
+use crate::{
+    data::session::Session,
+    path::validate,
+    session_log::{self, SessionEvent, SessionLog},
+};
+
+pub use self::decoder::{PacketDecoder, ReceivedPacket, MAX_PACKET_SIZE};
+
 use self::babara_project::{
-    connection::{self, packet::PacketType, Connect, Received},
-    data::{BoatData, PathData},
+    connection::{self, packet::PacketType},
+    data::PathData,
 };
 
+/// The version of the communication protocol spoken with the boat.
+pub const PROTOCOL_VERSION: &str = "0.1.0";
+
 /// Googles protobuf package
 pub mod google {
     /// Googles common types protobuf package
@@ -135,10 +143,12 @@ pub mod babara_project {
             }
         }
 
+        // The sampling instructions of the collection points and the home and end points are
+        // not sent until the communication protocol has fields for them.
         impl From<&crate::path::PathData> for PathData {
             fn from(value: &crate::path::PathData) -> Self {
                 Self {
-                    version: value.version().to_string(),
+                    version: String::from(crate::comm_proto::PROTOCOL_VERSION),
                     points: value
                         .collection_points()
                         .iter()
@@ -195,8 +205,12 @@ pub struct BoatPort {
     app_handle: tauri::AppHandle,
     /// The connection status of the port.
     connected: bool,
-    /// Current Data Buffer of the Serial Port.
-    buf: Vec<u8>,
+    /// Decoder for the packets received from the serial port.
+    decoder: PacketDecoder,
+    /// Log of the connection, created once the boat replies.
+    session: Option<SessionLog>,
+    /// The survey session the data received is tagged with.
+    mission: Option<Session>,
 }
 
 impl Debug for BoatPort {
@@ -222,7 +236,9 @@ impl BoatPort {
             port,
             app_handle,
             connected: true,
-            buf: vec![],
+            decoder: PacketDecoder::new(),
+            session: None,
+            mission: None,
         };
 
         if port.check_connection() {
@@ -241,12 +257,12 @@ impl BoatPort {
                 .send_packet(
                     1,
                     &connection::Connect {
-                        version: String::from("0.1.0"),
+                        version: String::from(PROTOCOL_VERSION),
                     },
                 )
                 .is_err()
             {
-                let _ = self.disconnect();
+                let _ = self.disconnect("Unable to Send Connection Message");
                 return false;
             };
 
@@ -257,13 +273,13 @@ impl BoatPort {
                 Ok(_) => continue,
                 // Continuing if we are still connected
                 Err(_) if self.connected() => continue,
-                Err(_) => {
-                    let _ = self.disconnect();
+                Err(e) => {
+                    let _ = self.disconnect(&e);
                     return false;
                 }
             };
         }
-        let _ = self.disconnect();
+        let _ = self.disconnect("No Reply to Connection Message");
         false
     }
 
@@ -276,30 +292,53 @@ impl BoatPort {
     }
 
     /// Handle a recived packet from a serial port.
-    fn handle_packet(&mut self, buf: &[u8], packet_type: PacketType) -> Result<PacketType, String> {
-        match packet_type {
-            PacketType::BoatData => self.handle_boat_data(buf),
-            PacketType::Connect => Connect::decode(buf)
-                .map_err(|e| e.to_string())
-                .map(|_| packet_type),
-            PacketType::Received => Received::decode(buf)
-                .map_err(|e| e.to_string())
-                .map(|_| packet_type),
-            PacketType::PathData => Err(String::from("Invalid Packet")),
-            PacketType::Undefined => Err(String::from("Invalid Packet")),
+    fn handle_packet(&mut self, packet: ReceivedPacket) -> Result<PacketType, String> {
+        let packet_type = packet.packet_type();
+        match packet {
+            ReceivedPacket::BoatData(data) => self.handle_boat_data(data),
+            ReceivedPacket::Connect(connect) => {
+                self.handle_connect(connect);
+                Ok(packet_type)
+            }
+            ReceivedPacket::Received(_) => Ok(packet_type),
         }
     }
 
-    /// Handles a BoatData from the boat.
-    fn handle_boat_data(&mut self, buf: &[u8]) -> Result<PacketType, String> {
-        let data = BoatData::decode(buf).map_err(|e| e.to_string())?;
+    /// Handles a Connect reply from the boat.
+    ///
+    /// The session log and survey session are started on the first reply of the connection.
+    fn handle_connect(&mut self, connect: connection::Connect) {
+        if self.session.is_some() {
+            return;
+        }
+        self.mission
+            .get_or_insert_with(|| Session::new(&self.name, None, Utc::now()));
+        match SessionLog::create(&self.app_handle, &self.name, &connect.version) {
+            Ok(v) => self.session = Some(v),
+            Err(e) => log::warn!("Unable to Create Session Log for {}: {}", self.name, e),
+        }
+    }
+
+    /// Records an event in the session log.
+    fn record(&mut self, event: SessionEvent) {
+        if let Some(session) = self.session.as_mut() {
+            session.record(event);
+        }
+    }
+
+    /// Handles a BoatData from the boat, tagging it with the survey session.
+    fn handle_boat_data(&mut self, mut data: crate::data::BoatData) -> Result<PacketType, String> {
+        self.record(SessionEvent::DataReceived {
+            samples: data.features().len(),
+        });
+        let mission = self
+            .mission
+            .get_or_insert_with(|| Session::new(&self.name, None, Utc::now()));
+        data.tag(mission);
         self.app_handle
             .emit_all(
                 "received-data",
-                ReceivedDataPayload::new(
-                    crate::data::BoatData::try_from(data)?,
-                    self.name().to_string(),
-                ),
+                ReceivedDataPayload::new(data, self.name().to_string()),
             )
             .map_err(|e| e.to_string())?;
         Ok(PacketType::BoatData)
@@ -310,7 +349,7 @@ impl BoatPort {
         let packet_type =
             connection::packet::PacketType::try_from(packet_type).map_err(|e| e.to_string())?;
         let data = connection::Packet {
-            version: String::from("0.1.0"),
+            version: String::from(PROTOCOL_VERSION),
             r#type: packet_type.into(),
             data: packet.encode_to_vec(),
         };
@@ -320,10 +359,30 @@ impl BoatPort {
         Ok(())
     }
 
-    /// Sends PathData to the port.
+    /// Sends PathData to the port, starting a new survey session once it is sent.
     pub fn send_path(&mut self, data: PathData) -> Result<(), String> {
+        let result = self.try_send_path(&data);
+        let event = match &result {
+            Ok(()) => {
+                let hash = session_log::hash(&data.encode_to_vec());
+                self.mission = Some(Session::new(&self.name, Some(hash.clone()), Utc::now()));
+                SessionEvent::PathSent {
+                    hash,
+                    points: data.points.len(),
+                }
+            }
+            Err(e) => SessionEvent::Error {
+                message: format!("Unable to Send Path: {e}"),
+            },
+        };
+        self.record(event);
+        result
+    }
+
+    /// Sends PathData to the port until the boat acknowledges it.
+    fn try_send_path(&mut self, data: &PathData) -> Result<(), String> {
         for _ in 0..10 {
-            self.send_packet(PacketType::PathData.into(), &data)?;
+            self.send_packet(PacketType::PathData.into(), data)?;
             // Wait for boat to reply
             std::thread::sleep(Duration::from_millis(200));
             match self.receive_packet() {
@@ -345,62 +404,36 @@ impl BoatPort {
     ///
     /// This function will return `Err` if the port is not connected.
     pub fn receive_packet(&mut self) -> Result<connection::packet::PacketType, String> {
-        macro_rules! handle_error {
-            ($result:expr, $log_msg:expr) => {
-                match $result {
-                    Ok(v) => v,
-                    Err(e) => {
-                        log::info!($log_msg);
-                        return Err(e.to_string());
-                    }
-                }
-            };
-        }
-
         if !self.connected() {
             return Err(String::from("Port not Connected"));
         }
 
-        match self.port.read_to_end(&mut self.buf) {
-            Ok(_) => (),
-            // Retry if we get a timeout
-            Err(e) if e.kind() == ErrorKind::TimedOut => {
-                if self.buf.is_empty() {
-                    return Err(String::from("Nothing is Received"));
-                }
-            }
+        let read = match self.decoder.read_from(&mut self.port) {
+            Ok(v) => v,
             Err(e) => {
-                self.disconnect()?;
+                self.disconnect(&e.to_string())?;
                 log::info!("Disconnected, Reason: {}", e);
                 return Err(e.to_string());
             }
         };
 
-        if let Ok(length) = prost::decode_length_delimiter(&*self.buf) {
-            let size = length + prost::length_delimiter_len(length);
-            if self.buf.len() < size {
-                return Err(String::from("Nothing is Received"));
-            };
+        // A corrupted length prefix can stall the decoder, retry from the next packet once the
+        // line goes quiet
+        if read == 0 && self.decoder.resync() {
+            log::info!("Received an Incomplete Packet");
+        }
 
-            let data: Vec<u8> = self.buf.drain(..size).collect();
-            log::info!("Received Data");
-            log::debug!("Data Received: {:?}", data);
-            let message = handle_error!(
-                connection::Packet::decode_length_delimited(&*data),
-                "Received and Invalid Packet"
-            );
-            let packet_type = handle_error!(
-                PacketType::try_from(message.r#type),
-                "Received an Invalid PacketType"
-            );
-
-            self.buf.clear();
-            Ok(handle_error!(
-                self.handle_packet(&message.data, packet_type),
-                "Received an Invalid Packet Data"
-            ))
-        } else {
-            Err(String::from("Nothing is Received"))
+        match self.decoder.next_packet() {
+            Some(packet) => {
+                log::info!("Received Data");
+                log::debug!("Packet Received: {:?}", packet);
+                let result = self.handle_packet(packet);
+                if let Err(e) = &result {
+                    self.record(SessionEvent::Error { message: e.clone() });
+                }
+                result
+            }
+            None => Err(String::from("Nothing is Received")),
         }
     }
 
@@ -415,7 +448,12 @@ impl BoatPort {
     }
 
     /// Disconnects the port
-    fn disconnect(&mut self) -> Result<(), String> {
+    fn disconnect(&mut self, reason: &str) -> Result<(), String> {
+        if self.connected {
+            self.record(SessionEvent::Disconnected {
+                reason: reason.to_string(),
+            });
+        }
         self.connected = false;
         self.app_handle
             .emit_all("disconnected", self.name.as_str())
@@ -424,6 +462,17 @@ impl BoatPort {
     }
 }
 
+impl Drop for BoatPort {
+    /// Records the end of the session if the port is closed while still connected.
+    fn drop(&mut self) {
+        if self.connected {
+            self.record(SessionEvent::Disconnected {
+                reason: String::from("Port Closed"),
+            });
+        }
+    }
+}
+
 /// Search for available serial ports for communication.
 #[tauri::command]
 pub async fn find_ports(
@@ -499,12 +548,28 @@ pub async fn find_ports(
 }
 
 /// Send PathData to the connected port.
+///
+/// The path is checked with `validate_path` using the given options, such as the bounds of the
+/// loaded map. Paths with errors are refused unless `force` is set.
 #[tauri::command]
 pub fn send_path(
     state: tauri::State<ConnectedBoats>,
     port: String,
     data: crate::path::PathData,
+    options: Option<validate::ValidationOptions>,
+    force: Option<bool>,
 ) -> Result<(), String> {
+    log::info!("Validating Path Data");
+    let report = validate::validate(&data, &options.unwrap_or_default());
+    if report.has_errors() && !force.unwrap_or(false) {
+        let errors = report
+            .errors()
+            .map(|i| i.message())
+            .collect::<Vec<_>>()
+            .join(", ");
+        return Err(format!("Invalid Path: {errors}"));
+    }
+
     log::info!("Sending Path Data to {port}");
     let mut ports = state.boats.lock().unwrap();
     let port = ports
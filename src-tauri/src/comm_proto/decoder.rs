@@ -0,0 +1,577 @@
+//! Decoding of length-delimited packets received from the boat.
+
+use std::io::{self, ErrorKind, Read};
+
+use prost::Message;
+
+use super::babara_project::{
+    connection::{packet::PacketType, Connect, Packet, Received},
+    data::BoatData,
+};
+
+/// The largest packet accepted from the boat in bytes.
+///
+/// Length delimiters above this value are treated as line noise instead of being waited for.
+pub const MAX_PACKET_SIZE: usize = 64 * 1024;
+
+/// The longest encoding of a length delimiter in bytes.
+const MAX_DELIMITER_LEN: usize = 10;
+
+/// A validated packet received from the boat.
+#[derive(Debug, Clone)]
+pub enum ReceivedPacket {
+    /// The boat replied to a connection message.
+    Connect(Connect),
+    /// The boat acknowledged the last packet sent to it.
+    Received(Received),
+    /// The boat sent the data it collected.
+    BoatData(crate::data::BoatData),
+}
+
+impl ReceivedPacket {
+    /// Gets the type of the packet.
+    pub fn packet_type(&self) -> PacketType {
+        match self {
+            Self::Connect(_) => PacketType::Connect,
+            Self::Received(_) => PacketType::Received,
+            Self::BoatData(_) => PacketType::BoatData,
+        }
+    }
+}
+
+impl TryFrom<&Packet> for ReceivedPacket {
+    type Error = String;
+
+    /// Decodes and validates the data of a packet.
+    fn try_from(value: &Packet) -> Result<Self, Self::Error> {
+        let packet_type = PacketType::try_from(value.r#type).map_err(|e| e.to_string())?;
+        match packet_type {
+            PacketType::Connect => Connect::decode(&*value.data)
+                .map(Self::Connect)
+                .map_err(|e| e.to_string()),
+            PacketType::Received => Received::decode(&*value.data)
+                .map(Self::Received)
+                .map_err(|e| e.to_string()),
+            PacketType::BoatData => {
+                let data = BoatData::decode(&*value.data).map_err(|e| e.to_string())?;
+                crate::data::BoatData::try_from(data).map(Self::BoatData)
+            }
+            PacketType::PathData => Err(String::from("Invalid Packet")),
+            PacketType::Undefined => Err(String::from("Invalid Packet")),
+        }
+    }
+}
+
+/// Outcome of decoding a frame at an offset of the buffer.
+#[derive(Debug)]
+enum Frame {
+    /// A valid packet and the number of bytes it spans.
+    Valid(ReceivedPacket, usize),
+    /// Not enough bytes are buffered to decode the frame.
+    Incomplete,
+    /// The bytes do not start a valid frame.
+    Invalid(String),
+}
+
+/// Buffer reassembling length-delimited `Packet`s from a byte stream.
+///
+/// Bytes that do not start a valid packet are discarded one at a time, so a packet following
+/// line noise is still recovered. The frames carry no start marker or checksum, so a packet is
+/// lost when the noise before it happens to decode as a valid packet running into it.
+///
+/// Packets of any version and with unknown fields are accepted, as the boat may run newer
+/// firmware than the app. The version is only compared when connecting.
+#[derive(Debug, Default)]
+pub struct PacketDecoder {
+    /// The bytes received but not decoded yet.
+    buf: Vec<u8>,
+}
+
+impl PacketDecoder {
+    /// Creates a new empty decoder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets the number of bytes buffered but not decoded yet.
+    pub fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Appends received bytes to the buffer.
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Reads all the bytes currently available from a reader into the buffer.
+    ///
+    /// A timed out read is treated as the end of the available bytes. Returns the number of bytes
+    /// read.
+    pub fn read_from<R: Read + ?Sized>(&mut self, reader: &mut R) -> io::Result<usize> {
+        let start = self.buf.len();
+        match reader.read_to_end(&mut self.buf) {
+            Ok(_) => (),
+            Err(e) if e.kind() == ErrorKind::TimedOut => (),
+            Err(e) => return Err(e),
+        };
+        Ok(self.buf.len() - start)
+    }
+
+    /// Decodes the next packet in the buffer.
+    ///
+    /// Invalid bytes before the packet are discarded. Returns `None` if no complete packet is
+    /// buffered.
+    pub fn next_packet(&mut self) -> Option<ReceivedPacket> {
+        let mut offset = 0;
+        loop {
+            match self.frame_at(offset) {
+                Frame::Valid(packet, size) => {
+                    self.discard(offset);
+                    self.buf.drain(..size);
+                    return Some(packet);
+                }
+                Frame::Incomplete => {
+                    self.discard(offset);
+                    return None;
+                }
+                Frame::Invalid(reason) => {
+                    log::trace!("Invalid Frame at {offset}: {reason}");
+                    offset += 1;
+                }
+            }
+        }
+    }
+
+    /// Discards an incomplete frame at the start of the buffer if a complete packet follows it.
+    ///
+    /// Line noise in a length delimiter can leave the decoder waiting for bytes that are never
+    /// sent, this should be called when no more bytes are arriving. Returns `true` if any bytes
+    /// were discarded.
+    pub fn resync(&mut self) -> bool {
+        let offset = (1..self.buf.len()).find(|&v| matches!(self.frame_at(v), Frame::Valid(..)));
+        match offset {
+            Some(v) => {
+                self.discard(v);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Discards bytes from the start of the buffer.
+    fn discard(&mut self, count: usize) {
+        if count > 0 {
+            log::info!("Discarding {count} Invalid Bytes");
+            self.buf.drain(..count);
+        }
+    }
+
+    /// Decodes the frame starting at an offset of the buffer.
+    fn frame_at(&self, offset: usize) -> Frame {
+        let buf = &self.buf[offset..];
+
+        let delimiter_len = match buf
+            .iter()
+            .take(MAX_DELIMITER_LEN)
+            .position(|v| v & 0x80 == 0)
+        {
+            Some(v) => v + 1,
+            None if buf.len() < MAX_DELIMITER_LEN => return Frame::Incomplete,
+            None => return Frame::Invalid(String::from("Invalid Length Delimiter")),
+        };
+        let length = match prost::decode_length_delimiter(&buf[..delimiter_len]) {
+            Ok(v) => v,
+            Err(e) => return Frame::Invalid(e.to_string()),
+        };
+        if length > MAX_PACKET_SIZE {
+            return Frame::Invalid(format!("Packet Too Large: {length} bytes"));
+        }
+
+        let size = delimiter_len + length;
+        if buf.len() < size {
+            return Frame::Incomplete;
+        }
+
+        let payload = &buf[delimiter_len..size];
+        let packet = match Packet::decode(payload) {
+            Ok(v) => v,
+            Err(e) => return Frame::Invalid(e.to_string()),
+        };
+        match ReceivedPacket::try_from(&packet) {
+            Ok(v) => Frame::Valid(v, size),
+            Err(e) => Frame::Invalid(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use proptest::{collection::vec, prelude::*, sample::Index};
+
+    use super::*;
+    use crate::comm_proto::{
+        babara_project::data::boat_data::{BoatDataFeature, Layer},
+        google::r#type::LatLng,
+        PROTOCOL_VERSION,
+    };
+    use crate::migrate;
+
+    /// In-memory transport behaving like a serial port.
+    ///
+    /// Each chunk is returned by a single `read_to_end` call, which then times out.
+    struct MemoryTransport {
+        chunks: VecDeque<Vec<u8>>,
+    }
+
+    impl MemoryTransport {
+        /// Splits a stream into chunks ending at the given positions.
+        fn new(stream: &[u8], splits: &[Index]) -> Self {
+            let mut splits: Vec<usize> = splits.iter().map(|v| v.index(stream.len() + 1)).collect();
+            splits.push(stream.len());
+            splits.sort_unstable();
+
+            let mut start = 0;
+            let chunks = splits
+                .into_iter()
+                .map(|end| {
+                    let chunk = stream[start..end].to_vec();
+                    start = end;
+                    chunk
+                })
+                .collect();
+            Self { chunks }
+        }
+
+        /// Checks if every chunk has been read.
+        fn is_empty(&self) -> bool {
+            self.chunks.is_empty()
+        }
+    }
+
+    impl Read for MemoryTransport {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.chunks.front_mut() {
+                Some(chunk) if !chunk.is_empty() => {
+                    let size = chunk.len().min(buf.len());
+                    buf[..size].copy_from_slice(&chunk[..size]);
+                    chunk.drain(..size);
+                    Ok(size)
+                }
+                Some(_) => {
+                    self.chunks.pop_front();
+                    Err(ErrorKind::TimedOut.into())
+                }
+                None => Err(ErrorKind::TimedOut.into()),
+            }
+        }
+    }
+
+    /// Decodes every packet from a transport the same way `BoatPort::receive_packet` does.
+    fn receive_all(transport: &mut MemoryTransport) -> Vec<ReceivedPacket> {
+        let mut decoder = PacketDecoder::new();
+        let mut packets = vec![];
+        while !transport.is_empty() {
+            if decoder.read_from(transport).unwrap() == 0 {
+                decoder.resync();
+            }
+            packets.extend(std::iter::from_fn(|| decoder.next_packet()));
+        }
+
+        // The line goes quiet after the transport is drained
+        while decoder.resync() {
+            packets.extend(std::iter::from_fn(|| decoder.next_packet()));
+        }
+        packets
+    }
+
+    /// Encodes a packet into a length-delimited frame.
+    fn frame(packet_type: PacketType, data: Vec<u8>) -> Vec<u8> {
+        Packet {
+            version: String::from(PROTOCOL_VERSION),
+            r#type: packet_type.into(),
+            data,
+        }
+        .encode_length_delimited_to_vec()
+    }
+
+    /// Encodes the type and data of a received packet back into protobuf.
+    fn encode(packet: &ReceivedPacket) -> (PacketType, Vec<u8>) {
+        let data = match packet {
+            ReceivedPacket::Connect(v) => v.encode_to_vec(),
+            ReceivedPacket::Received(v) => v.encode_to_vec(),
+            ReceivedPacket::BoatData(v) => BoatData::from(v).encode_to_vec(),
+        };
+        (packet.packet_type(), data)
+    }
+
+    fn version() -> impl Strategy<Value = String> {
+        "[0-9]{1,2}\\.[0-9]{1,2}\\.[0-9]{1,2}"
+    }
+
+    fn boat_data_feature() -> impl Strategy<Value = BoatDataFeature> {
+        (
+            -50.0..50.0,
+            0.0..100.0,
+            prop_oneof![
+                Just(Layer::Surface),
+                Just(Layer::Middle),
+                Just(Layer::SeaBed)
+            ],
+            0..4_000_000_000i64,
+            -90.0..90.0,
+            -180.0..180.0,
+        )
+            .prop_map(
+                |(temperature, depth, layer, seconds, latitude, longitude)| BoatDataFeature {
+                    temperature,
+                    depth,
+                    layer: layer.into(),
+                    time: Some(prost_types::Timestamp { seconds, nanos: 0 }),
+                    geometry: Some(LatLng {
+                        latitude,
+                        longitude,
+                    }),
+                },
+            )
+    }
+
+    /// A packet the boat may send, as its type and encoded data.
+    fn packet() -> impl Strategy<Value = (PacketType, Vec<u8>)> {
+        prop_oneof![
+            version()
+                .prop_map(|version| (PacketType::Connect, Connect { version }.encode_to_vec())),
+            Just((PacketType::Received, Received {}.encode_to_vec())),
+            // Received data is stored in the current version, so only that version round trips
+            vec(boat_data_feature(), 0..8).prop_map(|features| (
+                PacketType::BoatData,
+                BoatData {
+                    version: migrate::BOAT_DATA.current(),
+                    features
+                }
+                .encode_to_vec()
+            )),
+        ]
+    }
+
+    /// Checks if the garbage before a packet decodes as a valid packet running into it.
+    ///
+    /// The decoder cannot tell such a frame from a real one, so the packet is lost.
+    fn overlaps_packet(bytes: &[u8], start: usize) -> bool {
+        let decoder = PacketDecoder {
+            buf: bytes.to_vec(),
+        };
+        (0..start).any(|v| matches!(decoder.frame_at(v), Frame::Valid(_, size) if v + size > start))
+    }
+
+    fn stream(packets: &[(PacketType, Vec<u8>)]) -> Vec<u8> {
+        packets
+            .iter()
+            .flat_map(|(packet_type, data)| frame(*packet_type, data.clone()))
+            .collect()
+    }
+
+    proptest! {
+        #[test]
+        fn decodes_concatenated_packets(
+            packets in vec(packet(), 0..8),
+            splits in vec(any::<Index>(), 0..16),
+        ) {
+            let mut transport = MemoryTransport::new(&stream(&packets), &splits);
+            let received: Vec<_> = receive_all(&mut transport).iter().map(encode).collect();
+            prop_assert_eq!(received, packets);
+        }
+
+        #[test]
+        fn reports_packet_type(packet in packet()) {
+            let mut decoder = PacketDecoder::new();
+            decoder.push(&frame(packet.0, packet.1));
+            let received = decoder.next_packet();
+            prop_assert_eq!(received.map(|v| v.packet_type()), Some(packet.0));
+            prop_assert_eq!(decoder.buffered(), 0);
+        }
+
+        #[test]
+        fn recovers_packet_after_garbage(
+            garbage in vec(any::<u8>(), 0..256),
+            packet in packet(),
+            splits in vec(any::<Index>(), 0..16),
+        ) {
+            let start = garbage.len();
+            let mut bytes = garbage;
+            bytes.extend(stream(std::slice::from_ref(&packet)));
+            let mut transport = MemoryTransport::new(&bytes, &splits);
+            let received = receive_all(&mut transport);
+            if !overlaps_packet(&bytes, start) {
+                prop_assert_eq!(received.last().map(encode), Some(packet));
+            }
+        }
+
+        #[test]
+        fn recovers_packet_after_idle_line(
+            garbage in vec(prop_oneof![Just(0x00u8), Just(0xFFu8)], 0..256),
+            packet in packet(),
+            splits in vec(any::<Index>(), 0..16),
+        ) {
+            let start = garbage.len();
+            let mut bytes = garbage;
+            bytes.extend(stream(std::slice::from_ref(&packet)));
+            prop_assert!(!overlaps_packet(&bytes, start));
+
+            let mut transport = MemoryTransport::new(&bytes, &splits);
+            let received = receive_all(&mut transport);
+            prop_assert_eq!(received.last().map(encode), Some(packet));
+        }
+
+        #[test]
+        fn decodes_packets_before_truncation(
+            packets in vec(packet(), 1..8),
+            cut in any::<Index>(),
+            splits in vec(any::<Index>(), 0..16),
+        ) {
+            let bytes = stream(&packets);
+            let cut = cut.index(bytes.len());
+            let mut transport = MemoryTransport::new(&bytes[..cut], &splits);
+            let received: Vec<_> = receive_all(&mut transport).iter().map(encode).collect();
+
+            let complete = packets
+                .iter()
+                .scan(0, |end, packet| {
+                    *end += frame(packet.0, packet.1.clone()).len();
+                    Some(*end)
+                })
+                .take_while(|&end| end <= cut)
+                .count();
+            prop_assert_eq!(received, &packets[..complete]);
+        }
+
+        #[test]
+        fn decodes_packets_before_corruption(
+            packets in vec(packet(), 1..8),
+            corruptions in vec((any::<Index>(), 1..=u8::MAX), 1..8),
+            splits in vec(any::<Index>(), 0..16),
+        ) {
+            let mut bytes = stream(&packets);
+            let mut first = bytes.len();
+            for (index, mask) in corruptions {
+                let index = index.index(bytes.len());
+                bytes[index] ^= mask;
+                first = first.min(index);
+            }
+            let mut transport = MemoryTransport::new(&bytes, &splits);
+            let received: Vec<_> = receive_all(&mut transport).iter().map(encode).collect();
+
+            let intact = packets
+                .iter()
+                .scan(0, |end, packet| {
+                    *end += frame(packet.0, packet.1.clone()).len();
+                    Some(*end)
+                })
+                .take_while(|&end| end <= first)
+                .count();
+            prop_assert!(received.len() >= intact);
+            prop_assert_eq!(&received[..intact], &packets[..intact]);
+        }
+
+        #[test]
+        fn never_panics_on_arbitrary_bytes(
+            bytes in vec(any::<u8>(), 0..1024),
+            splits in vec(any::<Index>(), 0..16),
+        ) {
+            let mut transport = MemoryTransport::new(&bytes, &splits);
+            receive_all(&mut transport);
+        }
+    }
+
+    #[test]
+    fn rejects_packets_not_sent_by_the_boat() {
+        let mut decoder = PacketDecoder::new();
+        decoder.push(&frame(
+            PacketType::PathData,
+            crate::comm_proto::babara_project::data::PathData::default().encode_to_vec(),
+        ));
+        decoder.push(&frame(PacketType::Undefined, vec![]));
+        assert!(decoder.next_packet().is_none());
+        assert!(!decoder.resync());
+    }
+
+    #[test]
+    fn accepts_other_versions() {
+        let mut decoder = PacketDecoder::new();
+        let packet = Packet {
+            version: String::from("9.9.9"),
+            r#type: PacketType::Received.into(),
+            data: vec![],
+        };
+        decoder.push(&packet.encode_length_delimited_to_vec());
+        assert_eq!(
+            decoder.next_packet().map(|v| v.packet_type()),
+            Some(PacketType::Received)
+        );
+    }
+
+    #[test]
+    fn accepts_unknown_fields() {
+        // A packet from newer firmware with an extra varint field 15
+        let mut payload = Packet {
+            version: String::from(PROTOCOL_VERSION),
+            r#type: PacketType::Received.into(),
+            data: vec![],
+        }
+        .encode_to_vec();
+        payload.extend([0x78, 0x01]);
+        let mut bytes = vec![];
+        prost::encode_length_delimiter(payload.len(), &mut bytes).unwrap();
+        bytes.extend(payload);
+
+        let mut decoder = PacketDecoder::new();
+        decoder.push(&bytes);
+        assert_eq!(
+            decoder.next_packet().map(|v| v.packet_type()),
+            Some(PacketType::Received)
+        );
+        assert_eq!(decoder.buffered(), 0);
+    }
+
+    #[test]
+    fn waits_for_incomplete_packets() {
+        let bytes = frame(PacketType::Received, vec![]);
+        let mut decoder = PacketDecoder::new();
+        decoder.push(&bytes[..bytes.len() - 1]);
+        assert!(decoder.next_packet().is_none());
+        assert!(!decoder.resync());
+        assert_eq!(decoder.buffered(), bytes.len() - 1);
+
+        decoder.push(&bytes[bytes.len() - 1..]);
+        assert_eq!(
+            decoder.next_packet().map(|v| v.packet_type()),
+            Some(PacketType::Received)
+        );
+    }
+
+    #[test]
+    fn resyncs_after_oversized_length_delimiter() {
+        // A length delimiter of 8192 bytes followed by a valid packet
+        let mut decoder = PacketDecoder::new();
+        decoder.push(&[0x80, 0x40]);
+        decoder.push(&frame(PacketType::Received, vec![]));
+        assert!(decoder.next_packet().is_none());
+
+        assert!(decoder.resync());
+        assert_eq!(
+            decoder.next_packet().map(|v| v.packet_type()),
+            Some(PacketType::Received)
+        );
+        assert_eq!(decoder.buffered(), 0);
+    }
+
+    #[test]
+    fn discards_length_delimiters_above_limit() {
+        let mut decoder = PacketDecoder::new();
+        let mut bytes = vec![];
+        prost::encode_length_delimiter(MAX_PACKET_SIZE + 1, &mut bytes).unwrap();
+        decoder.push(&bytes);
+        assert!(matches!(decoder.frame_at(0), Frame::Invalid(_)));
+    }
+}
@@ -0,0 +1,488 @@
+//! Coordinate reference systems for reading and writing projected GeoJSON.
+//!
+//! Coordinates are kept as WGS84 longitude and latitude inside the application and only converted
+//! when files are imported or exported. UTM uses the Krüger series for the transverse Mercator
+//! projection, which is accurate to well under a millimetre within a zone.
+
+use std::{fmt::Display, str::FromStr};
+
+use geo_types::{coord, Coord};
+use serde_json::{json, Value};
+
+use crate::path::local::{WGS84_A, WGS84_F};
+
+/// The scale factor on the central meridian of a UTM zone.
+const UTM_SCALE: f64 = 0.9996;
+
+/// The false easting of a UTM zone in metres.
+const UTM_FALSE_EASTING: f64 = 500_000.0;
+
+/// The false northing of a southern UTM zone in metres.
+const UTM_FALSE_NORTHING: f64 = 10_000_000.0;
+
+/// The largest latitude shown in Web Mercator in degrees.
+const WEB_MERCATOR_MAX_LAT: f64 = 85.051_128_779_806_59;
+
+/// The name of WGS84 longitude and latitude in GeoJSON.
+const CRS84: &str = "urn:ogc:def:crs:OGC:1.3:CRS84";
+
+/// A coordinate reference system files can be read from and written to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Crs {
+    /// WGS84 longitude and latitude in degrees, `EPSG:4326`.
+    #[default]
+    Wgs84,
+    /// Web Mercator in metres, `EPSG:3857`.
+    WebMercator,
+    /// A WGS84 UTM zone in metres, `EPSG:326xx` in the north and `EPSG:327xx` in the south.
+    Utm {
+        /// The zone from 1 to 60.
+        zone: u8,
+        /// Whether the zone is in the northern hemisphere.
+        north: bool,
+    },
+}
+
+impl Crs {
+    /// Gets the coordinate reference system of an EPSG code.
+    pub fn from_epsg(code: u32) -> Option<Self> {
+        match code {
+            4326 => Some(Self::Wgs84),
+            3857 | 900_913 => Some(Self::WebMercator),
+            32601..=32660 => Some(Self::Utm {
+                zone: (code - 32600) as u8,
+                north: true,
+            }),
+            32701..=32760 => Some(Self::Utm {
+                zone: (code - 32700) as u8,
+                north: false,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Gets the EPSG code of the coordinate reference system.
+    pub fn epsg(&self) -> u32 {
+        match *self {
+            Self::Wgs84 => 4326,
+            Self::WebMercator => 3857,
+            Self::Utm { zone, north: true } => 32600 + u32::from(zone),
+            Self::Utm { zone, north: false } => 32700 + u32::from(zone),
+        }
+    }
+
+    /// Gets the UTM zone containing a longitude and latitude.
+    ///
+    /// The wider zones around Norway and Svalbard are used where they apply.
+    pub fn utm_for(c: Coord<f64>) -> Self {
+        let lng = (c.x + 180.0).rem_euclid(360.0) - 180.0;
+        let mut zone = (((lng + 180.0) / 6.0).floor() as u8).min(59) + 1;
+        if (56.0..64.0).contains(&c.y) && (3.0..12.0).contains(&lng) {
+            zone = 32;
+        } else if (72.0..=84.0).contains(&c.y) && (0.0..42.0).contains(&lng) {
+            zone = match lng {
+                l if l < 9.0 => 31,
+                l if l < 21.0 => 33,
+                l if l < 33.0 => 35,
+                _ => 37,
+            };
+        }
+        Self::Utm {
+            zone,
+            north: c.y >= 0.0,
+        }
+    }
+
+    /// Gets the UTM zone containing the middle of some longitudes and latitudes.
+    ///
+    /// Returns `None` if there are no coordinates.
+    pub fn utm_around<I: IntoIterator<Item = Coord<f64>>>(coords: I) -> Option<Self> {
+        let (sum, count) = coords
+            .into_iter()
+            .fold((coord! { x: 0.0, y: 0.0 }, 0), |(sum, count), c| {
+                (sum + c, count + 1)
+            });
+        (count > 0).then(|| Self::utm_for(sum / f64::from(count)))
+    }
+
+    /// Converts a WGS84 longitude and latitude into the coordinate reference system.
+    pub fn project(&self, c: Coord<f64>) -> Coord<f64> {
+        match *self {
+            Self::Wgs84 => c,
+            Self::WebMercator => {
+                let lat = c.y.clamp(-WEB_MERCATOR_MAX_LAT, WEB_MERCATOR_MAX_LAT);
+                coord! {
+                    x: WGS84_A * c.x.to_radians(),
+                    y: WGS84_A * (std::f64::consts::FRAC_PI_4 + lat.to_radians() / 2.0).tan().ln(),
+                }
+            }
+            Self::Utm { zone, north } => {
+                let (easting, northing) =
+                    TransverseMercator::new().forward(c.x - central_meridian(zone), c.y);
+                coord! {
+                    x: UTM_FALSE_EASTING + easting,
+                    y: if north { northing } else { northing + UTM_FALSE_NORTHING },
+                }
+            }
+        }
+    }
+
+    /// Converts a coordinate in the coordinate reference system into a WGS84 longitude and
+    /// latitude.
+    pub fn unproject(&self, c: Coord<f64>) -> Coord<f64> {
+        match *self {
+            Self::Wgs84 => c,
+            Self::WebMercator => coord! {
+                x: (c.x / WGS84_A).to_degrees(),
+                y: (2.0 * (c.y / WGS84_A).exp().atan() - std::f64::consts::FRAC_PI_2).to_degrees(),
+            },
+            Self::Utm { zone, north } => {
+                let northing = if north { c.y } else { c.y - UTM_FALSE_NORTHING };
+                let (lng, lat) =
+                    TransverseMercator::new().inverse(c.x - UTM_FALSE_EASTING, northing);
+                coord! { x: lng + central_meridian(zone), y: lat }
+            }
+        }
+    }
+
+    /// Gets the `crs` member of GeoJSON in the coordinate reference system.
+    pub fn to_geojson(self) -> Value {
+        let name = match self {
+            Self::Wgs84 => String::from(CRS84),
+            _ => format!("urn:ogc:def:crs:EPSG::{}", self.epsg()),
+        };
+        json!({ "type": "name", "properties": { "name": name } })
+    }
+
+    /// Reads the `crs` member of GeoJSON.
+    pub fn from_geojson(crs: &Value) -> Result<Self, String> {
+        let properties = &crs["properties"];
+        match (crs["type"].as_str(), properties["name"].as_str()) {
+            (Some("name"), Some(name)) => name.parse(),
+            (Some("EPSG"), _) => properties["code"]
+                .as_u64()
+                .and_then(|c| u32::try_from(c).ok())
+                .and_then(Self::from_epsg)
+                .ok_or(format!("Unsupported CRS: {}", properties["code"])),
+            _ => Err(format!("Invalid CRS: {}", crs)),
+        }
+    }
+}
+
+impl Display for Crs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EPSG:{}", self.epsg())
+    }
+}
+
+impl FromStr for Crs {
+    type Err = String;
+
+    /// Parses an EPSG code such as `32648`, `EPSG:32648` or `urn:ogc:def:crs:EPSG::32648`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        if value.ends_with("CRS84") {
+            return Ok(Self::Wgs84);
+        }
+        let code = value
+            .rsplit(':')
+            .next()
+            .and_then(|c| c.parse::<u32>().ok())
+            .ok_or(format!("Invalid CRS: {}", value))?;
+        Self::from_epsg(code).ok_or(format!("Unsupported CRS: {}", value))
+    }
+}
+
+/// Gets the longitude of the central meridian of a UTM zone.
+fn central_meridian(zone: u8) -> f64 {
+    f64::from(zone) * 6.0 - 183.0
+}
+
+/// The coefficients of the Krüger series for the WGS84 ellipsoid.
+struct TransverseMercator {
+    /// The third flattening.
+    n: f64,
+    /// The radius of the rectifying sphere scaled by the central scale factor.
+    radius: f64,
+    /// The coefficients from the conformal sphere to the projection.
+    alpha: [f64; 3],
+    /// The coefficients from the projection to the conformal sphere.
+    beta: [f64; 3],
+    /// The coefficients from the conformal latitude to the geodetic latitude.
+    delta: [f64; 3],
+}
+
+impl TransverseMercator {
+    /// Computes the coefficients for the WGS84 ellipsoid.
+    fn new() -> Self {
+        let n = WGS84_F / (2.0 - WGS84_F);
+        let (n2, n3) = (n * n, n * n * n);
+        Self {
+            n,
+            radius: UTM_SCALE * WGS84_A / (1.0 + n) * (1.0 + n2 / 4.0 + n2 * n2 / 64.0),
+            alpha: [
+                n / 2.0 - 2.0 * n2 / 3.0 + 5.0 * n3 / 16.0,
+                13.0 * n2 / 48.0 - 3.0 * n3 / 5.0,
+                61.0 * n3 / 240.0,
+            ],
+            beta: [
+                n / 2.0 - 2.0 * n2 / 3.0 + 37.0 * n3 / 96.0,
+                n2 / 48.0 + n3 / 15.0,
+                17.0 * n3 / 480.0,
+            ],
+            delta: [
+                2.0 * n - 2.0 * n2 / 3.0 - 2.0 * n3,
+                7.0 * n2 / 3.0 - 8.0 * n3 / 5.0,
+                56.0 * n3 / 15.0,
+            ],
+        }
+    }
+
+    /// Projects a longitude from the central meridian and a latitude in degrees into metres east
+    /// and north.
+    fn forward(&self, lng: f64, lat: f64) -> (f64, f64) {
+        let (lng, lat) = (lng.to_radians(), lat.to_radians());
+        let e = 2.0 * self.n.sqrt() / (1.0 + self.n);
+        let t = (lat.sin().atanh() - e * (e * lat.sin()).atanh()).sinh();
+        let xi = t.atan2(lng.cos());
+        let eta = (lng.sin() / (1.0 + t * t).sqrt()).atanh();
+
+        let (mut easting, mut northing) = (eta, xi);
+        for (j, alpha) in self.alpha.iter().enumerate() {
+            let k = 2.0 * (j + 1) as f64;
+            easting += alpha * (k * xi).cos() * (k * eta).sinh();
+            northing += alpha * (k * xi).sin() * (k * eta).cosh();
+        }
+        (self.radius * easting, self.radius * northing)
+    }
+
+    /// Finds the longitude from the central meridian and the latitude in degrees of a point in
+    /// metres east and north.
+    fn inverse(&self, easting: f64, northing: f64) -> (f64, f64) {
+        let (xi, eta) = (northing / self.radius, easting / self.radius);
+        let (mut xi_prime, mut eta_prime) = (xi, eta);
+        for (j, beta) in self.beta.iter().enumerate() {
+            let k = 2.0 * (j + 1) as f64;
+            xi_prime -= beta * (k * xi).sin() * (k * eta).cosh();
+            eta_prime -= beta * (k * xi).cos() * (k * eta).sinh();
+        }
+
+        let chi = (xi_prime.sin() / eta_prime.cosh()).asin();
+        let mut lat = chi;
+        for (j, delta) in self.delta.iter().enumerate() {
+            lat += delta * (2.0 * (j + 1) as f64 * chi).sin();
+        }
+        let lng = eta_prime.sinh().atan2(xi_prime.cos());
+        (lng.to_degrees(), lat.to_degrees())
+    }
+}
+
+/// Converts the positions in a GeoJSON `coordinates` member.
+fn transform_positions<F: Fn(Coord<f64>) -> Coord<f64>>(value: &mut Value, f: &F) {
+    let position = match value.as_array_mut() {
+        Some(v) => v,
+        None => return,
+    };
+    match (
+        position.first().and_then(Value::as_f64),
+        position.get(1).and_then(Value::as_f64),
+    ) {
+        (Some(x), Some(y)) => {
+            let c = f(coord! { x: x, y: y });
+            position[0] = json!(c.x);
+            position[1] = json!(c.y);
+        }
+        _ => position.iter_mut().for_each(|v| transform_positions(v, f)),
+    }
+}
+
+/// Converts the positions of GeoJSON geometries, features and feature collections.
+///
+/// Only the members holding geometries are followed, so `properties` are left unchanged.
+fn transform_geometries<F: Fn(Coord<f64>) -> Coord<f64>>(value: &mut Value, f: &F) {
+    let object = match value.as_object_mut() {
+        Some(v) => v,
+        None => return,
+    };
+    if let Some(v) = object.get_mut("coordinates") {
+        transform_positions(v, f);
+    }
+    for key in ["geometry", "geometries", "features"] {
+        match object.get_mut(key) {
+            Some(Value::Array(values)) => {
+                values.iter_mut().for_each(|v| transform_geometries(v, f))
+            }
+            Some(v) => transform_geometries(v, f),
+            None => {}
+        }
+    }
+    // The bounding box would be in the old coordinates
+    object.remove("bbox");
+}
+
+/// Converts every position in GeoJSON, including positions in some top-level members.
+fn transform<F: Fn(Coord<f64>) -> Coord<f64>>(value: &mut Value, members: &[&str], f: &F) {
+    if let Some(object) = value.as_object_mut() {
+        for member in members {
+            if let Some(v) = object.get_mut(*member) {
+                transform_positions(v, f);
+            }
+        }
+    }
+    transform_geometries(value, f);
+}
+
+/// Converts GeoJSON into WGS84 longitude and latitude, removing its `crs` member.
+///
+/// The `crs` member of the GeoJSON is used if it has one, then the given coordinate reference
+/// system, and WGS84 otherwise. Positions in the given members are also converted.
+pub fn read_geojson(value: &mut Value, crs: Option<Crs>, members: &[&str]) -> Result<Crs, String> {
+    let crs = match value.as_object_mut().and_then(|o| o.remove("crs")) {
+        Some(v) if !v.is_null() => Crs::from_geojson(&v)?,
+        _ => crs.unwrap_or_default(),
+    };
+    log::debug!("Reading GeoJSON in {}", crs);
+    if crs != Crs::Wgs84 {
+        transform(value, members, &|c| crs.unproject(c));
+    }
+    Ok(crs)
+}
+
+/// Converts WGS84 GeoJSON into a coordinate reference system, adding a `crs` member.
+///
+/// Positions in the given members are also converted.
+pub fn write_geojson(value: &mut Value, crs: Crs, members: &[&str]) {
+    log::debug!("Writing GeoJSON in {}", crs);
+    if crs != Crs::Wgs84 {
+        transform(value, members, &|c| crs.project(c));
+    }
+    if let Some(object) = value.as_object_mut() {
+        object.insert(String::from("crs"), crs.to_geojson());
+    }
+}
+
+/// Reads the coordinate reference system chosen for an import or export.
+///
+/// `auto` picks the UTM zone around the coordinates and anything else is read as an EPSG code.
+pub fn choose<I: IntoIterator<Item = Coord<f64>>>(
+    crs: Option<&str>,
+    coords: I,
+) -> Result<Option<Crs>, String> {
+    match crs {
+        None => Ok(None),
+        Some("auto") => Ok(Some(Crs::utm_around(coords).unwrap_or_default())),
+        Some(v) => v.parse().map(Some),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Coord<f64>, b: Coord<f64>, tolerance: f64) {
+        assert!(
+            (a.x - b.x).abs() < tolerance && (a.y - b.y).abs() < tolerance,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn projects_utm() {
+        let crs = Crs::from_epsg(32617).unwrap();
+        assert_eq!(crs, Crs::utm_for(coord! { x: -79.3871, y: 43.6426 }));
+
+        // The CN Tower in Toronto
+        let projected = crs.project(coord! { x: -79.3871, y: 43.6426 });
+        assert_close(projected, coord! { x: 630_087.375, y: 4_833_442.312 }, 0.01);
+
+        // The equator on the central meridian is at the false easting
+        let origin = Crs::utm_for(coord! { x: 105.0, y: 0.0 }).project(coord! { x: 105.0, y: 0.0 });
+        assert_close(origin, coord! { x: 500_000.0, y: 0.0 }, 1e-6);
+
+        let south = Crs::from_epsg(32748).unwrap();
+        for c in [
+            coord! { x: 101.7, y: 3.1 },
+            coord! { x: 106.8, y: -6.2 },
+            coord! { x: 99.1, y: -0.5 },
+        ] {
+            assert_close(south.unproject(south.project(c)), c, 1e-9);
+        }
+    }
+
+    #[test]
+    fn projects_web_mercator() {
+        let crs = Crs::WebMercator;
+        let corner = crs.project(coord! { x: 180.0, y: WEB_MERCATOR_MAX_LAT });
+        assert_close(
+            corner,
+            coord! { x: 20_037_508.342_789, y: 20_037_508.342_789 },
+            1e-3,
+        );
+        let c = coord! { x: 101.7, y: 3.1 };
+        assert_close(crs.unproject(crs.project(c)), c, 1e-9);
+    }
+
+    #[test]
+    fn picks_utm_zones() {
+        assert_eq!(
+            Crs::utm_for(coord! { x: 101.7, y: 3.1 }),
+            Crs::Utm {
+                zone: 47,
+                north: true
+            }
+        );
+        assert_eq!(Crs::utm_for(coord! { x: 180.0, y: -1.0 }).epsg(), 32701);
+        assert_eq!(Crs::utm_for(coord! { x: 179.9, y: -1.0 }).epsg(), 32760);
+        // Norway and Svalbard
+        assert_eq!(Crs::utm_for(coord! { x: 5.0, y: 60.0 }).epsg(), 32632);
+        assert_eq!(Crs::utm_for(coord! { x: 10.0, y: 78.0 }).epsg(), 32633);
+    }
+
+    #[test]
+    fn parses_crs_names() {
+        assert_eq!("EPSG:3857".parse::<Crs>(), Ok(Crs::WebMercator));
+        assert_eq!(
+            "urn:ogc:def:crs:EPSG::32647".parse::<Crs>().unwrap().epsg(),
+            32647
+        );
+        assert_eq!(CRS84.parse::<Crs>(), Ok(Crs::Wgs84));
+        assert!("EPSG:27700".parse::<Crs>().is_err());
+        assert!("UTM".parse::<Crs>().is_err());
+
+        let crs = Crs::from_epsg(32747).unwrap();
+        assert_eq!(Crs::from_geojson(&crs.to_geojson()), Ok(crs));
+    }
+
+    #[test]
+    fn converts_geojson() {
+        let original = json!({
+            "type": "FeatureCollection",
+            "home": [101.7, 3.1],
+            "features": [{
+                "type": "Feature",
+                "properties": { "coordinates": [1.0, 2.0], "home": [1.0, 2.0] },
+                "geometry": { "type": "LineString", "coordinates": [[101.7, 3.1], [101.8, 3.2]] },
+            }],
+        });
+        let crs = Crs::utm_for(coord! { x: 101.7, y: 3.1 });
+        let mut value = original.clone();
+        write_geojson(&mut value, crs, &["home"]);
+        assert_eq!(
+            value["crs"]["properties"]["name"],
+            "urn:ogc:def:crs:EPSG::32647"
+        );
+        assert!(value["home"][0].as_f64().unwrap() > 100_000.0);
+        assert_eq!(
+            value["features"][0]["properties"],
+            original["features"][0]["properties"]
+        );
+
+        assert_eq!(read_geojson(&mut value, None, &["home"]), Ok(crs));
+        assert!(value.get("crs").is_none());
+        let line = &value["features"][0]["geometry"]["coordinates"];
+        assert!((line[1][0].as_f64().unwrap() - 101.8).abs() < 1e-9);
+        assert!((value["home"][1].as_f64().unwrap() - 3.1).abs() < 1e-9);
+    }
+}
@@ -1,9 +1,15 @@
 //! Data structure and function for working with data collected by the boat.
 
+pub mod contour;
+pub mod merge;
+pub mod raster;
+pub mod session;
+pub mod summary;
+
 use std::{
     fmt::Display,
     io::{ErrorKind, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::FromStr,
 };
 
@@ -19,26 +25,48 @@ use tauri::{
     AppHandle,
 };
 
+use crate::{
+    crs::{self, Crs},
+    diagnostics, migrate,
+};
+
+use self::session::Session;
+
 /// Data received from the boat in GeoJSON format.
 ///
 /// # Fields
 ///
 /// `version`: The version of the BoatData format.
+/// `sessions`: The survey sessions the data was collected in.
 /// `features`: The data collected by the boat.
 #[derive(Debug, Clone)]
 pub struct BoatData {
-    /// The version of the communication protocol used.
+    /// The version of the boat data format.
     version: String,
+    /// The version the data was written with before it was upgraded.
+    migrated_from: Option<String>,
+    /// The survey sessions the data was collected in.
+    sessions: Vec<Session>,
     /// The individual data point collected.
     features: Vec<BoatDataFeature>,
 }
 
 impl BoatData {
-    /// Gets the version of the communication protocol used.
+    /// Gets the version of the boat data format.
     pub fn version(&self) -> &str {
         &self.version
     }
 
+    /// Gets the version the data was written with if it was upgraded from an older version.
+    pub fn migrated_from(&self) -> Option<&str> {
+        self.migrated_from.as_deref()
+    }
+
+    /// Gets the survey sessions the data was collected in.
+    pub fn sessions(&self) -> &[Session] {
+        &self.sessions
+    }
+
     /// Gets the individual data point collected.
     pub fn features(&self) -> &[BoatDataFeature] {
         &self.features
@@ -48,7 +76,7 @@ impl BoatData {
 impl Default for BoatData {
     /// Default `BoatData`.
     ///
-    /// The version would default to "0.1.0" and an empty feature array.
+    /// The version would default to the current version and an empty feature array.
     ///
     /// # Examples
     ///
@@ -56,12 +84,14 @@ impl Default for BoatData {
     /// use babara_project_desktop::data::BoatData;
     ///
     /// let default = BoatData::default();
-    /// assert_eq!(default.version, String::from("0.1.0"));
-    /// assert_eq!(default.features, vec![]);
+    /// assert_eq!(default.version(), "0.2.0");
+    /// assert!(default.features().is_empty());
     /// ```
     fn default() -> Self {
         Self {
-            version: String::from("0.1.0"),
+            version: migrate::BOAT_DATA.current(),
+            migrated_from: None,
+            sessions: vec![],
             features: vec![],
         }
     }
@@ -71,7 +101,26 @@ impl FromStr for BoatData {
     type Err = String;
 
     /// Creates a new `BoatData` from a GeoJSON string.
+    ///
+    /// Every problem found in the GeoJSON is listed in the error.
     fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::parse(value)
+            .map_err(|e| diagnostics::explain(e, &diagnostics::check_boat_data(value, None)))
+    }
+}
+
+impl BoatData {
+    /// Parses GeoJSON, stopping at the first problem.
+    fn parse(value: &str) -> Result<Self, String> {
+        // Upgrading data from older versions before reading it
+        let mut json: serde_json::Value =
+            serde_json::from_str(value).map_err(|e| format!("{e}"))?;
+        if json.get("type").and_then(|t| t.as_str()) == Some("FeatureCollection") {
+            migrate::BOAT_DATA
+                .migrate(&mut json)
+                .map_err(|e| format!("Invalid Boat Data GeoJSON: {e}"))?;
+        }
+        let value = &json.to_string();
         let geojson: GeoJson = value.parse().map_err(|e| format!("{e}"))?;
 
         log::info!("Parsing Feature Collection");
@@ -89,7 +138,16 @@ impl FromStr for BoatData {
             .ok_or(String::from("Invalid Boat Data GeoJSON: Missing Version"))?
             .as_str()
             .ok_or(String::from("Invalid Boat Data GeoJSON: Invalid Version"))?;
+        let migrated_from = foreign_members
+            .get("migrated_from")
+            .and_then(|v| v.as_str())
+            .map(String::from);
         log::debug!("Version: {}", version);
+        let sessions = match foreign_members.get("sessions") {
+            Some(serde_json::Value::Null) | None => vec![],
+            Some(v) => serde_json::from_value(v.clone())
+                .map_err(|e| format!("Invalid Boat Data GeoJSON: Invalid Sessions: {e}"))?,
+        };
 
         log::info!("Extracting Features");
         let features = features.features;
@@ -102,6 +160,8 @@ impl FromStr for BoatData {
 
         Ok(Self {
             version: String::from(version),
+            migrated_from,
+            sessions,
             features,
         })
     }
@@ -143,6 +203,12 @@ impl From<&BoatData> for GeoJson {
         let features = value.features.iter().map(geojson::Feature::from).collect();
         let mut foreign_members = Map::new();
         foreign_members.insert(String::from("version"), json!(&value.version));
+        if let Some(migrated_from) = &value.migrated_from {
+            foreign_members.insert(String::from("migrated_from"), json!(migrated_from));
+        }
+        if !value.sessions.is_empty() {
+            foreign_members.insert(String::from("sessions"), json!(&value.sessions));
+        }
 
         let collection = FeatureCollection {
             bbox: None,
@@ -179,8 +245,11 @@ impl TryFrom<crate::comm_proto::babara_project::data::BoatData> for BoatData {
     fn try_from(
         value: crate::comm_proto::babara_project::data::BoatData,
     ) -> Result<Self, Self::Error> {
+        // The received features are stored in the current format whatever the packet version
         Ok(Self {
-            version: value.version,
+            version: migrate::BOAT_DATA.current(),
+            migrated_from: None,
+            sessions: vec![],
             features: value
                 .features
                 .into_iter()
@@ -197,7 +266,7 @@ impl TryFrom<crate::comm_proto::babara_project::data::BoatData> for BoatData {
 /// `Surface`: The data is collected from the surface of the water body.
 /// `Middle`: The data is collected from the middle of the water body.
 /// `SeaBed`: The data is collected from the sea bed of the water body.
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 pub enum Layer {
     #[serde(rename = "surface")]
     /// The data is collected from the surface of the water body.
@@ -255,6 +324,7 @@ impl From<crate::comm_proto::babara_project::data::boat_data::Layer> for Layer {
 /// `layer`: The layer of the water body the temperature is collected at.
 /// `time`: The date and time the temperature is collected.
 /// `geometry`: The coordinate the temperature is collected.
+/// `session`: The id of the survey session the temperature is collected in.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BoatDataFeature {
     /// The temperature measured at the location.
@@ -271,6 +341,9 @@ pub struct BoatDataFeature {
         deserialize_with = "deserialize_geometry"
     )]
     geometry: Point<f64>,
+    /// The id of the survey session the temperature is measured in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    session: Option<String>,
 }
 
 impl BoatDataFeature {
@@ -298,6 +371,43 @@ impl BoatDataFeature {
     pub fn geometry(&self) -> Point<f64> {
         self.geometry
     }
+
+    /// Gets the id of the survey session the temperature is measured in.
+    pub fn session(&self) -> Option<&str> {
+        self.session.as_deref()
+    }
+}
+
+#[cfg(test)]
+impl BoatDataFeature {
+    /// Creates a sample outside of any session for building test data.
+    pub(crate) fn sample(
+        geometry: Point<f64>,
+        temperature: f64,
+        depth: f64,
+        layer: Layer,
+        time: &str,
+    ) -> Self {
+        Self {
+            temperature,
+            depth,
+            layer,
+            time: time.parse().unwrap(),
+            geometry,
+            session: None,
+        }
+    }
+}
+
+#[cfg(test)]
+impl BoatData {
+    /// Creates data in the current version from samples for tests.
+    pub(crate) fn from_samples(features: Vec<BoatDataFeature>) -> Self {
+        Self {
+            features,
+            ..Default::default()
+        }
+    }
 }
 
 impl From<BoatDataFeatureCSV> for BoatDataFeature {
@@ -323,6 +433,7 @@ impl From<&BoatDataFeatureCSV> for BoatDataFeature {
             temperature: value.temperature,
             depth: value.depth,
             layer: value.layer,
+            session: value.session.clone(),
         }
     }
 }
@@ -351,6 +462,7 @@ impl TryFrom<crate::comm_proto::babara_project::data::boat_data::BoatDataFeature
             layer: value.layer().into(),
             time: timestamp.into(),
             geometry: Point::new(geometry.longitude, geometry.latitude),
+            session: None,
         })
     }
 }
@@ -379,6 +491,9 @@ impl From<&BoatDataFeature> for geojson::Feature {
         properties.insert(String::from("depth"), value.depth.into());
         properties.insert(String::from("layer"), value.layer.to_string().into());
         properties.insert(String::from("time"), value.time.to_rfc3339().into());
+        if let Some(session) = &value.session {
+            properties.insert(String::from("session"), session.as_str().into());
+        }
 
         Self {
             bbox: None,
@@ -400,6 +515,7 @@ impl From<&BoatDataFeature> for geojson::Feature {
 /// `time`: The date and time the temperature is collected.
 /// `lat`: The latitude of the coordinate the temperature is collected.
 /// `lng`: The longitude of the coordinate the temperature is collected.
+/// `session`: The id of the survey session the temperature is collected in.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BoatDataFeatureCSV {
     /// The temperature measured at the location.
@@ -415,6 +531,9 @@ pub struct BoatDataFeatureCSV {
     lat: f64,
     /// The longitude coordinate the temperature is measured at.
     lng: f64,
+    /// The id of the survey session the temperature is measured in.
+    #[serde(default)]
+    session: Option<String>,
 }
 
 impl From<BoatDataFeature> for BoatDataFeatureCSV {
@@ -441,6 +560,7 @@ impl From<&BoatDataFeature> for BoatDataFeatureCSV {
             temperature: value.temperature,
             depth: value.depth,
             layer: value.layer,
+            session: value.session.clone(),
         }
     }
 }
@@ -477,15 +597,24 @@ pub fn read_data(app_handle: AppHandle) -> Result<BoatData, String> {
     data_dir.push("data.geojson");
     log::debug!("Application GeoJSON Boat Data: {}", data_dir.display());
 
-    import_data(data_dir)
+    read_boat_data(&data_dir, None)
 }
 
-/// Import boat data from the file system.
-#[tauri::command]
-pub fn import_data(import_path: PathBuf) -> Result<BoatData, String> {
-    log::debug!("Importing from: {}", import_path.display());
-    Ok(match file::read_string(&import_path) {
-        Ok(v) => BoatData::from_str(&v)?,
+/// Reads boat data from a file, using the default data if it does not exist.
+///
+/// GeoJSON in another coordinate reference system is converted to WGS84, using the given
+/// coordinate reference system if the file does not name one.
+fn read_boat_data(import_path: &Path, crs: Option<Crs>) -> Result<BoatData, String> {
+    Ok(match file::read_string(import_path) {
+        Ok(v) => {
+            let read = |content: &str| {
+                let mut value: serde_json::Value =
+                    serde_json::from_str(content).map_err(|e| format!("{e}"))?;
+                crs::read_geojson(&mut value, crs, &[])?;
+                BoatData::parse(&value.to_string())
+            };
+            read(&v).map_err(|e| diagnostics::explain(e, &diagnostics::check_boat_data(&v, crs)))?
+        }
         Err(api::Error::Io(e)) => match e.kind() {
             ErrorKind::NotFound => {
                 log::warn!(
@@ -500,13 +629,46 @@ pub fn import_data(import_path: PathBuf) -> Result<BoatData, String> {
     })
 }
 
+/// Writes boat data to a GeoJSON file, converting it into the coordinate reference system if one
+/// is given.
+fn write_boat_data(export_path: &Path, data: &BoatData, crs: Option<Crs>) -> Result<(), String> {
+    let content = match crs {
+        Some(crs) => {
+            let mut value = serde_json::to_value(GeoJson::from(data)).map_err(|e| e.to_string())?;
+            crs::write_geojson(&mut value, crs, &[]);
+            value.to_string()
+        }
+        None => data.to_string(),
+    };
+    let mut file = std::fs::File::create(export_path).map_err(|e| e.to_string())?;
+    write!(file, "{}", content).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Import boat data from the file system.
+///
+/// GeoJSON without a `crs` member is read in the given EPSG code, or WGS84 if there is none.
+#[tauri::command]
+pub fn import_data(import_path: PathBuf, crs: Option<String>) -> Result<BoatData, String> {
+    log::debug!("Importing from: {}", import_path.display());
+    let crs = crs.map(|v| v.parse::<Crs>()).transpose()?;
+    read_boat_data(&import_path, crs)
+}
+
 /// Export boat data to the file system.
+///
+/// The data is written in the given EPSG code, or the UTM zone around the data for `auto`, and
+/// always names its coordinate reference system.
 #[tauri::command]
-pub fn export_data(export_path: PathBuf, data: BoatData) -> Result<(), String> {
+pub fn export_data(
+    export_path: PathBuf,
+    data: BoatData,
+    crs: Option<String>,
+) -> Result<(), String> {
     log::debug!("Exporting to: {}", export_path.display());
-    let mut file = std::fs::File::create(export_path).map_err(|e| e.to_string())?;
-    write!(file, "{}", data).map_err(|e| e.to_string())?;
-    Ok(())
+    let coords = data.features().iter().map(|f| f.geometry().0);
+    let crs = crs::choose(crs.as_deref(), coords)?.unwrap_or_default();
+    write_boat_data(&export_path, &data, Some(crs))
 }
 
 /// Save boat data to application storage.
@@ -520,7 +682,7 @@ pub fn save_data(app_handle: AppHandle, data: BoatData) -> Result<(), String> {
     data_dir.push("data.geojson");
     log::debug!("Application GeoJSON Path: {}", data_dir.display());
 
-    export_data(data_dir, data)
+    write_boat_data(&data_dir, &data, None)
 }
 
 /// Export boat data in CSV format to the file system.
@@ -541,7 +703,9 @@ pub fn import_data_csv(import_path: PathBuf) -> Result<BoatData, String> {
     log::debug!("Importing from: {}", import_path.display());
     Ok(match file::read_string(&import_path) {
         Ok(v) => BoatData {
-            version: String::from("0.1.0"),
+            version: migrate::BOAT_DATA.current(),
+            migrated_from: None,
+            sessions: vec![],
             features: csv::Reader::from_reader(v.as_bytes())
                 .deserialize::<BoatDataFeatureCSV>()
                 .map(|v| v.map(BoatDataFeature::from))
@@ -0,0 +1,347 @@
+//! Isotherms traced over interpolated temperatures with marching squares.
+//!
+//! Lines are traced between the centres of the cells of a grid, so they stop at the edge of the
+//! grid and the survey area. Isotherms that close on themselves are written as polygons.
+
+use std::collections::HashMap;
+
+use geo_types::Coord;
+use geojson::{Feature, FeatureCollection, GeoJson, JsonObject, Value};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{
+    raster::{self, Grid, RasterOptions},
+    BoatData, Layer,
+};
+
+/// The most isotherms traced from one grid.
+const MAX_LEVELS: usize = 1000;
+
+/// Which temperatures isotherms are traced at.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct ContourOptions {
+    /// The difference in temperature between isotherms.
+    interval: f64,
+    /// A temperature an isotherm is traced at, with the others a whole number of intervals away.
+    base: f64,
+}
+
+impl ContourOptions {
+    /// Creates new options.
+    pub fn new(interval: f64, base: f64) -> Self {
+        Self { interval, base }
+    }
+}
+
+impl Default for ContourOptions {
+    fn default() -> Self {
+        Self {
+            interval: 1.0,
+            base: 0.0,
+        }
+    }
+}
+
+/// An edge between the centres of two neighbouring cells.
+///
+/// Edges are the column and row of their north or west cell and whether they go east.
+type Edge = (usize, usize, bool);
+
+/// The segments of an isotherm and where it crosses each edge.
+type Segments = (Vec<(Edge, Edge)>, HashMap<Edge, Coord<f64>>);
+
+/// Finds the segments of an isotherm in each square of four cell centres.
+///
+/// Returns the segments as the edges they join and where the isotherm crosses each edge in cells.
+fn segments(grid: &Grid, level: f64) -> Segments {
+    let mut segments = vec![];
+    let mut crossings = HashMap::new();
+    for row in 0..grid.height().saturating_sub(1) {
+        for column in 0..grid.width().saturating_sub(1) {
+            let corners = [(0, 0), (1, 0), (1, 1), (0, 1)].map(|(x, y)| {
+                grid.value(column + x, row + y)
+                    .map(|v| (Coord::from(((column + x) as f64, (row + y) as f64)), v))
+            });
+            // Squares touching a cell outside the survey area are skipped
+            let corners = match corners {
+                [Some(a), Some(b), Some(c), Some(d)] => [a, b, c, d],
+                _ => continue,
+            };
+
+            // The top, right, bottom and left edges of the square
+            let edges: [Edge; 4] = [
+                (column, row, true),
+                (column + 1, row, false),
+                (column, row + 1, true),
+                (column, row, false),
+            ];
+            let mut crossed = vec![];
+            for (i, edge) in edges.into_iter().enumerate() {
+                let (p, a) = corners[i];
+                let (q, b) = corners[(i + 1) % 4];
+                if (a >= level) != (b >= level) {
+                    let t = (level - a) / (b - a);
+                    crossings.insert(edge, p + (q - p) * t);
+                    crossed.push(i);
+                }
+            }
+
+            match crossed.as_slice() {
+                &[i, j] => segments.push((edges[i], edges[j])),
+                [_, _, _, _] => {
+                    // A saddle, joined through the middle if it is on the same side as the first
+                    // corner
+                    let middle = corners.iter().map(|c| c.1).sum::<f64>() / 4.0;
+                    if (middle >= level) == (corners[0].1 >= level) {
+                        segments.push((edges[0], edges[1]));
+                        segments.push((edges[2], edges[3]));
+                    } else {
+                        segments.push((edges[3], edges[0]));
+                        segments.push((edges[1], edges[2]));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    (segments, crossings)
+}
+
+/// Joins the segments of an isotherm into lines, returning whether each line is closed.
+fn join(segments: &[(Edge, Edge)]) -> Vec<(Vec<Edge>, bool)> {
+    let mut ends: HashMap<Edge, Vec<usize>> = HashMap::new();
+    for (i, (a, b)) in segments.iter().enumerate() {
+        ends.entry(*a).or_default().push(i);
+        ends.entry(*b).or_default().push(i);
+    }
+
+    let mut used = vec![false; segments.len()];
+    let mut lines = vec![];
+    // Starting from the ends of open lines before tracing the closed lines left
+    let mut starts = ends
+        .iter()
+        .filter(|(_, s)| s.len() == 1)
+        .map(|(e, _)| *e)
+        .collect::<Vec<_>>();
+    starts.sort_unstable();
+    starts.extend(segments.iter().map(|s| s.0));
+
+    for start in starts {
+        let mut edge = start;
+        let mut line = vec![edge];
+        while let Some(&i) = ends[&edge].iter().find(|&&i| !used[i]) {
+            used[i] = true;
+            let (a, b) = segments[i];
+            edge = if a == edge { b } else { a };
+            line.push(edge);
+        }
+        if line.len() > 1 {
+            let closed = line.first() == line.last();
+            lines.push((line, closed));
+        }
+    }
+    lines
+}
+
+/// Traces isotherms over a grid of temperatures.
+///
+/// Each isotherm is a feature with its temperature and layer as properties.
+pub fn contours(
+    grid: &Grid,
+    layer: Layer,
+    options: &ContourOptions,
+) -> Result<FeatureCollection, String> {
+    if !(options.interval.is_finite() && options.interval > 0.0) {
+        return Err(String::from("Contour Interval must be Positive"));
+    }
+    if !options.base.is_finite() {
+        return Err(String::from("Contour Base must be Finite"));
+    }
+    let (min, max) = grid.range().ok_or(String::from("Grid is Empty"))?;
+    let first = ((min - options.base) / options.interval).ceil();
+    let last = ((max - options.base) / options.interval).floor();
+    if last - first >= MAX_LEVELS as f64 {
+        return Err(String::from("Too Many Contours, use a Larger Interval"));
+    }
+
+    let mut features = vec![];
+    for step in (first as i64)..=(last as i64) {
+        let level = options.base + step as f64 * options.interval;
+        let (segments, crossings) = segments(grid, level);
+        log::debug!("Isotherm {}: {} Segments", level, segments.len());
+
+        for (line, closed) in join(&segments) {
+            let mut positions = line
+                .iter()
+                .map(|e| {
+                    let c = grid.position(crossings[e].x, crossings[e].y);
+                    vec![c.x, c.y]
+                })
+                .collect::<Vec<_>>();
+            let geometry = if closed {
+                // Exterior rings are anticlockwise
+                let area = positions
+                    .windows(2)
+                    .map(|w| w[0][0] * w[1][1] - w[1][0] * w[0][1])
+                    .sum::<f64>();
+                if area < 0.0 {
+                    positions.reverse();
+                }
+                Value::Polygon(vec![positions])
+            } else {
+                Value::LineString(positions)
+            };
+
+            let mut properties = JsonObject::new();
+            properties.insert(String::from("temperature"), json!(level));
+            properties.insert(String::from("layer"), json!(layer));
+            features.push(Feature {
+                bbox: None,
+                geometry: Some(geometry.into()),
+                id: None,
+                properties: Some(properties),
+                foreign_members: None,
+            });
+        }
+    }
+
+    Ok(FeatureCollection {
+        bbox: None,
+        features,
+        foreign_members: None,
+    })
+}
+
+/// Trace isotherms over the temperatures of a layer interpolated onto a grid.
+#[tauri::command(async)]
+pub fn temperature_contours(
+    data: BoatData,
+    area: Option<geojson::Geometry>,
+    raster: Option<RasterOptions>,
+    options: Option<ContourOptions>,
+) -> Result<GeoJson, String> {
+    log::info!("Tracing Isotherms");
+    let raster = raster.unwrap_or_default();
+    let grid = raster::interpolate(&data, raster::read_area(area)?.as_ref(), &raster)?;
+    let contours = contours(&grid, raster.layer(), &options.unwrap_or_default())?;
+    Ok(GeoJson::from(contours))
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::{coord, Point};
+
+    use super::*;
+    use crate::{data::BoatDataFeature, path::local::lake};
+
+    /// Builds data in a layer from the position in metres around a point on a lake and
+    /// temperature of each sample.
+    fn data(layer: Layer, samples: &[(f64, f64, f64)]) -> BoatData {
+        let projection = lake();
+        let features = samples
+            .iter()
+            .map(|&(x, y, temperature)| {
+                let place = Point::from(projection.unproject(coord! { x: x, y: y }));
+                BoatDataFeature::sample(place, temperature, 0.5, layer, "2024-01-01T00:00:00Z")
+            })
+            .collect();
+        BoatData::from_samples(features)
+    }
+
+    /// Builds surface data with temperatures falling away from a warm spot in the middle.
+    fn warm_spot() -> BoatData {
+        let mut samples = vec![];
+        for x in 0..=4 {
+            for y in 0..=4 {
+                let (dx, dy) = (x as f64 - 2.0, y as f64 - 2.0);
+                samples.push((x as f64 * 25.0, y as f64 * 25.0, 30.0 - dx.hypot(dy)));
+            }
+        }
+        data(Layer::Surface, &samples)
+    }
+
+    #[test]
+    fn traces_closed_isotherms() {
+        let raster = RasterOptions::new(
+            Layer::Surface,
+            5.0,
+            raster::Interpolation::Kriging {
+                range: None,
+                sill: None,
+                nugget: None,
+            },
+        );
+        let grid = raster::interpolate(&warm_spot(), None, &raster).unwrap();
+        let contours = contours(&grid, Layer::Surface, &ContourOptions::new(1.0, 0.5)).unwrap();
+
+        let temperatures = contours
+            .features
+            .iter()
+            .map(|f| f.property("temperature").and_then(|t| t.as_f64()).unwrap())
+            .collect::<Vec<_>>();
+        assert!(temperatures.contains(&29.5));
+        assert!(temperatures.iter().all(|t| (t - 0.5).fract() == 0.0));
+
+        // The warmest isotherm circles the warm spot
+        let warmest = contours
+            .features
+            .iter()
+            .find(|f| f.property("temperature") == Some(&json!(29.5)))
+            .unwrap();
+        assert_eq!(warmest.property("layer"), Some(&json!("surface")));
+        let ring = match &warmest.geometry.as_ref().unwrap().value {
+            Value::Polygon(rings) => rings[0].clone(),
+            v => panic!("{:?}", v),
+        };
+        assert_eq!(ring.first(), ring.last());
+        let area = ring
+            .windows(2)
+            .map(|w| w[0][0] * w[1][1] - w[1][0] * w[0][1])
+            .sum::<f64>();
+        assert!(area > 0.0);
+    }
+
+    #[test]
+    fn traces_open_isotherms() {
+        // A grid warming from west to east
+        let data = data(
+            Layer::Middle,
+            &[
+                (0.0, 0.0, 20.0),
+                (0.0, 100.0, 20.0),
+                (100.0, 0.0, 24.0),
+                (100.0, 100.0, 24.0),
+            ],
+        );
+        let raster = RasterOptions::new(
+            Layer::Middle,
+            10.0,
+            raster::Interpolation::InverseDistance {
+                power: 2.0,
+                radius: None,
+            },
+        );
+        let grid = raster::interpolate(&data, None, &raster).unwrap();
+        let contours = contours(&grid, Layer::Middle, &ContourOptions::new(1.0, 0.0)).unwrap();
+
+        // One line from the north to the south of the grid at each whole degree
+        assert_eq!(contours.features.len(), 3);
+        let north = grid.position(0.0, 0.0).y;
+        let south = grid.position(0.0, (grid.height() - 1) as f64).y;
+        for feature in &contours.features {
+            let line = match &feature.geometry.as_ref().unwrap().value {
+                Value::LineString(line) => line.clone(),
+                v => panic!("{:?}", v),
+            };
+            let mut ends = [line[0][1], line[line.len() - 1][1]];
+            ends.sort_by(f64::total_cmp);
+            assert!((ends[0] - south).abs() < 1e-9);
+            assert!((ends[1] - north).abs() < 1e-9);
+        }
+
+        assert!(super::contours(&grid, Layer::Middle, &ContourOptions::new(0.0, 0.0)).is_err());
+        assert!(super::contours(&grid, Layer::Middle, &ContourOptions::new(1e-6, 0.0)).is_err());
+    }
+}
@@ -0,0 +1,320 @@
+//! Combining data collected by the boat from several sources without repeating samples.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, Utc};
+use geo::GeodesicDistance;
+use serde::{Deserialize, Serialize};
+
+use super::{session, BoatData, BoatDataFeature};
+use crate::migrate;
+
+/// The most time between duplicate samples in seconds, a day.
+const MAX_TIME_TOLERANCE: f64 = 86_400.0;
+
+/// Which sample is kept when two samples of the same place and time disagree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    /// Keeps the sample already in the data.
+    Keep,
+    /// Replaces the sample already in the data with the new sample.
+    Replace,
+    /// Keeps the sample already in the data with the mean of both temperatures.
+    Average,
+}
+
+/// When two samples are the same and what to do when they disagree.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct MergeOptions {
+    /// The most time between duplicate samples in seconds.
+    time_tolerance: f64,
+    /// The furthest apart duplicate samples may be in metres.
+    distance_tolerance: f64,
+    /// The largest difference in temperature between duplicates that still agree.
+    temperature_tolerance: f64,
+    /// Which sample is kept when duplicates disagree.
+    resolution: Resolution,
+}
+
+impl MergeOptions {
+    /// Creates new options.
+    pub fn new(
+        time_tolerance: f64,
+        distance_tolerance: f64,
+        temperature_tolerance: f64,
+        resolution: Resolution,
+    ) -> Self {
+        Self {
+            time_tolerance,
+            distance_tolerance,
+            temperature_tolerance,
+            resolution,
+        }
+    }
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        Self {
+            time_tolerance: 1.0,
+            distance_tolerance: 1.0,
+            temperature_tolerance: 0.01,
+            resolution: Resolution::Keep,
+        }
+    }
+}
+
+/// The merged data and what happened to each new sample.
+#[derive(Debug, Clone, Serialize)]
+pub struct MergeReport {
+    /// The data with the new samples merged in.
+    data: BoatData,
+    /// The number of new samples added.
+    added: usize,
+    /// The number of new samples that were duplicates and skipped.
+    skipped: usize,
+    /// The number of new samples that were duplicates with a different temperature.
+    conflicting: usize,
+}
+
+impl MergeReport {
+    /// Gets the data with the new samples merged in.
+    pub fn data(&self) -> &BoatData {
+        &self.data
+    }
+
+    /// Gets the number of new samples added.
+    pub fn added(&self) -> usize {
+        self.added
+    }
+
+    /// Gets the number of new samples that were duplicates and skipped.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// Gets the number of new samples that were duplicates with a different temperature.
+    pub fn conflicting(&self) -> usize {
+        self.conflicting
+    }
+}
+
+/// Merges new samples into data, one source after another.
+///
+/// New samples are also checked against the samples added before them, so duplicates within a
+/// source are only added once. Sessions with the same id are combined into one. The merged data
+/// is written in the current version.
+pub fn merge<'a>(
+    data: &BoatData,
+    sources: impl IntoIterator<Item = &'a BoatData>,
+    options: &MergeOptions,
+) -> Result<MergeReport, String> {
+    if !(options.time_tolerance.is_finite() && options.time_tolerance >= 0.0) {
+        return Err(String::from("Time Tolerance must not be Negative"));
+    }
+    if options.time_tolerance > MAX_TIME_TOLERANCE {
+        return Err(String::from("Time Tolerance must be at most a Day"));
+    }
+    if !(options.distance_tolerance.is_finite() && options.distance_tolerance >= 0.0) {
+        return Err(String::from("Distance Tolerance must not be Negative"));
+    }
+    if !(options.temperature_tolerance.is_finite() && options.temperature_tolerance >= 0.0) {
+        return Err(String::from("Temperature Tolerance must not be Negative"));
+    }
+    let tolerance = Duration::milliseconds((options.time_tolerance * 1000.0).round() as i64);
+
+    let mut features: Vec<BoatDataFeature> = vec![];
+    // The samples by time, to only compare samples close in time
+    let mut times: BTreeMap<DateTime<Utc>, Vec<usize>> = BTreeMap::new();
+    let mut report = MergeReport {
+        data: BoatData::default(),
+        added: 0,
+        skipped: 0,
+        conflicting: 0,
+    };
+
+    let sources = sources.into_iter().collect::<Vec<_>>();
+    let mut sessions = vec![];
+    for s in data
+        .sessions()
+        .iter()
+        .chain(sources.iter().flat_map(|s| s.sessions()))
+    {
+        session::add(&mut sessions, s);
+    }
+
+    let existing = data.features().iter().map(|f| (f, false));
+    let sources = sources.iter().flat_map(|s| s.features()).map(|f| (f, true));
+    for (feature, new) in existing.chain(sources) {
+        let (Some(from), Some(to)) = (
+            feature.time.checked_sub_signed(tolerance),
+            feature.time.checked_add_signed(tolerance),
+        ) else {
+            return Err(format!("Sample Time out of Range: {}", feature.time));
+        };
+        let duplicate = times
+            .range(from..=to)
+            .flat_map(|(_, i)| i)
+            .copied()
+            .find(|&i| {
+                let other = &features[i];
+                other.layer == feature.layer
+                    && other.geometry.geodesic_distance(&feature.geometry)
+                        <= options.distance_tolerance
+            });
+
+        let Some(i) = duplicate else {
+            times.entry(feature.time).or_default().push(features.len());
+            features.push(feature.clone());
+            report.added += new as usize;
+            continue;
+        };
+        if !new {
+            // Duplicates already in the data are left as they are
+            times.entry(feature.time).or_default().push(features.len());
+            features.push(feature.clone());
+            continue;
+        }
+
+        let other = &mut features[i];
+        if (other.temperature - feature.temperature).abs() <= options.temperature_tolerance {
+            report.skipped += 1;
+            continue;
+        }
+        log::debug!(
+            "Conflicting Samples at {}: {} and {}",
+            feature.time,
+            other.temperature,
+            feature.temperature
+        );
+        report.conflicting += 1;
+        match options.resolution {
+            Resolution::Keep => {}
+            Resolution::Replace => {
+                // Moving the sample in the time index if it moved in time
+                if let Some(indices) = times.get_mut(&other.time) {
+                    indices.retain(|&j| j != i);
+                }
+                *other = feature.clone();
+                times.entry(feature.time).or_default().push(i);
+            }
+            Resolution::Average => {
+                other.temperature = (other.temperature + feature.temperature) / 2.0;
+            }
+        }
+    }
+    log::debug!(
+        "Added: {}, Skipped: {}, Conflicting: {}",
+        report.added,
+        report.skipped,
+        report.conflicting
+    );
+
+    report.data = BoatData {
+        version: migrate::BOAT_DATA.current(),
+        migrated_from: None,
+        sessions,
+        features,
+    };
+    Ok(report)
+}
+
+/// Merge boat data from other sources into the current boat data.
+#[tauri::command]
+pub fn merge_data(
+    data: BoatData,
+    sources: Vec<BoatData>,
+    options: Option<MergeOptions>,
+) -> Result<MergeReport, String> {
+    log::info!("Merging {} Boat Data Sources", sources.len());
+    merge(&data, &sources, &options.unwrap_or_default())
+}
+
+/// Remove repeated samples from boat data.
+#[tauri::command]
+pub fn deduplicate_data(
+    data: BoatData,
+    options: Option<MergeOptions>,
+) -> Result<MergeReport, String> {
+    log::info!("Removing Duplicate Boat Data");
+    merge(&BoatData::default(), [&data], &options.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::point;
+
+    use super::*;
+    use crate::data::Layer;
+
+    /// Builds boat data from the temperature, longitude, layer and time of each feature.
+    fn data(features: &[(f64, f64, Layer, &str)]) -> BoatData {
+        BoatData::from_samples(
+            features
+                .iter()
+                .map(|&(temperature, x, layer, time)| {
+                    BoatDataFeature::sample(point!(x: x, y: 3.1), temperature, 0.5, layer, time)
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn merges_sources() {
+        let existing = data(&[
+            (28.0, 101.7, Layer::Surface, "2024-01-01T00:00:00Z"),
+            (27.0, 101.7, Layer::SeaBed, "2024-01-01T00:00:00Z"),
+        ]);
+        let received = data(&[
+            // The same sample received again, half a second and a few centimetres out
+            (28.0, 101.7000003, Layer::Surface, "2024-01-01T00:00:00.5Z"),
+            // The same place and time but a different temperature
+            (29.0, 101.7, Layer::SeaBed, "2024-01-01T00:00:00Z"),
+            // Another layer, another time and another place
+            (26.0, 101.7, Layer::Middle, "2024-01-01T00:00:00Z"),
+            (28.0, 101.7, Layer::Surface, "2024-01-01T00:00:05Z"),
+            (28.0, 101.701, Layer::Surface, "2024-01-01T00:00:00Z"),
+        ]);
+        let again = data(&[(26.0, 101.7, Layer::Middle, "2024-01-01T00:00:00Z")]);
+
+        let report = merge(&existing, [&received, &again], &MergeOptions::default()).unwrap();
+        assert_eq!(
+            (report.added(), report.skipped(), report.conflicting()),
+            (3, 2, 1)
+        );
+        let temperatures = report
+            .data()
+            .features()
+            .iter()
+            .map(|f| f.temperature())
+            .collect::<Vec<_>>();
+        assert_eq!(temperatures, [28.0, 27.0, 26.0, 28.0, 28.0]);
+
+        let options = MergeOptions::new(1.0, 1.0, 0.01, Resolution::Replace);
+        let report = merge(&existing, [&received], &options).unwrap();
+        assert_eq!(report.data().features()[1].temperature(), 29.0);
+        let options = MergeOptions::new(1.0, 1.0, 0.01, Resolution::Average);
+        let report = merge(&existing, [&received], &options).unwrap();
+        assert_eq!(report.data().features()[1].temperature(), 28.0);
+
+        let options = MergeOptions::new(-1.0, 1.0, 0.01, Resolution::Keep);
+        assert!(merge(&existing, [&received], &options).is_err());
+        let options = MergeOptions::new(1e300, 1.0, 0.01, Resolution::Keep);
+        assert!(merge(&existing, [&received], &options).is_err());
+    }
+
+    #[test]
+    fn deduplicates_one_source() {
+        let repeated = data(&[
+            (28.0, 101.7, Layer::Surface, "2024-01-01T00:00:00Z"),
+            (28.0, 101.7, Layer::Surface, "2024-01-01T00:00:00Z"),
+            (28.0, 101.7, Layer::Middle, "2024-01-01T00:00:00Z"),
+        ]);
+        let report = deduplicate_data(repeated, None).unwrap();
+        assert_eq!((report.added(), report.skipped()), (2, 1));
+        assert_eq!(report.data().features().len(), 2);
+        assert_eq!(report.data().version(), "0.2.0");
+    }
+}
@@ -0,0 +1,900 @@
+//! Interpolating temperatures onto a grid to show over the map or open in GIS software.
+//!
+//! The grid is regular in longitude and latitude with square cells at its centre, and distances
+//! between samples are measured in a local projection around it.
+
+use std::{collections::BTreeMap, fs::File, io::Write, path::PathBuf};
+
+use geo::{BoundingRect, Contains};
+use geo_types::{coord, Coord, Geometry, MultiPolygon, Point};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+
+use super::{BoatData, Layer};
+use crate::path::local::LocalProjection;
+
+/// The most cells a grid may have.
+const MAX_CELLS: usize = 4_000_000;
+
+/// The most distinct sample locations that can be kriged.
+const MAX_KRIGING_SAMPLES: usize = 1000;
+
+/// The most work kriging a grid may take, as the square of the samples times the cells.
+///
+/// Each cell solves the kriging system again, which takes time in the square of the samples.
+const MAX_KRIGING_WORK: u64 = 10_000_000_000;
+
+/// Samples closer than this in metres are averaged into one sample.
+const SAMPLE_TOLERANCE: f64 = 0.01;
+
+/// The colours of the overlay from the coldest to the hottest temperature.
+const RAMP: [[u8; 3]; 5] = [
+    [49, 54, 149],
+    [116, 173, 209],
+    [255, 255, 191],
+    [244, 109, 67],
+    [165, 0, 38],
+];
+
+/// A way of estimating the temperature between samples.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Interpolation {
+    /// Weighting samples by the inverse of their distance raised to a power.
+    InverseDistance {
+        /// The power of the distance, larger powers follow the nearest samples more closely.
+        power: f64,
+        /// Only samples within this distance in metres are used if it is set.
+        radius: Option<f64>,
+    },
+    /// Ordinary kriging with a spherical variogram.
+    Kriging {
+        /// The distance in metres samples stop being related, half the largest distance between
+        /// samples if missing.
+        range: Option<f64>,
+        /// The variance of unrelated samples, the variance of the samples if missing.
+        sill: Option<f64>,
+        /// The variance of samples in the same place.
+        nugget: Option<f64>,
+    },
+}
+
+impl Interpolation {
+    /// Checks the options that were given, the defaults depend on the samples.
+    fn validate(&self) -> Result<(), String> {
+        let valid = |v: f64| v.is_finite() && v > 0.0;
+        match *self {
+            Self::InverseDistance { power, radius } => {
+                if !valid(power) {
+                    return Err(String::from("Power must be Positive"));
+                }
+                if radius.is_some_and(|r| !valid(r)) {
+                    return Err(String::from("Search Radius must be Positive"));
+                }
+            }
+            Self::Kriging {
+                range,
+                sill,
+                nugget,
+            } => {
+                if range.is_some_and(|r| !valid(r)) || sill.is_some_and(|s| !valid(s)) {
+                    return Err(String::from("Variogram Range and Sill must be Positive"));
+                }
+                let below_sill = |n: f64| (0.0..sill.unwrap_or(f64::INFINITY)).contains(&n);
+                if nugget.is_some_and(|n| !below_sill(n)) {
+                    return Err(String::from("Variogram Nugget must be less than the Sill"));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How temperatures are interpolated onto a grid.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct RasterOptions {
+    /// The layer to interpolate the temperatures of.
+    layer: Layer,
+    /// The width of each cell in metres.
+    cell_size: f64,
+    /// How temperatures are estimated between samples.
+    interpolation: Interpolation,
+}
+
+impl RasterOptions {
+    /// Creates new options.
+    pub fn new(layer: Layer, cell_size: f64, interpolation: Interpolation) -> Self {
+        Self {
+            layer,
+            cell_size,
+            interpolation,
+        }
+    }
+
+    /// Gets the layer to interpolate the temperatures of.
+    pub fn layer(&self) -> Layer {
+        self.layer
+    }
+}
+
+impl Default for RasterOptions {
+    fn default() -> Self {
+        Self {
+            layer: Layer::Surface,
+            cell_size: 10.0,
+            interpolation: Interpolation::InverseDistance {
+                power: 2.0,
+                radius: None,
+            },
+        }
+    }
+}
+
+/// Temperatures on a regular grid of longitude and latitude.
+#[derive(Debug, Clone)]
+pub struct Grid {
+    /// The number of columns.
+    width: usize,
+    /// The number of rows.
+    height: usize,
+    /// The outer edges of the grid as west, south, east and north.
+    bounds: (f64, f64, f64, f64),
+    /// The temperature of each cell by row from the north, `NaN` outside the survey area.
+    values: Vec<f64>,
+}
+
+impl Grid {
+    /// Gets the number of columns.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Gets the number of rows.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Gets the outer edges of the grid as west, south, east and north.
+    pub fn bounds(&self) -> (f64, f64, f64, f64) {
+        self.bounds
+    }
+
+    /// Gets the temperature of a cell, counting rows from the north.
+    ///
+    /// Returns `None` outside the grid or the survey area.
+    pub fn value(&self, column: usize, row: usize) -> Option<f64> {
+        if column >= self.width || row >= self.height {
+            return None;
+        }
+        Some(self.values[row * self.width + column]).filter(|v| !v.is_nan())
+    }
+
+    /// Gets the lowest and highest temperature in the grid.
+    pub fn range(&self) -> Option<(f64, f64)> {
+        self.values
+            .iter()
+            .filter(|v| !v.is_nan())
+            .fold(None, |range, &v| match range {
+                Some((min, max)) => Some((v.min(min), v.max(max))),
+                None => Some((v, v)),
+            })
+    }
+
+    /// Gets the longitude and latitude of a position measured in cells from the centre of the
+    /// north west cell, counting rows to the south.
+    pub fn position(&self, column: f64, row: f64) -> Coord<f64> {
+        let (cell_x, cell_y) = self.cell();
+        let (west, _, _, north) = self.bounds;
+        coord! {
+            x: west + (column + 0.5) * cell_x,
+            y: north - (row + 0.5) * cell_y,
+        }
+    }
+
+    /// Gets the size of a cell in degrees of longitude and latitude.
+    fn cell(&self) -> (f64, f64) {
+        let (west, south, east, north) = self.bounds;
+        (
+            (east - west) / self.width as f64,
+            (north - south) / self.height as f64,
+        )
+    }
+}
+
+/// Where an overlay image goes on the map.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Overlay {
+    /// The outer edges of the image as west, south, east and north.
+    bounds: (f64, f64, f64, f64),
+    /// The temperature shown in the coldest colour.
+    min: f64,
+    /// The temperature shown in the hottest colour.
+    max: f64,
+}
+
+/// Averages the temperatures of samples in the same place.
+fn samples(data: &BoatData, layer: Layer, projection: &LocalProjection) -> Vec<(Coord<f64>, f64)> {
+    let mut places: BTreeMap<(i64, i64), (Coord<f64>, f64, usize)> = BTreeMap::new();
+    for feature in data.features().iter().filter(|f| f.layer() == layer) {
+        let c = projection.project(feature.geometry().0);
+        let key = (
+            (c.x / SAMPLE_TOLERANCE).round() as i64,
+            (c.y / SAMPLE_TOLERANCE).round() as i64,
+        );
+        let place = places.entry(key).or_insert((c, 0.0, 0));
+        place.1 += feature.temperature();
+        place.2 += 1;
+    }
+    places
+        .into_values()
+        .map(|(c, sum, count)| (c, sum / count as f64))
+        .collect()
+}
+
+/// Gets the distance between two projected coordinates.
+fn distance(a: Coord<f64>, b: Coord<f64>) -> f64 {
+    (a.x - b.x).hypot(a.y - b.y)
+}
+
+/// An LU decomposition with partial pivoting for solving the same system many times.
+struct Lu {
+    size: usize,
+    /// The lower and upper factors stored together by row.
+    factors: Vec<f64>,
+    /// The row of the original matrix in each row of the factors.
+    pivots: Vec<usize>,
+}
+
+impl Lu {
+    /// Decomposes a square matrix stored by row, returning `None` if it is singular.
+    fn new(mut factors: Vec<f64>, size: usize) -> Option<Self> {
+        let scale = factors.iter().fold(0.0, |m: f64, v| m.max(v.abs()));
+        let mut pivots = (0..size).collect::<Vec<_>>();
+        for k in 0..size {
+            let pivot = (k..size).max_by(|&a, &b| {
+                factors[a * size + k]
+                    .abs()
+                    .total_cmp(&factors[b * size + k].abs())
+            })?;
+            if factors[pivot * size + k].abs() <= scale * 1e-12 {
+                return None;
+            }
+            if pivot != k {
+                for j in 0..size {
+                    factors.swap(k * size + j, pivot * size + j);
+                }
+                pivots.swap(k, pivot);
+            }
+            for i in k + 1..size {
+                let factor = factors[i * size + k] / factors[k * size + k];
+                factors[i * size + k] = factor;
+                for j in k + 1..size {
+                    factors[i * size + j] -= factor * factors[k * size + j];
+                }
+            }
+        }
+        Some(Self {
+            size,
+            factors,
+            pivots,
+        })
+    }
+
+    /// Solves the system for a right hand side.
+    fn solve(&self, b: &[f64]) -> Vec<f64> {
+        let n = self.size;
+        let mut x = self.pivots.iter().map(|&p| b[p]).collect::<Vec<_>>();
+        for i in 0..n {
+            for j in 0..i {
+                x[i] -= self.factors[i * n + j] * x[j];
+            }
+        }
+        for i in (0..n).rev() {
+            for j in i + 1..n {
+                x[i] -= self.factors[i * n + j] * x[j];
+            }
+            x[i] /= self.factors[i * n + i];
+        }
+        x
+    }
+}
+
+/// A spherical variogram.
+#[derive(Debug, Clone, Copy)]
+struct Variogram {
+    range: f64,
+    sill: f64,
+    nugget: f64,
+}
+
+impl Variogram {
+    /// Gets the expected variance between samples a distance apart.
+    fn at(&self, h: f64) -> f64 {
+        if h <= 0.0 {
+            0.0
+        } else if h >= self.range {
+            self.sill
+        } else {
+            let r = h / self.range;
+            self.nugget + (self.sill - self.nugget) * (1.5 * r - 0.5 * r.powi(3))
+        }
+    }
+}
+
+/// Estimates the temperature anywhere from the samples.
+enum Estimator {
+    /// Every sample has the same temperature.
+    Constant(f64),
+    InverseDistance {
+        samples: Vec<(Coord<f64>, f64)>,
+        power: f64,
+        radius: Option<f64>,
+    },
+    Kriging {
+        samples: Vec<(Coord<f64>, f64)>,
+        variogram: Variogram,
+        system: Lu,
+    },
+}
+
+impl Estimator {
+    fn new(samples: Vec<(Coord<f64>, f64)>, interpolation: &Interpolation) -> Result<Self, String> {
+        interpolation.validate()?;
+        let first = samples[0].1;
+        if samples.iter().all(|&(_, v)| v == first) {
+            return Ok(Self::Constant(first));
+        }
+
+        match *interpolation {
+            Interpolation::InverseDistance { power, radius } => Ok(Self::InverseDistance {
+                samples,
+                power,
+                radius,
+            }),
+            Interpolation::Kriging {
+                range,
+                sill,
+                nugget,
+            } => {
+                let n = samples.len();
+                if n > MAX_KRIGING_SAMPLES {
+                    return Err(format!(
+                        "Too Many Samples for Kriging: {}, the most is {}",
+                        n, MAX_KRIGING_SAMPLES
+                    ));
+                }
+                let mean = samples.iter().map(|s| s.1).sum::<f64>() / n as f64;
+                let variance = samples.iter().map(|s| (s.1 - mean).powi(2)).sum::<f64>() / n as f64;
+                let furthest = samples
+                    .iter()
+                    .flat_map(|a| samples.iter().map(|b| distance(a.0, b.0)))
+                    .fold(0.0, f64::max);
+                let variogram = Variogram {
+                    range: range.unwrap_or(furthest / 2.0),
+                    sill: sill.unwrap_or(variance),
+                    nugget: nugget.unwrap_or(0.0),
+                };
+                let valid = |v: f64| v.is_finite() && v > 0.0;
+                if !(valid(variogram.range) && valid(variogram.sill)) {
+                    return Err(String::from("Variogram Range and Sill must be Positive"));
+                }
+                if !(0.0..variogram.sill).contains(&variogram.nugget) {
+                    return Err(String::from("Variogram Nugget must be less than the Sill"));
+                }
+                log::debug!("Kriging with {:?}", variogram);
+
+                // The variances between samples with a row and column keeping the weights unbiased
+                let size = n + 1;
+                let mut matrix = vec![1.0; size * size];
+                matrix[size * size - 1] = 0.0;
+                for (i, a) in samples.iter().enumerate() {
+                    for (j, b) in samples.iter().enumerate() {
+                        matrix[i * size + j] = variogram.at(distance(a.0, b.0));
+                    }
+                }
+                let system =
+                    Lu::new(matrix, size).ok_or(String::from("Unable to Krige the Samples"))?;
+                Ok(Self::Kriging {
+                    samples,
+                    variogram,
+                    system,
+                })
+            }
+        }
+    }
+
+    /// Estimates the temperature at a projected coordinate, `NaN` if there are no samples near.
+    fn estimate(&self, c: Coord<f64>) -> f64 {
+        match self {
+            Self::Constant(v) => *v,
+            Self::InverseDistance {
+                samples,
+                power,
+                radius,
+            } => {
+                let (mut sum, mut weights) = (0.0, 0.0);
+                for &(s, v) in samples {
+                    let d = distance(c, s);
+                    if d < SAMPLE_TOLERANCE {
+                        return v;
+                    }
+                    if radius.is_some_and(|r| d > r) {
+                        continue;
+                    }
+                    let w = d.powf(-power);
+                    sum += w * v;
+                    weights += w;
+                }
+                if weights > 0.0 {
+                    sum / weights
+                } else {
+                    f64::NAN
+                }
+            }
+            Self::Kriging {
+                samples,
+                variogram,
+                system,
+            } => {
+                let mut b = samples
+                    .iter()
+                    .map(|&(s, _)| variogram.at(distance(c, s)))
+                    .collect::<Vec<_>>();
+                b.push(1.0);
+                let weights = system.solve(&b);
+                samples.iter().zip(weights).map(|(s, w)| s.1 * w).sum()
+            }
+        }
+    }
+}
+
+/// Interpolates the temperatures of a layer onto a grid.
+///
+/// The grid covers the survey area if there is one, leaving the cells outside it empty, and the
+/// samples otherwise.
+pub fn interpolate(
+    data: &BoatData,
+    area: Option<&MultiPolygon<f64>>,
+    options: &RasterOptions,
+) -> Result<Grid, String> {
+    if !(options.cell_size.is_finite() && options.cell_size > 0.0) {
+        return Err(String::from("Cell Size must be Positive"));
+    }
+    let points = data
+        .features()
+        .iter()
+        .filter(|f| f.layer() == options.layer)
+        .map(|f| f.geometry().0)
+        .collect::<Vec<_>>();
+    let rect = match area {
+        Some(area) => area.bounding_rect(),
+        None => geo_types::MultiPoint::from(points).bounding_rect(),
+    }
+    .ok_or(format!("No Data in the {} Layer", options.layer))?;
+
+    let projection = LocalProjection::new(rect.center());
+    let samples = samples(data, options.layer, &projection);
+    if samples.is_empty() {
+        return Err(format!("No Data in the {} Layer", options.layer));
+    }
+    log::debug!("Interpolating {} Samples", samples.len());
+    let estimator = Estimator::new(samples, &options.interpolation)?;
+
+    // Cells are square at the centre of the grid, ignoring slivers of a cell at the edges
+    let cell =
+        projection.unproject(coord! { x: options.cell_size, y: options.cell_size }) - rect.center();
+    let cells = |size: f64, cell: f64| ((size / cell - 1e-3).ceil() as usize).max(1);
+    let (width, height) = (cells(rect.width(), cell.x), cells(rect.height(), cell.y));
+    if width.saturating_mul(height) > MAX_CELLS {
+        return Err(String::from("Too Many Cells, use a Larger Cell Size"));
+    }
+    if let Estimator::Kriging { samples, .. } = &estimator {
+        let work = (samples.len() as u64)
+            .pow(2)
+            .saturating_mul((width * height) as u64);
+        if work > MAX_KRIGING_WORK {
+            return Err(String::from(
+                "Too Many Samples and Cells for Kriging, use a Larger Cell Size",
+            ));
+        }
+    }
+    let (west, north) = (rect.min().x, rect.max().y);
+    log::debug!("Grid: {} x {}", width, height);
+
+    log::info!("Interpolating Grid");
+    let values = (0..width * height)
+        .into_par_iter()
+        .map(|i| {
+            let (column, row) = (i % width, i / width);
+            let centre = coord! {
+                x: west + (column as f64 + 0.5) * cell.x,
+                y: north - (row as f64 + 0.5) * cell.y,
+            };
+            match area {
+                Some(area) if !area.contains(&Point::from(centre)) => f64::NAN,
+                _ => estimator.estimate(projection.project(centre)),
+            }
+        })
+        .collect();
+
+    Ok(Grid {
+        width,
+        height,
+        bounds: (
+            west,
+            north - height as f64 * cell.y,
+            west + width as f64 * cell.x,
+            north,
+        ),
+        values,
+    })
+}
+
+/// Writes a grid as a single band 32-bit float GeoTIFF in WGS84.
+///
+/// Cells outside the survey area are `NaN`, which is marked as no data.
+pub fn write_geotiff<W: Write>(grid: &Grid, mut writer: W) -> std::io::Result<()> {
+    const SHORT: u16 = 3;
+    const LONG: u16 = 4;
+    const ASCII: u16 = 2;
+    const DOUBLE: u16 = 12;
+
+    let (scale_x, scale_y) = grid.cell();
+    let (west, _, _, north) = grid.bounds;
+    let shorts = |v: &[u16]| v.iter().flat_map(|v| v.to_le_bytes()).collect::<Vec<u8>>();
+    let doubles = |v: &[f64]| v.iter().flat_map(|v| v.to_le_bytes()).collect::<Vec<u8>>();
+    let long = |v: u32| v.to_le_bytes().to_vec();
+    let image_size = (grid.width * grid.height * 4) as u32;
+    let geo_keys = [
+        // Version 1.1.0 with 3 keys
+        1, 1, 0, 3, // Geographic model
+        1024, 0, 1, 2, // Cells are areas
+        1025, 0, 1, 1, // WGS84
+        2048, 0, 1, 4326,
+    ];
+
+    // Tags in ascending order with the strip offset filled in below
+    let mut entries: Vec<(u16, u16, u32, Vec<u8>)> = vec![
+        (256, LONG, 1, long(grid.width as u32)),
+        (257, LONG, 1, long(grid.height as u32)),
+        (258, SHORT, 1, shorts(&[32])),
+        (259, SHORT, 1, shorts(&[1])),
+        (262, SHORT, 1, shorts(&[1])),
+        (273, LONG, 1, long(0)),
+        (277, SHORT, 1, shorts(&[1])),
+        (278, LONG, 1, long(grid.height as u32)),
+        (279, LONG, 1, long(image_size)),
+        (284, SHORT, 1, shorts(&[1])),
+        (339, SHORT, 1, shorts(&[3])),
+        (33550, DOUBLE, 3, doubles(&[scale_x, scale_y, 0.0])),
+        (
+            33922,
+            DOUBLE,
+            6,
+            doubles(&[0.0, 0.0, 0.0, west, north, 0.0]),
+        ),
+        (34735, SHORT, geo_keys.len() as u32, shorts(&geo_keys)),
+        (42113, ASCII, 4, b"nan\0".to_vec()),
+    ];
+
+    let directory_size = 2 + entries.len() * 12 + 4;
+    let extra_size = entries
+        .iter()
+        .filter(|e| e.3.len() > 4)
+        .map(|e| e.3.len() + e.3.len() % 2)
+        .sum::<usize>();
+    let image_offset = 8 + directory_size + extra_size;
+    entries[5].3 = long(image_offset as u32);
+
+    let mut header = vec![b'I', b'I', 42, 0];
+    header.extend(8u32.to_le_bytes());
+    let mut directory = (entries.len() as u16).to_le_bytes().to_vec();
+    let mut extra = vec![];
+    for (tag, kind, count, mut value) in entries {
+        directory.extend(tag.to_le_bytes());
+        directory.extend(kind.to_le_bytes());
+        directory.extend(count.to_le_bytes());
+        if value.len() > 4 {
+            directory.extend(((8 + directory_size + extra.len()) as u32).to_le_bytes());
+            if value.len() % 2 == 1 {
+                value.push(0);
+            }
+            extra.extend(value);
+        } else {
+            value.resize(4, 0);
+            directory.extend(value);
+        }
+    }
+    directory.extend(0u32.to_le_bytes());
+
+    writer.write_all(&header)?;
+    writer.write_all(&directory)?;
+    writer.write_all(&extra)?;
+    for v in &grid.values {
+        writer.write_all(&(*v as f32).to_le_bytes())?;
+    }
+    writer.flush()
+}
+
+/// Gets the colour of a temperature between the coldest and hottest temperature.
+fn colour(value: f64, min: f64, max: f64) -> [u8; 4] {
+    if value.is_nan() {
+        return [0, 0, 0, 0];
+    }
+    let t = match max - min {
+        range if range > 0.0 => ((value - min) / range).clamp(0.0, 1.0),
+        _ => 0.5,
+    } * (RAMP.len() - 1) as f64;
+    let i = (t.floor() as usize).min(RAMP.len() - 2);
+    let f = t - i as f64;
+    let channel = |c: usize| {
+        (RAMP[i][c] as f64 + (RAMP[i + 1][c] as f64 - RAMP[i][c] as f64) * f).round() as u8
+    };
+    [channel(0), channel(1), channel(2), 255]
+}
+
+/// Writes a grid as a coloured PNG image, returning where it goes on the map.
+///
+/// Cells outside the survey area are transparent.
+pub fn write_overlay<W: Write>(grid: &Grid, writer: W) -> Result<Overlay, String> {
+    let (min, max) = grid.range().ok_or(String::from("Grid is Empty"))?;
+    let pixels = grid
+        .values
+        .iter()
+        .flat_map(|&v| colour(v, min, max))
+        .collect::<Vec<_>>();
+
+    let mut encoder = png::Encoder::new(writer, grid.width as u32, grid.height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer
+        .write_image_data(&pixels)
+        .map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+
+    Ok(Overlay {
+        bounds: grid.bounds,
+        min,
+        max,
+    })
+}
+
+/// Reads the survey area the grid is clipped to.
+pub(crate) fn read_area(
+    area: Option<geojson::Geometry>,
+) -> Result<Option<MultiPolygon<f64>>, String> {
+    let area = match area {
+        Some(v) => Geometry::try_from(v.value).map_err(|e| e.to_string())?,
+        None => return Ok(None),
+    };
+    match area {
+        Geometry::Polygon(p) => Ok(Some(MultiPolygon(vec![p]))),
+        Geometry::MultiPolygon(p) => Ok(Some(p)),
+        _ => Err(String::from("Survey Area must be a Polygon")),
+    }
+}
+
+/// Export the temperatures of a layer interpolated onto a grid as a GeoTIFF.
+#[tauri::command(async)]
+pub fn export_temperature_geotiff(
+    export_path: PathBuf,
+    data: BoatData,
+    area: Option<geojson::Geometry>,
+    options: Option<RasterOptions>,
+) -> Result<(), String> {
+    log::debug!("Exporting GeoTIFF to: {}", export_path.display());
+    let grid = interpolate(
+        &data,
+        read_area(area)?.as_ref(),
+        &options.unwrap_or_default(),
+    )?;
+    let file = File::create(export_path).map_err(|e| e.to_string())?;
+    write_geotiff(&grid, std::io::BufWriter::new(file)).map_err(|e| e.to_string())
+}
+
+/// Export the temperatures of a layer interpolated onto a grid as a coloured PNG image.
+///
+/// Returns the bounds to place the image on the map and the temperatures of the colours.
+#[tauri::command(async)]
+pub fn export_temperature_overlay(
+    export_path: PathBuf,
+    data: BoatData,
+    area: Option<geojson::Geometry>,
+    options: Option<RasterOptions>,
+) -> Result<Overlay, String> {
+    log::debug!("Exporting Overlay to: {}", export_path.display());
+    let grid = interpolate(
+        &data,
+        read_area(area)?.as_ref(),
+        &options.unwrap_or_default(),
+    )?;
+    let file = File::create(export_path).map_err(|e| e.to_string())?;
+    write_overlay(&grid, std::io::BufWriter::new(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::polygon;
+
+    use super::*;
+    use crate::{data::BoatDataFeature, path::local::lake};
+
+    const TIME: &str = "2024-01-01T00:00:00Z";
+
+    /// Builds surface data from the position in metres around a point on a lake and temperature
+    /// of each sample.
+    fn data(samples: &[(f64, f64, f64)]) -> (BoatData, LocalProjection) {
+        let projection = lake();
+        let features = samples
+            .iter()
+            .map(|&(x, y, temperature)| {
+                let place = Point::from(projection.unproject(coord! { x: x, y: y }));
+                BoatDataFeature::sample(place, temperature, 0.5, Layer::Surface, TIME)
+            })
+            .collect();
+        (BoatData::from_samples(features), projection)
+    }
+
+    fn corners() -> (BoatData, LocalProjection) {
+        data(&[
+            (0.0, 0.0, 20.0),
+            (100.0, 0.0, 24.0),
+            (0.0, 100.0, 24.0),
+            (100.0, 100.0, 28.0),
+            (100.0, 100.0, 30.0),
+        ])
+    }
+
+    #[test]
+    fn interpolates_inverse_distance() {
+        let (data, _) = corners();
+        let grid = interpolate(&data, None, &RasterOptions::default()).unwrap();
+
+        assert_eq!((grid.width(), grid.height()), (10, 10));
+        // Samples in the same place are averaged
+        assert_eq!(grid.range().map(|r| r.0 > 20.0 && r.1 < 29.0), Some(true));
+        let centre = (grid.value(4, 4).unwrap() + grid.value(5, 5).unwrap()) / 2.0;
+        assert!((centre - 24.0).abs() < 0.5, "{}", centre);
+        assert!(grid.value(9, 0).unwrap() > 27.0);
+        assert!(grid.value(0, 9).unwrap() < 21.0);
+        assert_eq!(grid.value(10, 0), None);
+    }
+
+    #[test]
+    fn kriges_exact_samples() {
+        let (data, _) = corners();
+        let options = RasterOptions::new(
+            Layer::Surface,
+            100.0 / 3.0,
+            Interpolation::Kriging {
+                range: None,
+                sill: None,
+                nugget: None,
+            },
+        );
+        let grid = interpolate(&data, None, &options).unwrap();
+        assert_eq!((grid.width(), grid.height()), (3, 3));
+
+        // Kriging without a nugget is symmetric in the samples
+        let middle = grid.value(1, 1).unwrap();
+        assert!((middle - 24.25).abs() < 1e-6, "{}", middle);
+        assert!(grid.value(2, 0).unwrap() > middle);
+
+        let lu = Lu::new(vec![2.0, 1.0, 1.0, 3.0], 2).unwrap();
+        let x = lu.solve(&[3.0, 5.0]);
+        assert!((x[0] - 0.8).abs() < 1e-12 && (x[1] - 1.4).abs() < 1e-12);
+        assert!(Lu::new(vec![1.0, 2.0, 2.0, 4.0], 2).is_none());
+    }
+
+    #[test]
+    fn limits_kriging_work() {
+        let samples = (0..60)
+            .map(|i| {
+                (
+                    f64::from(i % 8) * 10.0,
+                    f64::from(i / 8) * 10.0,
+                    f64::from(20 + i % 7),
+                )
+            })
+            .collect::<Vec<_>>();
+        let (data, _) = data(&samples);
+        let kriging = Interpolation::Kriging {
+            range: None,
+            sill: None,
+            nugget: None,
+        };
+        let options = RasterOptions::new(Layer::Surface, 10.0, kriging);
+        assert!(interpolate(&data, None, &options).is_ok());
+        // Fewer cells than the limit on cells, but too many to krige with this many samples
+        let options = RasterOptions::new(Layer::Surface, 0.04, kriging);
+        assert!(interpolate(&data, None, &options).is_err());
+    }
+
+    #[test]
+    fn checks_options_of_constant_samples() {
+        let (data, _) = data(&[(0.0, 0.0, 20.0), (100.0, 100.0, 20.0)]);
+        let grid = interpolate(&data, None, &RasterOptions::default()).unwrap();
+        assert_eq!(grid.range(), Some((20.0, 20.0)));
+
+        for interpolation in [
+            Interpolation::InverseDistance {
+                power: -1.0,
+                radius: None,
+            },
+            Interpolation::InverseDistance {
+                power: 2.0,
+                radius: Some(0.0),
+            },
+            Interpolation::Kriging {
+                range: Some(f64::NAN),
+                sill: None,
+                nugget: None,
+            },
+            Interpolation::Kriging {
+                range: None,
+                sill: Some(1.0),
+                nugget: Some(2.0),
+            },
+        ] {
+            let options = RasterOptions::new(Layer::Surface, 10.0, interpolation);
+            assert!(interpolate(&data, None, &options).is_err());
+        }
+    }
+
+    #[test]
+    fn clips_to_survey_area() {
+        let (data, projection) = corners();
+        let c = |x: f64, y: f64| projection.unproject(coord! { x: x, y: y });
+        let area = MultiPolygon(vec![polygon![c(0.0, 0.0), c(100.0, 0.0), c(0.0, 100.0)]]);
+        let grid = interpolate(&data, Some(&area), &RasterOptions::default()).unwrap();
+
+        assert!(grid.value(0, 9).is_some());
+        assert!(grid.value(9, 0).is_none());
+
+        let mut image = vec![];
+        let overlay = write_overlay(&grid, &mut image).unwrap();
+        assert_eq!(&image[1..4], b"PNG");
+        assert_eq!(overlay.bounds, grid.bounds());
+
+        let options =
+            RasterOptions::new(Layer::SeaBed, 10.0, RasterOptions::default().interpolation);
+        assert!(interpolate(&data, None, &options).is_err());
+        let options =
+            RasterOptions::new(Layer::Surface, 0.0, RasterOptions::default().interpolation);
+        assert!(interpolate(&data, None, &options).is_err());
+    }
+
+    #[test]
+    fn writes_geotiff() {
+        let (data, _) = corners();
+        let grid = interpolate(&data, None, &RasterOptions::default()).unwrap();
+        let mut tiff = vec![];
+        write_geotiff(&grid, &mut tiff).unwrap();
+
+        assert_eq!(&tiff[..4], b"II*\0");
+        let u16_at = |i: usize| u16::from_le_bytes([tiff[i], tiff[i + 1]]);
+        let u32_at = |i: usize| u32::from_le_bytes(tiff[i..i + 4].try_into().unwrap());
+        assert_eq!(u16_at(8), 15);
+
+        // The strip of cells fills the end of the file
+        let strip = (0..15)
+            .map(|i| 10 + i * 12)
+            .find(|&e| u16_at(e) == 273)
+            .unwrap();
+        let offset = u32_at(strip + 8) as usize;
+        assert_eq!(tiff.len() - offset, 10 * 10 * 4);
+        let first = f32::from_le_bytes(tiff[offset..offset + 4].try_into().unwrap());
+        assert_eq!(first, grid.value(0, 0).unwrap() as f32);
+
+        // The tie point places the first cell at the north west corner
+        let tie = (0..15)
+            .map(|i| 10 + i * 12)
+            .find(|&e| u16_at(e) == 33922)
+            .unwrap();
+        let at = u32_at(tie + 8) as usize + 24;
+        let west = f64::from_le_bytes(tiff[at..at + 8].try_into().unwrap());
+        assert_eq!(west, grid.bounds().0);
+    }
+}
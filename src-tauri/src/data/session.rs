@@ -0,0 +1,324 @@
+//! Survey sessions grouping the data collected by the boat in each run.
+//!
+//! A session is started when a boat connects and again whenever a path is sent to it, and the
+//! samples received are tagged with the session of the boat they came from.
+
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::BoatData;
+
+/// A run of the boat that collected data.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    /// The unique id of the session.
+    id: String,
+    /// The name of the session shown to the user.
+    name: String,
+    /// The name of the serial port connected to the boat.
+    boat: String,
+    /// The SHA-256 hash of the encoded path the boat followed, if one was sent.
+    #[serde(default)]
+    path_hash: Option<String>,
+    /// When the session started.
+    start: DateTime<Utc>,
+    /// When the last data of the session was collected.
+    #[serde(default)]
+    end: Option<DateTime<Utc>>,
+    /// Notes about the session.
+    #[serde(default)]
+    notes: String,
+}
+
+impl Session {
+    /// Creates a new session starting at a time, named after the boat and the time.
+    pub fn new(boat: &str, path_hash: Option<String>, start: DateTime<Utc>) -> Self {
+        Self {
+            id: format!("{}@{}", boat, start.format("%Y%m%dT%H%M%S%.3fZ")),
+            name: format!("{} {}", boat, start.format("%Y-%m-%d %H:%M")),
+            boat: boat.to_string(),
+            path_hash,
+            start,
+            end: None,
+            notes: String::new(),
+        }
+    }
+
+    /// Gets the unique id of the session.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Gets the name of the session shown to the user.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Gets the name of the serial port connected to the boat.
+    pub fn boat(&self) -> &str {
+        &self.boat
+    }
+
+    /// Gets the hash of the encoded path the boat followed, if one was sent.
+    pub fn path_hash(&self) -> Option<&str> {
+        self.path_hash.as_deref()
+    }
+
+    /// Gets when the session started.
+    pub fn start(&self) -> DateTime<Utc> {
+        self.start
+    }
+
+    /// Gets when the last data of the session was collected.
+    pub fn end(&self) -> Option<DateTime<Utc>> {
+        self.end
+    }
+
+    /// Gets the notes about the session.
+    pub fn notes(&self) -> &str {
+        &self.notes
+    }
+
+    /// Widens the session to cover another copy of it, such as one received later.
+    pub(crate) fn extend(&mut self, other: &Session) {
+        self.start = self.start.min(other.start);
+        self.end = self.end.max(other.end);
+        if self.path_hash.is_none() {
+            self.path_hash.clone_from(&other.path_hash);
+        }
+    }
+}
+
+/// Adds a session to a list of sessions, extending it if it is already there.
+pub(crate) fn add(sessions: &mut Vec<Session>, session: &Session) {
+    match sessions.iter_mut().find(|s| s.id == session.id) {
+        Some(s) => s.extend(session),
+        None => sessions.push(session.clone()),
+    }
+}
+
+impl BoatData {
+    /// Tags the samples without a session with a session, adding the session to the data.
+    ///
+    /// The end of the session is moved to the latest sample tagged.
+    pub fn tag(&mut self, session: &Session) {
+        let mut session = session.clone();
+        for feature in self.features.iter_mut().filter(|f| f.session.is_none()) {
+            feature.session = Some(session.id.clone());
+            session.end = session.end.max(Some(feature.time));
+        }
+        add(&mut self.sessions, &session);
+    }
+
+    /// Gets the data collected in a session.
+    pub fn select_session(&self, id: &str) -> Result<BoatData, String> {
+        let session = self
+            .sessions
+            .iter()
+            .find(|s| s.id == id)
+            .ok_or(format!("Unknown Session: {id}"))?;
+        Ok(BoatData {
+            version: self.version.clone(),
+            migrated_from: self.migrated_from.clone(),
+            sessions: vec![session.clone()],
+            features: self
+                .features
+                .iter()
+                .filter(|f| f.session() == Some(id))
+                .cloned()
+                .collect(),
+        })
+    }
+}
+
+/// A session and the number of samples collected in it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SessionSummary {
+    /// The session.
+    #[serde(flatten)]
+    session: Session,
+    /// The number of samples collected in the session.
+    samples: usize,
+}
+
+impl SessionSummary {
+    /// Gets the session.
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    /// Gets the number of samples collected in the session.
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+}
+
+/// List the sessions of boat data, newest first.
+#[tauri::command]
+pub fn list_sessions(data: BoatData) -> Vec<SessionSummary> {
+    log::debug!("Listing Sessions");
+    let mut summaries = data
+        .sessions()
+        .iter()
+        .map(|s| SessionSummary {
+            session: s.clone(),
+            samples: data
+                .features()
+                .iter()
+                .filter(|f| f.session() == Some(s.id()))
+                .count(),
+        })
+        .collect::<Vec<_>>();
+    summaries.sort_by_key(|v| std::cmp::Reverse(v.session.start));
+    summaries
+}
+
+/// Rename a session, optionally changing its notes.
+#[tauri::command]
+pub fn rename_session(
+    mut data: BoatData,
+    id: String,
+    name: String,
+    notes: Option<String>,
+) -> Result<BoatData, String> {
+    log::debug!("Renaming Session: {}", id);
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(String::from("Session Name must not be Empty"));
+    }
+    let session = data
+        .sessions
+        .iter_mut()
+        .find(|s| s.id == id)
+        .ok_or(format!("Unknown Session: {id}"))?;
+    session.name = name.to_string();
+    if let Some(notes) = notes {
+        session.notes = notes;
+    }
+    Ok(data)
+}
+
+/// Export the data collected in a session to the file system.
+///
+/// The data is written the same way as `export_data`.
+#[tauri::command]
+pub fn export_session(
+    export_path: PathBuf,
+    data: BoatData,
+    id: String,
+    crs: Option<String>,
+) -> Result<(), String> {
+    log::debug!("Exporting Session: {}", id);
+    super::export_data(export_path, data.select_session(&id)?, crs)
+}
+
+/// Delete a session and the data collected in it.
+#[tauri::command]
+pub fn delete_session(mut data: BoatData, id: String) -> Result<BoatData, String> {
+    log::debug!("Deleting Session: {}", id);
+    let count = data.sessions.len();
+    data.sessions.retain(|s| s.id != id);
+    if data.sessions.len() == count {
+        return Err(format!("Unknown Session: {id}"));
+    }
+    data.features.retain(|f| f.session() != Some(&id));
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::point;
+
+    use super::*;
+    use crate::data::{BoatDataFeature, Layer};
+
+    /// Builds boat data with a sample at each time.
+    fn data(times: &[&str]) -> BoatData {
+        BoatData::from_samples(
+            times
+                .iter()
+                .map(|time| {
+                    let place = point!(x: 101.7, y: 3.1);
+                    BoatDataFeature::sample(place, 28.0, 0.5, Layer::Surface, time)
+                })
+                .collect(),
+        )
+    }
+
+    fn time(text: &str) -> DateTime<Utc> {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn tags_samples_with_sessions() {
+        let first = Session::new("COM3", None, time("2024-01-01T00:00:00Z"));
+        let second = Session::new(
+            "COM3",
+            Some(String::from("abc")),
+            time("2024-01-02T00:00:00Z"),
+        );
+        assert_eq!(first.id(), "COM3@20240101T000000.000Z");
+
+        let mut stored = data(&["2024-01-01T00:10:00Z"]);
+        stored.tag(&first);
+        let mut received = data(&["2024-01-01T00:20:00Z"]);
+        received.tag(&first);
+        let mut later = data(&["2024-01-02T00:10:00Z", "2024-01-02T00:30:00Z"]);
+        later.tag(&second);
+
+        let merged = super::super::merge::merge(&stored, [&received, &later], &Default::default())
+            .unwrap()
+            .data()
+            .clone();
+        assert_eq!(merged.sessions().len(), 2);
+        assert_eq!(
+            merged.sessions()[0].end(),
+            Some(time("2024-01-01T00:20:00Z"))
+        );
+
+        // Sessions and their samples are kept when the data is written
+        let merged: BoatData = merged.to_string().parse().unwrap();
+        assert_eq!(merged.sessions()[1].path_hash(), Some("abc"));
+        let sessions = list_sessions(merged.clone());
+        let counts = sessions
+            .iter()
+            .map(|s| (s.session().id(), s.samples()))
+            .collect::<Vec<_>>();
+        assert_eq!(counts, [(second.id(), 2), (first.id(), 2)]);
+        assert_eq!(
+            merged.select_session(first.id()).unwrap().features().len(),
+            2
+        );
+        assert!(merged.select_session("COM4@20240101T000000.000Z").is_err());
+    }
+
+    #[test]
+    fn renames_and_deletes_sessions() {
+        let session = Session::new("COM3", None, time("2024-01-01T00:00:00Z"));
+        let mut tagged = data(&["2024-01-01T00:10:00Z"]);
+        tagged.tag(&session);
+        let mut data = data(&["2024-01-01T00:20:00Z"]);
+        data.features.append(&mut tagged.features);
+        data.sessions = tagged.sessions;
+
+        let id = session.id().to_string();
+        let renamed = rename_session(
+            data.clone(),
+            id.clone(),
+            String::from(" Morning Run "),
+            Some(String::from("Windy")),
+        )
+        .unwrap();
+        assert_eq!(renamed.sessions()[0].name(), "Morning Run");
+        assert_eq!(renamed.sessions()[0].notes(), "Windy");
+        assert!(rename_session(data.clone(), id.clone(), String::from(" "), None).is_err());
+
+        let deleted = delete_session(data.clone(), id.clone()).unwrap();
+        assert!(deleted.sessions().is_empty());
+        assert_eq!(deleted.features().len(), 1);
+        assert_eq!(deleted.features()[0].session(), None);
+        assert!(delete_session(deleted, id).is_err());
+    }
+}
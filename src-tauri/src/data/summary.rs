@@ -0,0 +1,428 @@
+//! Statistics of the temperatures collected by the boat.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{BoatData, BoatDataFeature, Layer};
+
+/// How the data is grouped when it is summarised.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SummaryOptions {
+    /// The height of each depth bin in metres.
+    depth_bin: f64,
+    /// The length of each time window in seconds.
+    time_window: i64,
+    /// The percentiles to calculate from 0 to 100.
+    percentiles: Vec<f64>,
+}
+
+impl SummaryOptions {
+    /// Creates new options.
+    pub fn new(depth_bin: f64, time_window: i64, percentiles: Vec<f64>) -> Self {
+        Self {
+            depth_bin,
+            time_window,
+            percentiles,
+        }
+    }
+}
+
+impl Default for SummaryOptions {
+    fn default() -> Self {
+        Self {
+            depth_bin: 1.0,
+            time_window: 3600,
+            percentiles: vec![5.0, 25.0, 75.0, 95.0],
+        }
+    }
+}
+
+/// Statistics of a group of temperatures.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Statistics {
+    /// The number of temperatures.
+    count: usize,
+    /// The lowest temperature.
+    min: f64,
+    /// The highest temperature.
+    max: f64,
+    /// The mean temperature.
+    mean: f64,
+    /// The median temperature.
+    median: f64,
+    /// The sample standard deviation, 0 for a single temperature.
+    std_dev: f64,
+    /// Each percentile asked for and its temperature.
+    percentiles: Vec<(f64, f64)>,
+}
+
+impl Statistics {
+    /// Calculates the statistics of some temperatures, returning `None` if there are none.
+    fn new(mut values: Vec<f64>, percentiles: &[f64]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_by(f64::total_cmp);
+        let count = values.len();
+        let mean = values.iter().sum::<f64>() / count as f64;
+        let variance = match count {
+            1 => 0.0,
+            _ => values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (count - 1) as f64,
+        };
+        Some(Self {
+            count,
+            min: values[0],
+            max: values[count - 1],
+            mean,
+            median: percentile(&values, 50.0),
+            std_dev: variance.sqrt(),
+            percentiles: percentiles
+                .iter()
+                .map(|&p| (p, percentile(&values, p)))
+                .collect(),
+        })
+    }
+
+    /// Gets the number of temperatures.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Gets the lowest temperature.
+    pub fn min(&self) -> f64 {
+        self.min
+    }
+
+    /// Gets the highest temperature.
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    /// Gets the mean temperature.
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Gets the median temperature.
+    pub fn median(&self) -> f64 {
+        self.median
+    }
+
+    /// Gets the sample standard deviation.
+    pub fn std_dev(&self) -> f64 {
+        self.std_dev
+    }
+
+    /// Gets each percentile asked for and its temperature.
+    pub fn percentiles(&self) -> &[(f64, f64)] {
+        &self.percentiles
+    }
+}
+
+/// Interpolates a percentile of sorted values the same way as spreadsheets.
+fn percentile(sorted: &[f64], percentile: f64) -> f64 {
+    let rank = percentile / 100.0 * (sorted.len() - 1) as f64;
+    let (below, above) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[below] + (sorted[above] - sorted[below]) * (rank - below as f64)
+}
+
+/// Statistics of the temperatures in one layer.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LayerSummary {
+    /// The layer the temperatures were collected in.
+    layer: Layer,
+    /// The statistics of the temperatures.
+    statistics: Statistics,
+}
+
+/// Statistics of the temperatures in a range of depths.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DepthSummary {
+    /// The shallowest depth of the bin in metres.
+    min_depth: f64,
+    /// The depth the bin goes down to in metres, not including it.
+    max_depth: f64,
+    /// The statistics of the temperatures.
+    statistics: Statistics,
+}
+
+/// Statistics of the temperatures in a window of time.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimeSummary {
+    /// The start of the window.
+    start: DateTime<Utc>,
+    /// The end of the window, not including it.
+    end: DateTime<Utc>,
+    /// The statistics of the temperatures.
+    statistics: Statistics,
+}
+
+/// Statistics of all of the data collected by the boat.
+#[derive(Debug, Clone, Serialize)]
+pub struct DataSummary {
+    /// The statistics of every temperature, if there are any.
+    overall: Option<Statistics>,
+    /// The statistics of each layer with data.
+    layers: Vec<LayerSummary>,
+    /// The statistics of each depth bin with data, shallowest first.
+    depths: Vec<DepthSummary>,
+    /// The statistics of each time window with data, earliest first.
+    windows: Vec<TimeSummary>,
+    /// The bounding box of the data as west, south, east and north.
+    bounds: Option<(f64, f64, f64, f64)>,
+    /// The time of the first data.
+    start: Option<DateTime<Utc>>,
+    /// The time of the last data.
+    end: Option<DateTime<Utc>>,
+}
+
+impl DataSummary {
+    /// Gets the statistics of every temperature.
+    pub fn overall(&self) -> Option<&Statistics> {
+        self.overall.as_ref()
+    }
+
+    /// Gets the statistics of each layer with data.
+    pub fn layers(&self) -> &[LayerSummary] {
+        &self.layers
+    }
+
+    /// Gets the statistics of each depth bin with data.
+    pub fn depths(&self) -> &[DepthSummary] {
+        &self.depths
+    }
+
+    /// Gets the statistics of each time window with data.
+    pub fn windows(&self) -> &[TimeSummary] {
+        &self.windows
+    }
+
+    /// Gets the bounding box of the data as west, south, east and north.
+    pub fn bounds(&self) -> Option<(f64, f64, f64, f64)> {
+        self.bounds
+    }
+
+    /// Gets the time of the first data.
+    pub fn start(&self) -> Option<DateTime<Utc>> {
+        self.start
+    }
+
+    /// Gets the time of the last data.
+    pub fn end(&self) -> Option<DateTime<Utc>> {
+        self.end
+    }
+}
+
+/// Groups the temperatures of features by a bin, sorted by the bin.
+fn group_by<F: Fn(&BoatDataFeature) -> i64>(
+    features: &[BoatDataFeature],
+    bin: F,
+) -> BTreeMap<i64, Vec<f64>> {
+    let mut groups: BTreeMap<i64, Vec<f64>> = BTreeMap::new();
+    for feature in features {
+        groups
+            .entry(bin(feature))
+            .or_default()
+            .push(feature.temperature());
+    }
+    groups
+}
+
+/// Summarises the temperatures collected by the boat.
+pub fn summarise(data: &BoatData, options: &SummaryOptions) -> Result<DataSummary, String> {
+    if !(options.depth_bin.is_finite() && options.depth_bin > 0.0) {
+        return Err(String::from("Depth Bin must be Positive"));
+    }
+    if options.time_window <= 0 {
+        return Err(String::from("Time Window must be Positive"));
+    }
+    if options
+        .percentiles
+        .iter()
+        .any(|p| !(0.0..=100.0).contains(p))
+    {
+        return Err(String::from("Percentiles must be from 0 to 100"));
+    }
+    let features = data.features();
+    let temperatures = |f: &[BoatDataFeature]| f.iter().map(|f| f.temperature()).collect();
+    let statistics = |values| Statistics::new(values, &options.percentiles);
+
+    log::info!("Summarising Layers");
+    let layers = [Layer::Surface, Layer::Middle, Layer::SeaBed]
+        .into_iter()
+        .filter_map(|layer| {
+            let values = features
+                .iter()
+                .filter(|f| f.layer() == layer)
+                .map(|f| f.temperature())
+                .collect();
+            Some(LayerSummary {
+                layer,
+                statistics: statistics(values)?,
+            })
+        })
+        .collect();
+
+    log::info!("Summarising Depths");
+    let depths = group_by(features, |f| (f.depth() / options.depth_bin).floor() as i64)
+        .into_iter()
+        .filter_map(|(bin, values)| {
+            Some(DepthSummary {
+                min_depth: bin as f64 * options.depth_bin,
+                max_depth: (bin + 1) as f64 * options.depth_bin,
+                statistics: statistics(values)?,
+            })
+        })
+        .collect();
+
+    log::info!("Summarising Time Windows");
+    let windows = group_by(features, |f| {
+        f.time().timestamp().div_euclid(options.time_window)
+    })
+    .into_iter()
+    .filter_map(|(window, values)| {
+        let time = |w: i64| Utc.timestamp_opt(w * options.time_window, 0).single();
+        Some(TimeSummary {
+            start: time(window)?,
+            end: time(window + 1)?,
+            statistics: statistics(values)?,
+        })
+    })
+    .collect();
+
+    let bounds = features.iter().map(|f| f.geometry()).fold(None, |b, p| {
+        let (w, s, e, n) = b.unwrap_or((p.x(), p.y(), p.x(), p.y()));
+        Some((w.min(p.x()), s.min(p.y()), e.max(p.x()), n.max(p.y())))
+    });
+
+    Ok(DataSummary {
+        overall: statistics(temperatures(features)),
+        layers,
+        depths,
+        windows,
+        bounds,
+        start: features.iter().map(|f| f.time()).min(),
+        end: features.iter().map(|f| f.time()).max(),
+    })
+}
+
+/// Calculate statistics of the temperatures collected by the boat.
+#[tauri::command]
+pub fn data_summary(
+    data: BoatData,
+    options: Option<SummaryOptions>,
+) -> Result<DataSummary, String> {
+    log::info!("Summarising Boat Data");
+    summarise(&data, &options.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::point;
+
+    use super::*;
+
+    /// The temperature, depth, layer, time and position of a feature.
+    type Sample<'a> = (f64, f64, Layer, &'a str, (f64, f64));
+
+    /// Builds boat data from some features.
+    fn data(features: &[Sample]) -> BoatData {
+        BoatData::from_samples(
+            features
+                .iter()
+                .map(|&(temperature, depth, layer, time, (x, y))| {
+                    BoatDataFeature::sample(point!(x: x, y: y), temperature, depth, layer, time)
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn calculates_statistics() {
+        let statistics = Statistics::new(vec![4.0, 1.0, 3.0, 2.0], &[25.0, 100.0]).unwrap();
+        assert_eq!((statistics.min(), statistics.max()), (1.0, 4.0));
+        assert_eq!(statistics.mean(), 2.5);
+        assert_eq!(statistics.median(), 2.5);
+        assert!((statistics.std_dev() - 1.290_994).abs() < 1e-6);
+        assert_eq!(statistics.percentiles(), [(25.0, 1.75), (100.0, 4.0)]);
+
+        let single = Statistics::new(vec![7.0], &[5.0]).unwrap();
+        assert_eq!((single.median(), single.std_dev()), (7.0, 0.0));
+        assert_eq!(Statistics::new(vec![], &[]), None);
+    }
+
+    #[test]
+    fn groups_data() {
+        let data = data(&[
+            (
+                28.0,
+                0.2,
+                Layer::Surface,
+                "2024-01-01T10:05:00Z",
+                (101.7, 3.1),
+            ),
+            (
+                27.0,
+                0.4,
+                Layer::Surface,
+                "2024-01-01T10:55:00Z",
+                (101.8, 3.0),
+            ),
+            (
+                25.0,
+                3.5,
+                Layer::Middle,
+                "2024-01-01T11:10:00Z",
+                (101.75, 3.2),
+            ),
+            (
+                22.0,
+                8.0,
+                Layer::SeaBed,
+                "2024-01-01T11:20:00Z",
+                (101.72, 3.15),
+            ),
+        ]);
+        let summary = summarise(&data, &SummaryOptions::default()).unwrap();
+
+        assert_eq!(summary.overall().unwrap().count(), 4);
+        let layers = summary.layers();
+        assert_eq!(layers.len(), 3);
+        assert_eq!(layers[0].layer, Layer::Surface);
+        assert_eq!(layers[0].statistics.mean(), 27.5);
+
+        let depths = summary
+            .depths()
+            .iter()
+            .map(|d| (d.min_depth, d.statistics.count()))
+            .collect::<Vec<_>>();
+        assert_eq!(depths, [(0.0, 2), (3.0, 1), (8.0, 1)]);
+
+        let windows = summary.windows();
+        assert_eq!(windows.len(), 2);
+        assert_eq!(windows[0].start.to_rfc3339(), "2024-01-01T10:00:00+00:00");
+        assert_eq!(windows[1].statistics.mean(), 23.5);
+
+        assert_eq!(summary.bounds(), Some((101.7, 3.0, 101.8, 3.2)));
+        assert_eq!(
+            summary.end().unwrap() - summary.start().unwrap(),
+            chrono::Duration::minutes(75)
+        );
+    }
+
+    #[test]
+    fn summarises_empty_data() {
+        let summary = summarise(&BoatData::default(), &SummaryOptions::default()).unwrap();
+        assert!(summary.overall().is_none());
+        assert!(summary.layers().is_empty());
+        assert_eq!(summary.bounds(), None);
+
+        let options = SummaryOptions::new(0.0, 3600, vec![]);
+        assert!(summarise(&BoatData::default(), &options).is_err());
+        let options = SummaryOptions::new(1.0, 3600, vec![101.0]);
+        assert!(summarise(&BoatData::default(), &options).is_err());
+    }
+}
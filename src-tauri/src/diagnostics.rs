@@ -0,0 +1,727 @@
+//! Finding every problem in path and boat data GeoJSON files.
+//!
+//! Parsing stops at the first problem and serde does not say where it is, so files that fail to
+//! parse are checked again as plain JSON. Each value is read on its own with the types the file is
+//! read into, and each problem is reported with the JSON pointer and the line and column of the
+//! value, the value found and what the deserializer expected instead.
+
+use std::{collections::HashMap, fmt::Display, path::PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use tauri::api::file;
+
+use crate::{
+    crs::Crs,
+    data::{session::Session, Layer},
+    migrate,
+    path::sampling::SamplingInstructions,
+};
+
+/// A problem found in a GeoJSON file.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    /// The JSON pointer to the value, or to its parent object if the value is missing.
+    pointer: String,
+    /// The line of the value starting from 1.
+    line: usize,
+    /// The column of the value in characters starting from 1.
+    column: usize,
+    /// The value in the file, if there is one.
+    found: Option<Value>,
+    /// What the value should have been.
+    expected: String,
+}
+
+impl Diagnostic {
+    /// Gets the JSON pointer to the value.
+    pub fn pointer(&self) -> &str {
+        &self.pointer
+    }
+
+    /// Gets the line of the value.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Gets the column of the value.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    /// Gets the value in the file.
+    pub fn found(&self) -> Option<&Value> {
+        self.found.as_ref()
+    }
+
+    /// Gets what the value should have been.
+    pub fn expected(&self) -> &str {
+        &self.expected
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Line {}, Column {}", self.line, self.column)?;
+        if !self.pointer.is_empty() {
+            write!(f, " ({})", self.pointer)?;
+        }
+        write!(f, ": expected {}", self.expected)?;
+        match &self.found {
+            Some(found) => write!(f, ", found {}", found),
+            None => write!(f, ", found nothing"),
+        }
+    }
+}
+
+/// Finds where each value starts in JSON text, keyed by its JSON pointer.
+///
+/// The text must already be known to be valid JSON.
+struct Locator<'a> {
+    text: &'a str,
+    position: usize,
+    starts: HashMap<String, usize>,
+}
+
+impl<'a> Locator<'a> {
+    fn locate(text: &'a str) -> HashMap<String, usize> {
+        let mut locator = Self {
+            text,
+            position: 0,
+            starts: HashMap::new(),
+        };
+        locator.value(String::new());
+        locator.starts
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.position += 1;
+        }
+    }
+
+    /// Skips a string, returning its contents.
+    fn string(&mut self) -> String {
+        let start = self.position;
+        self.position += 1;
+        while let Some(b) = self.peek() {
+            self.position += if b == b'\\' { 2 } else { 1 };
+            if b == b'"' {
+                break;
+            }
+        }
+        serde_json::from_str(&self.text[start..self.position]).unwrap_or_default()
+    }
+
+    fn value(&mut self, pointer: String) {
+        self.skip_whitespace();
+        self.starts.insert(pointer.clone(), self.position);
+        match self.peek() {
+            Some(b'{') => {
+                self.position += 1;
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b'"') => {
+                            let key = self.string();
+                            self.skip_whitespace();
+                            // Skipping the colon
+                            self.position += 1;
+                            self.value(format!("{}/{}", pointer, escape(&key)));
+                        }
+                        Some(b',') => self.position += 1,
+                        Some(_) => {
+                            self.position += 1;
+                            break;
+                        }
+                        None => break,
+                    }
+                }
+            }
+            Some(b'[') => {
+                self.position += 1;
+                let mut index = 0;
+                loop {
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b']') => {
+                            self.position += 1;
+                            break;
+                        }
+                        Some(b',') => self.position += 1,
+                        Some(_) => {
+                            self.value(format!("{}/{}", pointer, index));
+                            index += 1;
+                        }
+                        None => break,
+                    }
+                }
+            }
+            Some(b'"') => {
+                self.string();
+            }
+            _ => {
+                while !matches!(
+                    self.peek(),
+                    None | Some(b',' | b']' | b'}' | b' ' | b'\t' | b'\n' | b'\r')
+                ) {
+                    self.position += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Describes what a value should have been from the error reading or validating it.
+fn expected(error: impl Display) -> String {
+    let message = error.to_string();
+    match message.split_once(", expected ") {
+        Some((_, expected)) => String::from(expected),
+        None => format!("a valid value ({message})"),
+    }
+}
+
+/// Escapes a member name for a JSON pointer.
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// Collects the problems in a JSON file.
+struct Checker<'a> {
+    text: &'a str,
+    starts: HashMap<String, usize>,
+    /// Whether positions are longitude and latitude.
+    geographic: bool,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Checker<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            starts: Locator::locate(text),
+            geographic: true,
+            diagnostics: vec![],
+        }
+    }
+
+    /// Gets the line and column of a value, using its closest parent if it is missing.
+    fn line_column(&self, pointer: &str) -> (usize, usize) {
+        let mut pointer = pointer;
+        let offset = loop {
+            match self.starts.get(pointer) {
+                Some(offset) => break *offset,
+                None => match pointer.rfind('/') {
+                    Some(i) => pointer = &pointer[..i],
+                    None => break 0,
+                },
+            }
+        };
+        let before = &self.text[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        (
+            before.matches('\n').count() + 1,
+            before[line_start..].chars().count() + 1,
+        )
+    }
+
+    fn report(&mut self, pointer: &str, found: Option<&Value>, expected: &str) {
+        let (line, column) = self.line_column(pointer);
+        self.diagnostics.push(Diagnostic {
+            pointer: String::from(pointer),
+            line,
+            column,
+            found: found.cloned(),
+            expected: String::from(expected),
+        });
+    }
+
+    /// Checks a member is present and passes a check, reporting it otherwise.
+    fn member<F: Fn(&Value) -> bool>(
+        &mut self,
+        object: &Value,
+        pointer: &str,
+        name: &str,
+        expected: &str,
+        check: F,
+    ) {
+        let pointer = format!("{}/{}", pointer, escape(name));
+        match object.get(name) {
+            Some(v) if check(v) => {}
+            v => self.report(&pointer, v, expected),
+        }
+    }
+
+    /// Checks a member can be read as the type it is read into, reading missing members as null.
+    fn deserializes<T: DeserializeOwned>(&mut self, object: &Value, pointer: &str, name: &str) {
+        let value = object.get(name);
+        if let Err(e) = serde_json::from_value::<T>(value.cloned().unwrap_or(Value::Null)) {
+            let pointer = format!("{}/{}", pointer, escape(name));
+            self.report(&pointer, value, &expected(e));
+        }
+    }
+
+    /// Checks the root is a feature collection with a version, returning its features.
+    fn collection<'v>(
+        &mut self,
+        root: &'v Value,
+        migrations: &migrate::Migrations,
+    ) -> Option<&'v [Value]> {
+        if !root.is_object() {
+            self.report("", Some(root), "a GeoJSON object");
+            return None;
+        }
+        self.member(root, "", "type", "\"FeatureCollection\"", |v| {
+            v == "FeatureCollection"
+        });
+        match root.get("version") {
+            Some(Value::String(v)) => {
+                if let Err(e) = migrations.check(v) {
+                    self.report("/version", root.get("version"), &format!("a version ({e})"));
+                }
+            }
+            v => self.report("/version", v, "a version string"),
+        }
+        match root.get("crs") {
+            Some(Value::Null) | None => {}
+            Some(crs) => match Crs::from_geojson(crs) {
+                Ok(crs) => self.geographic = crs == Crs::Wgs84,
+                Err(e) => self.report("/crs", Some(crs), &format!("a supported CRS ({e})")),
+            },
+        }
+        match root.get("features") {
+            Some(Value::Array(features)) => Some(features),
+            v => {
+                self.report("/features", v, "an array of features");
+                None
+            }
+        }
+    }
+
+    /// Checks a feature and the type of its geometry, returning the geometry.
+    fn feature<'v>(&mut self, feature: &'v Value, pointer: &str) -> Option<&'v Value> {
+        if !feature.is_object() {
+            self.report(pointer, Some(feature), "a feature object");
+            return None;
+        }
+        self.member(feature, pointer, "type", "\"Feature\"", |v| v == "Feature");
+        match feature.get("geometry") {
+            Some(geometry @ Value::Object(_)) => Some(geometry),
+            v => {
+                self.report(&format!("{}/geometry", pointer), v, "a geometry object");
+                None
+            }
+        }
+    }
+
+    fn position(&mut self, value: &Value, pointer: &str) {
+        let numbers = value
+            .as_array()
+            .filter(|a| a.len() >= 2)
+            .and_then(|a| Some((a[0].as_f64()?, a[1].as_f64()?)));
+        match numbers {
+            None => self.report(pointer, Some(value), "a position of two numbers"),
+            Some((lng, lat)) if self.geographic => {
+                if !(-180.0..=180.0).contains(&lng) {
+                    self.report(
+                        &format!("{}/0", pointer),
+                        Some(&json!(lng)),
+                        "a longitude from -180 to 180",
+                    );
+                }
+                if !(-90.0..=90.0).contains(&lat) {
+                    self.report(
+                        &format!("{}/1", pointer),
+                        Some(&json!(lat)),
+                        "a latitude from -90 to 90",
+                    );
+                }
+            }
+            Some(_) => {}
+        }
+    }
+
+    /// Checks the positions of a geometry, returning how many there are.
+    fn positions(&mut self, geometry: &Value, pointer: &str) -> usize {
+        let pointer = format!("{}/coordinates", pointer);
+        match geometry.get("coordinates") {
+            Some(Value::Array(positions)) => {
+                for (i, position) in positions.iter().enumerate() {
+                    self.position(position, &format!("{}/{}", pointer, i));
+                }
+                positions.len()
+            }
+            v => {
+                self.report(&pointer, v, "an array of positions");
+                0
+            }
+        }
+    }
+
+    /// Checks sampling instructions with the same reading and validation as paths.
+    ///
+    /// Each member, and each layer and depth, is read on its own to find the values that are wrong.
+    fn sampling(&mut self, sampling: &Value, pointer: &str) {
+        let members = match sampling.as_object() {
+            Some(v) => v,
+            None => {
+                self.report(pointer, Some(sampling), "sampling instructions");
+                return;
+            }
+        };
+        let count = self.diagnostics.len();
+        for (name, value) in members {
+            let member = format!("{}/{}", pointer, escape(name));
+            let values = match (name.as_str(), value) {
+                ("layers" | "depths", Value::Array(items)) => items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| (format!("{}/{}", member, i), v, json!({ name: [v] })))
+                    .collect(),
+                _ => vec![(member, value, json!({ name: value }))],
+            };
+            for (pointer, found, alone) in values {
+                let checked = serde_json::from_value::<SamplingInstructions>(alone)
+                    .map_err(expected)
+                    .and_then(|s| s.validate().map_err(expected));
+                if let Err(e) = checked {
+                    self.report(&pointer, Some(found), &e);
+                }
+            }
+        }
+
+        // Checking the members together once each is valid
+        if self.diagnostics.len() == count {
+            let checked = serde_json::from_value::<SamplingInstructions>(sampling.clone())
+                .map_err(expected)
+                .and_then(|s| s.validate().map_err(expected));
+            if let Err(e) = checked {
+                self.report(pointer, Some(sampling), &e);
+            }
+        }
+    }
+}
+
+/// Reports a JSON syntax error, which stops anything else being checked.
+fn syntax(error: serde_json::Error) -> Vec<Diagnostic> {
+    let message = error.to_string();
+    let location = format!(" at line {} column {}", error.line(), error.column());
+    vec![Diagnostic {
+        pointer: String::new(),
+        line: error.line(),
+        column: error.column(),
+        found: None,
+        expected: format!(
+            "valid JSON ({})",
+            message.strip_suffix(&location).unwrap_or(&message)
+        ),
+    }]
+}
+
+/// Finds every problem in path GeoJSON.
+///
+/// Positions are checked as longitude and latitude unless the file or the given coordinate
+/// reference system is projected.
+pub fn check_path(text: &str, crs: Option<Crs>) -> Vec<Diagnostic> {
+    let root: Value = match serde_json::from_str(text) {
+        Ok(v) => v,
+        Err(e) => return syntax(e),
+    };
+    let mut checker = Checker::new(text);
+    checker.geographic = crs.unwrap_or_default() == Crs::Wgs84;
+    let features = checker.collection(&root, &migrate::PATH);
+    for name in ["home", "end"] {
+        match root.get(name) {
+            Some(Value::Null) | None => {}
+            Some(v) => checker.position(v, &format!("/{}", name)),
+        }
+    }
+
+    let (mut points, mut lines) = (vec![], vec![]);
+    for (i, feature) in features.unwrap_or_default().iter().enumerate() {
+        let pointer = format!("/features/{}", i);
+        let geometry = match checker.feature(feature, &pointer) {
+            Some(v) => v,
+            None => continue,
+        };
+        let geometry_pointer = format!("{}/geometry", pointer);
+        match geometry.get("type").and_then(Value::as_str) {
+            Some("MultiPoint") => {
+                let count = checker.positions(geometry, &geometry_pointer);
+                points.push(pointer.clone());
+                match feature.get("sampling") {
+                    Some(Value::Array(sampling)) => {
+                        if sampling.len() != count {
+                            checker.report(
+                                &format!("{}/sampling", pointer),
+                                feature.get("sampling"),
+                                &format!("sampling instructions for each of the {count} points"),
+                            );
+                        }
+                        for (j, s) in sampling.iter().enumerate() {
+                            checker.sampling(s, &format!("{}/sampling/{}", pointer, j));
+                        }
+                    }
+                    Some(Value::Null) | None => {}
+                    v => checker.report(
+                        &format!("{}/sampling", pointer),
+                        v,
+                        "an array of sampling instructions",
+                    ),
+                }
+            }
+            Some("LineString") => {
+                if checker.positions(geometry, &geometry_pointer) == 1 {
+                    checker.report(
+                        &format!("{}/coordinates", geometry_pointer),
+                        geometry.get("coordinates"),
+                        "a line of at least two positions",
+                    );
+                }
+                lines.push(pointer);
+            }
+            _ => checker.report(
+                &format!("{}/type", geometry_pointer),
+                geometry.get("type"),
+                "a \"MultiPoint\" or \"LineString\" geometry",
+            ),
+        }
+    }
+
+    if features.is_some() {
+        for (name, found) in [("MultiPoint", &points), ("LineString", &lines)] {
+            if found.is_empty() {
+                checker.report("/features", None, &format!("a {} feature", name));
+            }
+            for pointer in found.iter().skip(1) {
+                checker.report(pointer, None, &format!("only one {} feature", name));
+            }
+        }
+    }
+    checker.diagnostics
+}
+
+/// Finds every problem in boat data GeoJSON.
+///
+/// Positions are checked as longitude and latitude unless the file or the given coordinate
+/// reference system is projected.
+pub fn check_boat_data(text: &str, crs: Option<Crs>) -> Vec<Diagnostic> {
+    let root: Value = match serde_json::from_str(text) {
+        Ok(v) => v,
+        Err(e) => return syntax(e),
+    };
+    let mut checker = Checker::new(text);
+    checker.geographic = crs.unwrap_or_default() == Crs::Wgs84;
+    let features = checker.collection(&root, &migrate::BOAT_DATA);
+    checker.deserializes::<Option<Vec<Session>>>(&root, "", "sessions");
+
+    for (i, feature) in features.unwrap_or_default().iter().enumerate() {
+        let pointer = format!("/features/{}", i);
+        if let Some(geometry) = checker.feature(feature, &pointer) {
+            let geometry_pointer = format!("{}/geometry", pointer);
+            if geometry.get("type").and_then(Value::as_str) == Some("Point") {
+                match geometry.get("coordinates") {
+                    Some(v) => checker.position(v, &format!("{}/coordinates", geometry_pointer)),
+                    None => checker.report(
+                        &format!("{}/coordinates", geometry_pointer),
+                        None,
+                        "a position of two numbers",
+                    ),
+                }
+            } else {
+                checker.report(
+                    &format!("{}/type", geometry_pointer),
+                    geometry.get("type"),
+                    "a \"Point\" geometry",
+                );
+            }
+        }
+
+        let properties = match feature.get("properties") {
+            Some(properties @ Value::Object(_)) => properties,
+            v => {
+                if feature.is_object() {
+                    checker.report(&format!("{}/properties", pointer), v, "a properties object");
+                }
+                continue;
+            }
+        };
+        let pointer = format!("{}/properties", pointer);
+        checker.deserializes::<f64>(properties, &pointer, "temperature");
+        checker.deserializes::<f64>(properties, &pointer, "depth");
+        checker.deserializes::<Layer>(properties, &pointer, "layer");
+        checker.deserializes::<DateTime<Utc>>(properties, &pointer, "time");
+        checker.deserializes::<Option<String>>(properties, &pointer, "session");
+    }
+    checker.diagnostics
+}
+
+/// Adds the problems found in a file to the error from parsing it.
+pub fn explain(error: String, diagnostics: &[Diagnostic]) -> String {
+    if diagnostics.is_empty() {
+        return error;
+    }
+    let mut message = error;
+    for diagnostic in diagnostics {
+        message.push_str(&format!("\n{}", diagnostic));
+    }
+    message
+}
+
+/// Finds every problem in a path GeoJSON file.
+#[tauri::command]
+pub fn diagnose_path_file(
+    import_path: PathBuf,
+    crs: Option<String>,
+) -> Result<Vec<Diagnostic>, String> {
+    log::debug!("Checking Path: {}", import_path.display());
+    let crs = crs.map(|v| v.parse::<Crs>()).transpose()?;
+    let text = file::read_string(&import_path).map_err(|e| e.to_string())?;
+    Ok(check_path(&text, crs))
+}
+
+/// Finds every problem in a boat data GeoJSON file.
+#[tauri::command]
+pub fn diagnose_data_file(
+    import_path: PathBuf,
+    crs: Option<String>,
+) -> Result<Vec<Diagnostic>, String> {
+    log::debug!("Checking Boat Data: {}", import_path.display());
+    let crs = crs.map(|v| v.parse::<Crs>()).transpose()?;
+    let text = file::read_string(&import_path).map_err(|e| e.to_string())?;
+    Ok(check_boat_data(&text, crs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATH: &str = r#"{
+  "type": "FeatureCollection",
+  "version": "0.3.0",
+  "home": [101.69, 3.1],
+  "features": [
+    {
+      "type": "Feature",
+      "properties": null,
+      "geometry": { "type": "MultiPoint", "coordinates": [[101.7, 3.1], [101.71, 3.1]] },
+      "sampling": [{ "layers": ["surface"] }, { "repeat": 2 }]
+    },
+    {
+      "type": "Feature",
+      "properties": null,
+      "geometry": { "type": "LineString", "coordinates": [[101.7, 3.1], [101.71, 3.1]] }
+    }
+  ]
+}"#;
+
+    #[test]
+    fn accepts_valid_files() {
+        assert_eq!(check_path(PATH, None), vec![]);
+        assert!(PATH.parse::<crate::path::PathData>().is_ok());
+
+        let data = crate::data::BoatData::default().to_string();
+        assert_eq!(check_boat_data(&data, None), vec![]);
+    }
+
+    #[test]
+    fn reports_every_path_problem() {
+        let text = PATH
+            .replace("\"surface\"", "\"deep\"")
+            .replace("{ \"repeat\": 2 }", "{ \"repeat\": 0 }")
+            .replace("[101.69, 3.1]", "[201.69, 3.1]");
+        let diagnostics = check_path(&text, None);
+        let pointers = diagnostics.iter().map(|d| d.pointer()).collect::<Vec<_>>();
+        assert_eq!(
+            pointers,
+            [
+                "/home/0",
+                "/features/0/sampling/0/layers/0",
+                "/features/0/sampling/1/repeat"
+            ]
+        );
+
+        let layer = &diagnostics[1];
+        assert_eq!((layer.line(), layer.column()), (10, 33));
+        assert_eq!(layer.found(), Some(&json!("deep")));
+        assert_eq!(layer.expected(), "one of `surface`, `middle`, `sea bed`");
+        assert_eq!(
+            diagnostics[2].expected(),
+            "a valid value (Sampling Repeat must be Positive)"
+        );
+
+        // Members that are only wrong together are reported at their sampling instructions
+        let together = PATH.replace("\"layers\": [\"surface\"]", "\"layers\": []");
+        let diagnostics = check_path(&together, None);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].pointer(), "/features/0/sampling/0");
+        assert_eq!(
+            diagnostics[0].expected(),
+            "a valid value (Sampling requires a Layer or Depth)"
+        );
+
+        let error = text.parse::<crate::path::PathData>().unwrap_err();
+        assert!(error.contains("Line 10, Column 33 (/features/0/sampling/0/layers/0)"));
+    }
+
+    #[test]
+    fn reports_missing_features() {
+        let text = r#"{"type": "FeatureCollection", "version": "9.0.0", "features": []}"#;
+        let diagnostics = check_path(text, None);
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(diagnostics[0].pointer(), "/version");
+        assert_eq!(diagnostics[1].expected(), "a MultiPoint feature");
+
+        let diagnostics = check_path("{\n  \"type\": \"FeatureCollection\",\n}", None);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line(), 3);
+    }
+
+    #[test]
+    fn reports_every_data_problem() {
+        let text = r#"{
+  "type": "FeatureCollection",
+  "version": "0.1.0",
+  "features": [
+    {
+      "type": "Feature",
+      "geometry": { "type": "Point", "coordinates": [101.7, 3.1] },
+      "properties": { "temperature": 28.1, "depth": 1.0, "layer": "surface", "time": "2024-01-01T00:00:00Z" }
+    },
+    {
+      "type": "Feature",
+      "geometry": { "type": "Point", "coordinates": [101.7, 93.1] },
+      "properties": { "temperature": "hot", "depth": 1.0, "layer": "deep", "time": "2024-01-01T00:00:00Z" }
+    },
+    {
+      "type": "Feature",
+      "geometry": { "type": "Point", "coordinates": [101.7, 3.1] },
+      "properties": { "depth": 1.0, "layer": "surface", "time": "yesterday" }
+    }
+  ]
+}"#;
+        let diagnostics = check_boat_data(text, None);
+        let pointers = diagnostics.iter().map(|d| d.pointer()).collect::<Vec<_>>();
+        assert_eq!(
+            pointers,
+            [
+                "/features/1/geometry/coordinates/1",
+                "/features/1/properties/temperature",
+                "/features/1/properties/layer",
+                "/features/2/properties/temperature",
+                "/features/2/properties/time",
+            ]
+        );
+        // Missing values are located at their parent object
+        assert_eq!(diagnostics[3].line(), 18);
+        assert_eq!(diagnostics[3].found(), None);
+
+        let error = text.parse::<crate::data::BoatData>().unwrap_err();
+        assert_eq!(error.lines().count(), 6);
+    }
+}
@@ -0,0 +1,101 @@
+//! Desktop application for Autonomous Water Temperature Collection Robot (AWTC-R).
+
+pub mod comm_proto;
+pub mod crs;
+pub mod data;
+pub mod diagnostics;
+pub mod mbtiles;
+pub mod migrate;
+pub mod path;
+pub mod session_log;
+
+use std::error::Error;
+
+use tauri::{Manager, State, WindowEvent};
+use tauri_plugin_log::LogTarget;
+
+pub(crate) fn error_to_string<E: Error>(error: E) -> String {
+    error.to_string()
+}
+
+/// Builds and runs the tauri application.
+pub fn run() {
+    tauri::Builder::default()
+        .invoke_handler(tauri::generate_handler![
+            path::read_path,
+            path::save_path,
+            path::import_path,
+            path::export_path,
+            path::coverage::coverage_report,
+            path::history::undo_path,
+            path::history::redo_path,
+            path::history::list_path_revisions,
+            path::history::restore_path_revision,
+            path::interpolate::interpolate_path,
+            path::library::list_paths,
+            path::library::active_path,
+            path::library::set_active_path,
+            path::library::create_path,
+            path::library::rename_path,
+            path::library::update_path_metadata,
+            path::library::duplicate_path,
+            path::library::delete_path,
+            path::obstacles::avoid_obstacles,
+            path::route::order_collection_points,
+            path::simplify::simplify_path,
+            path::stats::path_stats,
+            path::survey::generate_survey,
+            path::transform::transform_path,
+            path::validate::validate_path,
+            data::read_data,
+            data::save_data,
+            data::import_data,
+            data::export_data,
+            data::import_data_csv,
+            data::export_data_csv,
+            data::contour::temperature_contours,
+            data::merge::merge_data,
+            data::merge::deduplicate_data,
+            data::session::list_sessions,
+            data::session::rename_session,
+            data::session::export_session,
+            data::session::delete_session,
+            data::raster::export_temperature_geotiff,
+            data::raster::export_temperature_overlay,
+            data::summary::data_summary,
+            diagnostics::diagnose_path_file,
+            diagnostics::diagnose_data_file,
+            comm_proto::find_ports,
+            comm_proto::send_path,
+            mbtiles::fetch_mbtiles,
+            mbtiles::mbtiles_metadata,
+            session_log::list_session_logs,
+            session_log::open_session_log,
+        ])
+        .plugin(
+            tauri_plugin_log::Builder::default()
+                .targets([LogTarget::LogDir, LogTarget::Stdout, LogTarget::Webview])
+                .build(),
+        )
+        .manage(comm_proto::ConnectedBoats::default())
+        .manage(path::history::PathHistories::default())
+        .on_window_event(|event| {
+            if let WindowEvent::Destroyed = event.event() {
+                // Dropping all connected ports when exiting
+                let boats: State<'_, comm_proto::ConnectedBoats> = event.window().state();
+                boats.boats.lock().unwrap().clear();
+            }
+        })
+        .setup(|app| {
+            // Dropping all connected ports when exiting
+            let app_handle = app.app_handle();
+            ctrlc::set_handler(move || {
+                let boats: State<'_, comm_proto::ConnectedBoats> = app_handle.state();
+                boats.boats.lock().unwrap().clear();
+                std::process::exit(0);
+            })?;
+            Ok(())
+        })
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
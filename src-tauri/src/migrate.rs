@@ -0,0 +1,231 @@
+//! Upgrading path and data files written by older versions of the application.
+//!
+//! Files are upgraded as JSON before they are parsed, one step at a time, so each migration only
+//! needs to know about the version before it.
+
+use semver::Version;
+use serde_json::{json, Value};
+
+/// A step upgrading a file from one version to the next.
+pub struct Migration {
+    /// The oldest version the step applies to.
+    from: Version,
+    /// The version of the file after the step.
+    to: Version,
+    /// Changes the file to the new version.
+    migrate: fn(&mut Value) -> Result<(), String>,
+}
+
+/// The migrations of one kind of file, oldest first.
+pub struct Migrations {
+    /// The name of the kind of file shown in errors.
+    name: &'static str,
+    /// The version written by this version of the application.
+    current: Version,
+    /// The steps upgrading older files.
+    steps: &'static [Migration],
+}
+
+/// The migrations of path files.
+///
+/// Version 0.3.0 only added the optional `home` and `end` members so it has no step.
+pub const PATH: Migrations = Migrations {
+    name: "Path",
+    current: Version::new(0, 3, 0),
+    steps: &[Migration {
+        from: Version::new(0, 1, 0),
+        to: Version::new(0, 2, 0),
+        migrate: path_sampling,
+    }],
+};
+
+/// The migrations of boat data files.
+///
+/// Version 0.2.0 only added the optional `sessions` member and `session` property so it has no
+/// step.
+pub const BOAT_DATA: Migrations = Migrations {
+    name: "Boat Data",
+    current: Version::new(0, 2, 0),
+    steps: &[],
+};
+
+impl Migrations {
+    /// Gets the version written by this version of the application.
+    pub fn current(&self) -> String {
+        self.current.to_string()
+    }
+
+    /// Reads the version of a file, checking it can be upgraded.
+    pub fn check(&self, text: &str) -> Result<Version, String> {
+        let version =
+            Version::parse(text).map_err(|e| format!("Invalid Version: {}: {}", text, e))?;
+        if version.major > self.current.major {
+            return Err(format!(
+                "Unsupported {} Version: {}, newer than {}",
+                self.name, version, self.current
+            ));
+        }
+        Ok(version)
+    }
+
+    /// Upgrades a file to the current version, returning the version it was written with.
+    ///
+    /// The original version is kept in the `migrated_from` member of upgraded files. Files from
+    /// a newer minor version are read as they are, but a newer major version is rejected.
+    pub fn migrate(&self, value: &mut Value) -> Result<Version, String> {
+        let object = value
+            .as_object_mut()
+            .ok_or(String::from("Missing Version"))?;
+        let text = object
+            .get("version")
+            .ok_or(String::from("Missing Version"))?
+            .as_str()
+            .ok_or(String::from("Invalid Version"))?;
+        let original = self.check(text)?;
+        if original >= self.current {
+            if original > self.current {
+                log::warn!(
+                    "{} Version {} is newer than {}",
+                    self.name,
+                    original,
+                    self.current
+                );
+            }
+            return Ok(original);
+        }
+
+        log::info!("Migrating {} from {}", self.name, original);
+        let mut version = original.clone();
+        for step in self.steps {
+            if version >= step.from && version < step.to {
+                log::debug!("Migrating {} from {} to {}", self.name, version, step.to);
+                (step.migrate)(value)?;
+                version = step.to.clone();
+            }
+        }
+
+        // Objects were checked above and migrations keep them as objects
+        let object = value.as_object_mut().unwrap();
+        object.insert(String::from("version"), json!(self.current.to_string()));
+        object
+            .entry("migrated_from")
+            .or_insert(json!(original.to_string()));
+        Ok(original)
+    }
+}
+
+/// Adds the default sampling instructions to every collection point.
+///
+/// Paths before 0.2.0 sampled every layer at every collection point.
+fn path_sampling(value: &mut Value) -> Result<(), String> {
+    let features = value
+        .get_mut("features")
+        .and_then(Value::as_array_mut)
+        .ok_or(String::from("Missing Features"))?;
+    for feature in features {
+        let points = match feature.pointer("/geometry/type") {
+            Some(t) if t == "MultiPoint" => feature
+                .pointer("/geometry/coordinates")
+                .and_then(Value::as_array)
+                .map_or(0, |c| c.len()),
+            _ => continue,
+        };
+        let sampling = json!({ "layers": ["surface", "middle", "sea bed"] });
+        feature["sampling"] = Value::Array(vec![sampling; points]);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bump(value: &mut Value) -> Result<(), String> {
+        value["steps"] = json!(value["steps"].as_u64().unwrap_or(0) + 1);
+        Ok(())
+    }
+
+    const TEST: Migrations = Migrations {
+        name: "Test",
+        current: Version::new(1, 2, 0),
+        steps: &[
+            Migration {
+                from: Version::new(0, 1, 0),
+                to: Version::new(1, 0, 0),
+                migrate: bump,
+            },
+            Migration {
+                from: Version::new(1, 0, 0),
+                to: Version::new(1, 2, 0),
+                migrate: bump,
+            },
+        ],
+    };
+
+    #[test]
+    fn migrates_step_by_step() {
+        let mut value = json!({ "version": "0.1.3" });
+        assert_eq!(TEST.migrate(&mut value).unwrap(), Version::new(0, 1, 3));
+        assert_eq!(value["steps"], 2);
+        assert_eq!(value["version"], "1.2.0");
+        assert_eq!(value["migrated_from"], "0.1.3");
+
+        let mut value = json!({ "version": "1.1.0" });
+        TEST.migrate(&mut value).unwrap();
+        assert_eq!(value["steps"], 1);
+    }
+
+    #[test]
+    fn keeps_current_and_newer_minor_versions() {
+        for version in ["1.2.0", "1.5.1"] {
+            let mut value = json!({ "version": version });
+            TEST.migrate(&mut value).unwrap();
+            assert_eq!(value, json!({ "version": version }));
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_versions() {
+        assert!(TEST.migrate(&mut json!({ "version": "2.0.0" })).is_err());
+        assert!(TEST.migrate(&mut json!({ "version": "one" })).is_err());
+        assert!(TEST.migrate(&mut json!({})).is_err());
+        assert!(TEST.migrate(&mut json!([])).is_err());
+
+        let data = r#"{"type": "FeatureCollection", "version": "1.0.0", "features": []}"#;
+        assert!(data.parse::<crate::data::BoatData>().is_err());
+    }
+
+    #[test]
+    fn adds_path_sampling() {
+        let mut value = json!({
+            "type": "FeatureCollection",
+            "version": "0.1.0",
+            "features": [
+                {
+                    "type": "Feature",
+                    "properties": null,
+                    "geometry": { "type": "MultiPoint", "coordinates": [[0.0, 0.0], [1.0, 0.0]] },
+                },
+                {
+                    "type": "Feature",
+                    "properties": null,
+                    "geometry": { "type": "LineString", "coordinates": [[0.0, 0.0], [1.0, 0.0]] },
+                },
+            ],
+        });
+        PATH.migrate(&mut value).unwrap();
+
+        assert_eq!(value["version"], "0.3.0");
+        assert_eq!(
+            value["features"][0]["sampling"].as_array().unwrap().len(),
+            2
+        );
+        assert!(value["features"][1].get("sampling").is_none());
+
+        let path =
+            crate::path::PathData::try_from(geojson::GeoJson::from_json_value(value).unwrap())
+                .unwrap();
+        assert_eq!(path.migrated_from(), Some("0.1.0"));
+        assert_eq!(path.sampling()[0], Default::default());
+    }
+}
@@ -1,14 +1,32 @@
 //! States and function for working with robot paths.
+//!
+//! Commands that preview an edit return the edited path without saving it, so the result can be
+//! shown on the map before it is accepted with `save_path`.
+
+pub mod coverage;
+pub mod format;
+pub mod history;
+pub mod interpolate;
+pub mod library;
+pub(crate) mod local;
+pub mod obstacles;
+pub mod route;
+pub mod sampling;
+pub mod simplify;
+pub mod stats;
+pub mod survey;
+pub mod transform;
+pub mod validate;
 
 use std::{
     fmt::Display,
     io::{ErrorKind, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::FromStr,
 };
 
-use geo_types::{LineString, MultiPoint};
-use geojson::{FeatureCollection, GeoJson, Geometry, Value};
+use geo_types::{LineString, MultiPoint, Point};
+use geojson::{Feature, FeatureCollection, GeoJson, Value};
 use serde::{de, Deserialize, Serialize};
 use serde_json::{json, Map};
 use tauri::{
@@ -16,23 +34,81 @@ use tauri::{
     AppHandle,
 };
 
+use crate::{
+    crs::{self, Crs},
+    diagnostics, migrate,
+};
+
+use self::{
+    format::PathFormat, history::PathHistories, library::PathLibrary,
+    sampling::SamplingInstructions,
+};
+
 /// Information on where to collect data for the boat.
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct PathData {
-    /// The version of the communication protocol used.
+    /// The version of the path format.
     version: String,
+    /// The version the path was written with before it was upgraded.
+    migrated_from: Option<String>,
     /// The path the robot boat is following.
     path: LineString<f64>,
     /// The coordinates to where the data should be collected.
     collection_points: MultiPoint<f64>,
+    /// What to sample at each of the collection points.
+    sampling: Vec<SamplingInstructions>,
+    /// Where the boat is launched from and returns to.
+    home: Option<Point<f64>>,
+    /// Where the boat finishes if it is not the home point.
+    end: Option<Point<f64>>,
 }
 
 impl PathData {
-    /// Gets the version of the communication protocol used.
+    /// Creates a new path using the current version.
+    ///
+    /// Every collection point uses the default sampling instructions.
+    pub fn new(path: LineString<f64>, collection_points: MultiPoint<f64>) -> Self {
+        Self {
+            sampling: vec![SamplingInstructions::default(); collection_points.0.len()],
+            path,
+            collection_points,
+            ..Default::default()
+        }
+    }
+
+    /// Sets what to sample at each of the collection points.
+    pub fn with_sampling(mut self, sampling: Vec<SamplingInstructions>) -> Result<Self, String> {
+        if sampling.len() != self.collection_points.0.len() {
+            return Err(String::from(
+                "Sampling Instructions must match the Collection Points",
+            ));
+        }
+        for instructions in &sampling {
+            instructions.validate()?;
+        }
+        self.sampling = sampling;
+        Ok(self)
+    }
+
+    /// Sets where the boat is launched from and where it finishes.
+    ///
+    /// The boat returns home if there is no end point.
+    pub fn with_home(mut self, home: Option<Point<f64>>, end: Option<Point<f64>>) -> Self {
+        self.home = home;
+        self.end = end;
+        self
+    }
+
+    /// Gets the version of the path format.
     pub fn version(&self) -> &str {
         &self.version
     }
 
+    /// Gets the version the path was written with if it was upgraded from an older version.
+    pub fn migrated_from(&self) -> Option<&str> {
+        self.migrated_from.as_deref()
+    }
+
     /// Gets the path the robot boat is following.
     pub fn path(&self) -> &LineString<f64> {
         &self.path
@@ -42,6 +118,26 @@ impl PathData {
     pub fn collection_points(&self) -> &MultiPoint<f64> {
         &self.collection_points
     }
+
+    /// Gets what to sample at each of the collection points.
+    pub fn sampling(&self) -> &[SamplingInstructions] {
+        &self.sampling
+    }
+
+    /// Gets where the boat is launched from and returns to.
+    pub fn home(&self) -> Option<Point<f64>> {
+        self.home
+    }
+
+    /// Gets the end point if it is not the home point.
+    pub fn end(&self) -> Option<Point<f64>> {
+        self.end
+    }
+
+    /// Gets where the boat finishes, which is the home point if there is no end point.
+    pub fn finish(&self) -> Option<Point<f64>> {
+        self.end.or(self.home)
+    }
 }
 
 impl Default for PathData {
@@ -49,7 +145,11 @@ impl Default for PathData {
         Self {
             path: LineString(vec![]),
             collection_points: MultiPoint(vec![]),
-            version: String::from("0.1.0"),
+            sampling: vec![],
+            home: None,
+            end: None,
+            version: migrate::PATH.current(),
+            migrated_from: None,
         }
     }
 }
@@ -57,9 +157,13 @@ impl Default for PathData {
 impl FromStr for PathData {
     type Err = String;
 
+    /// Every problem found in the GeoJSON is listed in the error.
     fn from_str(value: &str) -> Result<Self, Self::Err> {
-        let geojson: GeoJson = value.parse().map_err(|e| format!("{e}"))?;
-        Self::try_from(geojson)
+        value
+            .parse::<GeoJson>()
+            .map_err(|e| format!("{e}"))
+            .and_then(Self::try_from)
+            .map_err(|e| diagnostics::explain(e, &diagnostics::check_path(value, None)))
     }
 }
 
@@ -78,8 +182,15 @@ impl TryFrom<GeoJson> for PathData {
             FeatureCollection::try_from(value).map_err(|_| String::from("Invalid Spec"))?;
         log::debug!("Feature Collection: {}", features);
 
-        // Checking for version
+        // Upgrading paths from older versions before reading them
         log::info!("Checking Version");
+        let mut json = serde_json::to_value(&features).map_err(|e| e.to_string())?;
+        migrate::PATH
+            .migrate(&mut json)
+            .map_err(|e| format!("Invalid Path GeoJSON: {e}"))?;
+        let features = GeoJson::from_json_value(json)
+            .and_then(FeatureCollection::try_from)
+            .map_err(|e| e.to_string())?;
         let foreign_members = features
             .foreign_members
             .ok_or(String::from("Invalid Path GeoJSON: Missing Version"))?;
@@ -88,8 +199,27 @@ impl TryFrom<GeoJson> for PathData {
             .ok_or(String::from("Invalid Path GeoJSON: Missing Version"))?
             .as_str()
             .ok_or(String::from("Invalid Path GeoJSON: Invalid Version"))?;
+        let migrated_from = foreign_members
+            .get("migrated_from")
+            .and_then(|v| v.as_str())
+            .map(String::from);
         log::debug!("Version: {}", version);
 
+        // Home and end points are optional
+        log::info!("Extracting Home and End Points");
+        let position = |name: &str| -> Result<Option<Point<f64>>, String> {
+            foreign_members
+                .get(name)
+                .map(|v| {
+                    serde_json::from_value::<(f64, f64)>(v.clone())
+                        .map(Point::from)
+                        .map_err(|e| format!("Invalid Path GeoJSON: Invalid {}: {}", name, e))
+                })
+                .transpose()
+        };
+        let (home, end) = (position("home")?, position("end")?);
+        log::debug!("Home: {:?}, End: {:?}", home, end);
+
         log::info!("Extracting Features");
         let features = features.features;
         if features.len() != 2 {
@@ -97,31 +227,50 @@ impl TryFrom<GeoJson> for PathData {
         }
         log::debug!("Features: {:?}", features);
 
-        // Extracting Geometries
-        log::info!("Extracting Geometries");
-        let mut geometries = features
-            .into_iter()
-            .map(|f| f.geometry)
-            .collect::<Option<Vec<Geometry>>>()
-            .ok_or(String::from("Invalid Path GeoJSON: Path GeoJSON requires two features (Multi Point and Line String)."))?;
-        log::debug!("Geometries: {:?}", geometries);
-
         // Extracting Path and Points
         log::info!("Extracting Path and Points");
-        let (path, points) = match (geometries.remove(0).value, geometries.remove(0).value) {
-            (p @ Value::MultiPoint(_), l @ Value::LineString(_)) => (l, p),
-            (l @ Value::LineString(_), p @ Value::MultiPoint(_)) => (l, p),
-            _ => return Err(String::from("Invalid Path GeoJSON: Path GeoJSON requires two features (Multi Point and Line String).")),
+        let requires_features = || {
+            String::from("Invalid Path GeoJSON: Path GeoJSON requires two features (Multi Point and Line String).")
+        };
+        let mut features = features.into_iter();
+        let (first, second) = match (features.next(), features.next()) {
+            (Some(first), Some(second)) => (first, second),
+            _ => return Err(requires_features()),
+        };
+        let geometry = |f: &Feature| f.geometry.as_ref().map(|g| g.value.clone());
+        let (path, points, points_feature) = match (geometry(&first), geometry(&second)) {
+            (Some(p @ Value::MultiPoint(_)), Some(l @ Value::LineString(_))) => (l, p, first),
+            (Some(l @ Value::LineString(_)), Some(p @ Value::MultiPoint(_))) => (l, p, second),
+            _ => return Err(requires_features()),
         };
         log::debug!("Path: {}", path);
         log::debug!("Points: {}", points);
 
-        Ok(Self {
-            // We can safely unwrap as we know the values will work
+        // We can safely unwrap as we know the values will work
+        let path = Self {
             path: LineString::try_from(path).unwrap(),
             collection_points: MultiPoint::try_from(points).unwrap(),
+            sampling: vec![],
+            home,
+            end,
             version: String::from(version),
-        })
+            migrated_from,
+        };
+
+        // Sampling instructions are optional, using the defaults for older paths
+        log::info!("Extracting Sampling Instructions");
+        let sampling = match points_feature
+            .foreign_members
+            .as_ref()
+            .and_then(|m| m.get("sampling"))
+        {
+            Some(v) => serde_json::from_value(v.clone())
+                .map_err(|e| format!("Invalid Path GeoJSON: Invalid Sampling: {}", e))?,
+            None => vec![SamplingInstructions::default(); path.collection_points.0.len()],
+        };
+        log::debug!("Sampling: {:?}", sampling);
+        path.with_sampling(sampling)
+            .map_err(|e| format!("Invalid Path GeoJSON: {}", e))
     }
 }
 
@@ -139,14 +288,25 @@ impl From<&mut PathData> for GeoJson {
 
 impl From<&PathData> for GeoJson {
     fn from(value: &PathData) -> Self {
-        let points = geojson::Value::from(&value.collection_points);
+        let mut points = Feature::from(geojson::Value::from(&value.collection_points));
+        let mut sampling = Map::new();
+        sampling.insert(String::from("sampling"), json!(&value.sampling));
+        points.foreign_members = Some(sampling);
         let path = geojson::Value::from(&value.path);
         let mut foreign_members = Map::new();
         foreign_members.insert(String::from("version"), json!(&value.version));
+        if let Some(migrated_from) = &value.migrated_from {
+            foreign_members.insert(String::from("migrated_from"), json!(migrated_from));
+        }
+        for (name, point) in [("home", value.home), ("end", value.end)] {
+            if let Some(point) = point {
+                foreign_members.insert(String::from(name), json!([point.x(), point.y()]));
+            }
+        }
 
         let collection = FeatureCollection {
             bbox: None,
-            features: vec![points.into(), path.into()],
+            features: vec![points, path.into()],
             foreign_members: Some(foreign_members),
         };
         GeoJson::from(collection)
@@ -173,59 +333,206 @@ impl<'de> Deserialize<'de> for PathData {
     }
 }
 
-/// Read data from application storage.
+/// Read the active path of the library.
+///
+/// An empty path is returned if there is no active path.
 #[tauri::command]
 pub fn read_path(app_handle: AppHandle) -> Result<PathData, String> {
     log::debug!("Reading Path");
-    let mut data_dir = app_handle
-        .path_resolver()
-        .app_data_dir()
-        .ok_or(String::from("Unable to Get App Data Directory"))?;
-    data_dir.push("path.geojson");
-    log::debug!("Application GeoJSON Path: {}", data_dir.display());
-
-    import_path(data_dir)
+    let library = PathLibrary::open(&app_handle)?;
+    match library.active() {
+        Some(metadata) => library.read(metadata.id()),
+        None => {
+            log::info!("No Active Path, using default PathData");
+            Ok(PathData::default())
+        }
+    }
 }
 
-/// Import path data from the file system.
-#[tauri::command]
-pub fn import_path(import_path: PathBuf) -> Result<PathData, String> {
-    log::debug!("Importing from: {}", import_path.display());
-    Ok(match file::read_string(&import_path) {
-        Ok(v) => PathData::from_str(&v)?,
+/// Positions in path GeoJSON outside of its geometries.
+const PATH_POSITIONS: &[&str] = &["home", "end"];
+
+/// Reads path data from a file, using the default path if it does not exist.
+///
+/// GeoJSON in another coordinate reference system is converted to WGS84, using the given
+/// coordinate reference system if the file does not name one.
+pub(crate) fn read_path_file(import_path: &Path, crs: Option<Crs>) -> Result<PathData, String> {
+    let content = match file::read_string(import_path) {
+        Ok(v) => v,
         Err(api::Error::Io(e)) => match e.kind() {
             ErrorKind::NotFound => {
                 log::warn!(
                     "Unable to find Path: {}, using default PathData",
                     import_path.display()
                 );
-                PathData::default()
+                return Ok(PathData::default());
             }
             _ => return Err(e.to_string()),
         },
         Err(e) => return Err(e.to_string()),
-    })
+    };
+    let format =
+        PathFormat::detect(import_path, &content).ok_or(String::from("Unknown Path Format"))?;
+    if format != PathFormat::GeoJson {
+        if crs.is_some_and(|c| c != Crs::Wgs84) {
+            return Err(format!("{} Paths must be in WGS84", format));
+        }
+        return format.parse(&content);
+    }
+
+    let read = |content: &str| {
+        let mut value: serde_json::Value =
+            serde_json::from_str(content).map_err(|e| e.to_string())?;
+        crs::read_geojson(&mut value, crs, PATH_POSITIONS)?;
+        PathData::try_from(GeoJson::from_json_value(value).map_err(|e| e.to_string())?)
+    };
+    read(&content).map_err(|e| diagnostics::explain(e, &diagnostics::check_path(&content, crs)))
+}
+
+/// Writes path data to a file.
+///
+/// The format is chosen from the extension of the file, using GeoJSON if it is unknown. GeoJSON
+/// is converted into the coordinate reference system if one is given.
+pub(crate) fn write_path(
+    export_path: &Path,
+    path: &PathData,
+    crs: Option<Crs>,
+) -> Result<(), String> {
+    let format = PathFormat::from_extension(export_path).unwrap_or(PathFormat::GeoJson);
+    let content = match (format, crs) {
+        (PathFormat::GeoJson, Some(crs)) => {
+            let mut value = serde_json::to_value(GeoJson::from(path)).map_err(|e| e.to_string())?;
+            crs::write_geojson(&mut value, crs, PATH_POSITIONS);
+            value.to_string()
+        }
+        (_, Some(crs)) if crs != Crs::Wgs84 => {
+            return Err(format!("{} Paths must be in WGS84", format))
+        }
+        _ => format.write(path)?,
+    };
+    let mut file = std::fs::File::create(export_path).map_err(|e| e.to_string())?;
+    write!(file, "{}", content).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Import path data from the file system.
+///
+/// The format is detected from the contents of the file, falling back to its extension. GeoJSON
+/// without a `crs` member is read in the given EPSG code, or WGS84 if there is none.
+#[tauri::command]
+pub fn import_path(import_path: PathBuf, crs: Option<String>) -> Result<PathData, String> {
+    log::debug!("Importing from: {}", import_path.display());
+    let crs = crs.map(|v| v.parse::<Crs>()).transpose()?;
+    read_path_file(&import_path, crs)
 }
 
 /// Export path data to the file system.
+///
+/// The format is chosen from the extension of the file, using GeoJSON if it is unknown. GeoJSON
+/// is written in the given EPSG code, or the UTM zone around the path for `auto`, and always
+/// names its coordinate reference system.
 #[tauri::command]
-pub fn export_path(export_path: PathBuf, path: PathData) -> Result<(), String> {
+pub fn export_path(
+    export_path: PathBuf,
+    path: PathData,
+    crs: Option<String>,
+) -> Result<(), String> {
     log::debug!("Exporting to: {}", export_path.display());
-    let mut file = std::fs::File::create(export_path).map_err(|e| e.to_string())?;
-    write!(file, "{}", path).map_err(|e| e.to_string())?;
-    Ok(())
+    let coords = path
+        .path()
+        .coords()
+        .copied()
+        .chain(path.collection_points().iter().map(|p| p.0))
+        .collect::<Vec<_>>();
+    let crs = crs::choose(crs.as_deref(), coords)?.unwrap_or_default();
+    write_path(&export_path, &path, Some(crs))
 }
 
-/// Save data to application storage.
+/// Save data to the active path of the library, recording it in the history of the path.
+///
+/// A new path is added to the library if there is no active path.
 #[tauri::command]
-pub fn save_path(app_handle: AppHandle, path: PathData) -> Result<(), String> {
+pub fn save_path(
+    app_handle: AppHandle,
+    state: tauri::State<'_, PathHistories>,
+    path: PathData,
+) -> Result<(), String> {
     log::debug!("Saving Path");
-    let mut data_dir = app_handle
-        .path_resolver()
-        .app_data_dir()
-        .ok_or(String::from("Unable to Get App Data Directory"))?;
-    data_dir.push("path.geojson");
-    log::debug!("Application GeoJSON Path: {}", data_dir.display());
-
-    export_path(data_dir, path)
+    let mut library = PathLibrary::open(&app_handle)?;
+    let id = match library.active() {
+        Some(metadata) => metadata.id(),
+        None => {
+            log::info!("No Active Path, Creating New Path");
+            let metadata = library.create("Untitled Path", None, "", &path)?;
+            library.set_active(metadata.id())?;
+            return Ok(());
+        }
+    };
+    // Loading the history before writing so it starts from the path before this edit
+    state.load(&library, id)?;
+    library.write(id, &path)?;
+    state.update(&library, id, |h| h.record(path))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::{line_string, point};
+
+    use super::*;
+
+    #[test]
+    fn keeps_home_and_end_in_geojson() {
+        let path = PathData::new(
+            line_string![(x: 101.7, y: 3.1), (x: 101.71, y: 3.1)],
+            MultiPoint(vec![]),
+        );
+        assert_eq!(path.finish(), None);
+
+        let path = path.with_home(Some(point!(x: 101.69, y: 3.1)), None);
+        let parsed = path.to_string().parse::<PathData>().unwrap();
+        assert_eq!(parsed, path);
+        assert_eq!(parsed.finish(), parsed.home());
+
+        let path = path.with_home(
+            Some(point!(x: 101.69, y: 3.1)),
+            Some(point!(x: 101.72, y: 3.1)),
+        );
+        let parsed = path.to_string().parse::<PathData>().unwrap();
+        assert_eq!(parsed.end(), Some(point!(x: 101.72, y: 3.1)));
+        assert_eq!(parsed.finish(), parsed.end());
+    }
+
+    #[test]
+    fn exports_paths_in_utm() {
+        let file = std::env::temp_dir().join(format!("path-crs-{}.geojson", std::process::id()));
+        let path = PathData::new(
+            line_string![(x: 101.7, y: 3.1), (x: 101.71, y: 3.1)],
+            MultiPoint(vec![point!(x: 101.705, y: 3.1)]),
+        )
+        .with_home(Some(point!(x: 101.69, y: 3.1)), None);
+
+        export_path(file.clone(), path.clone(), Some(String::from("auto"))).unwrap();
+        let written: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&file).unwrap()).unwrap();
+        assert_eq!(
+            written["crs"],
+            Crs::Utm {
+                zone: 47,
+                north: true
+            }
+            .to_geojson()
+        );
+        assert!(written["home"][0].as_f64().unwrap() > 180.0);
+
+        let read = import_path(file.clone(), None).unwrap();
+        let _ = std::fs::remove_file(&file);
+        for (a, b) in read.path().coords().zip(path.path().coords()) {
+            assert!((a.x - b.x).abs() < 1e-9 && (a.y - b.y).abs() < 1e-9);
+        }
+        assert!((read.home().unwrap().x() - 101.69).abs() < 1e-9);
+
+        let gpx = file.with_extension("gpx");
+        assert!(export_path(gpx, path, Some(String::from("3857"))).is_err());
+    }
 }
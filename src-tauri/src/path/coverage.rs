@@ -0,0 +1,356 @@
+//! Comparing the collection points of a path with the data the boat collected.
+
+use geo::GeodesicDistance;
+use geo_types::{LineString, MultiPoint, Point};
+use serde::{Deserialize, Serialize};
+
+use super::{sampling::SamplingInstructions, PathData};
+use crate::data::{BoatData, Layer};
+
+/// How much of the sampling at a collection point was done.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Coverage {
+    /// Nothing was collected at the point.
+    Missing,
+    /// Some of the layers or depths were collected.
+    Partial,
+    /// Every layer and depth was collected as often as asked.
+    Complete,
+}
+
+/// How far data may be from what was planned to count towards it.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct CoverageOptions {
+    /// The furthest data may be from a collection point in metres.
+    tolerance: f64,
+    /// The furthest data may be from a sampling depth in metres.
+    depth_tolerance: f64,
+}
+
+impl CoverageOptions {
+    /// Creates new options.
+    pub fn new(tolerance: f64, depth_tolerance: f64) -> Self {
+        Self {
+            tolerance,
+            depth_tolerance,
+        }
+    }
+}
+
+impl Default for CoverageOptions {
+    fn default() -> Self {
+        Self {
+            tolerance: 10.0,
+            depth_tolerance: 0.5,
+        }
+    }
+}
+
+/// What was collected at one collection point.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PointCoverage {
+    /// The index of the collection point in the path.
+    index: usize,
+    /// The longitude and latitude of the collection point.
+    coordinate: (f64, f64),
+    /// How much of the sampling was done.
+    coverage: Coverage,
+    /// The number of data features matched to the point.
+    samples: usize,
+    /// The layers not collected as often as asked.
+    missing_layers: Vec<Layer>,
+    /// The depths not collected as often as asked.
+    missing_depths: Vec<f64>,
+}
+
+impl PointCoverage {
+    /// Gets the index of the collection point in the path.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Gets how much of the sampling was done.
+    pub fn coverage(&self) -> Coverage {
+        self.coverage
+    }
+
+    /// Gets the number of data features matched to the point.
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    /// Gets the layers not collected as often as asked.
+    pub fn missing_layers(&self) -> &[Layer] {
+        &self.missing_layers
+    }
+
+    /// Gets the depths not collected as often as asked.
+    pub fn missing_depths(&self) -> &[f64] {
+        &self.missing_depths
+    }
+}
+
+/// The collection points of a path that were missed and a path to collect them.
+#[derive(Debug, Clone, Serialize)]
+pub struct CoverageReport {
+    /// What was collected at each collection point.
+    points: Vec<PointCoverage>,
+    /// The number of points where nothing was collected.
+    missing: usize,
+    /// The number of points where some of the sampling was done.
+    partial: usize,
+    /// The number of points where all of the sampling was done.
+    complete: usize,
+    /// The number of data features not near any collection point.
+    unmatched: usize,
+    /// A path visiting only the points that were missed, sampling only what was missed.
+    follow_up: PathData,
+}
+
+impl CoverageReport {
+    /// Gets what was collected at each collection point.
+    pub fn points(&self) -> &[PointCoverage] {
+        &self.points
+    }
+
+    /// Gets the number of points where nothing was collected.
+    pub fn missing(&self) -> usize {
+        self.missing
+    }
+
+    /// Gets the number of points where some of the sampling was done.
+    pub fn partial(&self) -> usize {
+        self.partial
+    }
+
+    /// Gets the number of points where all of the sampling was done.
+    pub fn complete(&self) -> usize {
+        self.complete
+    }
+
+    /// Gets the number of data features not near any collection point.
+    pub fn unmatched(&self) -> usize {
+        self.unmatched
+    }
+
+    /// Gets the path visiting only the points that were missed.
+    pub fn follow_up(&self) -> &PathData {
+        &self.follow_up
+    }
+}
+
+/// Matches the data to the collection points of a path.
+///
+/// Each data feature counts towards the nearest collection point within the tolerance.
+pub fn coverage(
+    path: &PathData,
+    data: &BoatData,
+    options: &CoverageOptions,
+) -> Result<CoverageReport, String> {
+    if !(options.tolerance.is_finite() && options.tolerance >= 0.0) {
+        return Err(String::from("Coverage Tolerance must not be Negative"));
+    }
+    if !(options.depth_tolerance.is_finite() && options.depth_tolerance >= 0.0) {
+        return Err(String::from("Depth Tolerance must not be Negative"));
+    }
+    let points = &path.collection_points().0;
+
+    log::info!("Matching Data to Collection Points");
+    let mut matched = vec![vec![]; points.len()];
+    let mut unmatched = 0;
+    for feature in data.features() {
+        let nearest = points
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (i, p.geodesic_distance(&feature.geometry())))
+            .filter(|&(_, d)| d <= options.tolerance)
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        match nearest {
+            Some((i, _)) => matched[i].push(feature),
+            None => unmatched += 1,
+        }
+    }
+    log::debug!("Unmatched Features: {}", unmatched);
+
+    log::info!("Checking Sampling");
+    let mut report = CoverageReport {
+        points: vec![],
+        missing: 0,
+        partial: 0,
+        complete: 0,
+        unmatched,
+        follow_up: PathData::default(),
+    };
+    let mut follow_up = (vec![], vec![]);
+    for (i, (point, sampling)) in points.iter().zip(path.sampling()).enumerate() {
+        let features = &matched[i];
+        let repeat = sampling.repeat() as usize;
+        let missing_layers = sampling
+            .layers()
+            .iter()
+            .filter(|&&l| features.iter().filter(|f| f.layer() == l).count() < repeat)
+            .copied()
+            .collect::<Vec<_>>();
+        let missing_depths = sampling
+            .depths()
+            .iter()
+            .filter(|&&d| {
+                let near = features
+                    .iter()
+                    .filter(|f| (f.depth() - d).abs() <= options.depth_tolerance);
+                near.count() < repeat
+            })
+            .copied()
+            .collect::<Vec<_>>();
+
+        let coverage = if features.is_empty() {
+            report.missing += 1;
+            Coverage::Missing
+        } else if missing_layers.is_empty() && missing_depths.is_empty() {
+            report.complete += 1;
+            Coverage::Complete
+        } else {
+            report.partial += 1;
+            Coverage::Partial
+        };
+        log::debug!("Collection Point {}: {:?}", i, coverage);
+
+        if coverage != Coverage::Complete {
+            follow_up.0.push(*point);
+            follow_up.1.push(SamplingInstructions::new(
+                missing_layers.clone(),
+                missing_depths.clone(),
+                sampling.dwell_time(),
+                sampling.repeat(),
+                sampling.label().map(String::from),
+            )?);
+        }
+        report.points.push(PointCoverage {
+            index: i,
+            coordinate: point.x_y(),
+            coverage,
+            samples: features.len(),
+            missing_layers,
+            missing_depths,
+        });
+    }
+
+    // Visiting the missed points in the order they were planned
+    let (points, sampling): (Vec<Point<f64>>, _) = follow_up;
+    report.follow_up = PathData::new(
+        points.iter().copied().collect::<LineString<f64>>(),
+        MultiPoint(points),
+    )
+    .with_sampling(sampling)?
+    .with_home(path.home(), path.end());
+    Ok(report)
+}
+
+/// Compare the collection points of a path with the data collected by the boat.
+#[tauri::command]
+pub fn coverage_report(
+    path: PathData,
+    data: BoatData,
+    options: Option<CoverageOptions>,
+) -> Result<CoverageReport, String> {
+    log::info!("Checking Coverage of Path");
+    coverage(&path, &data, &options.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::GeodesicDestination;
+    use geo_types::point;
+
+    use super::*;
+    use crate::data::BoatDataFeature;
+
+    /// Builds boat data from the position, depth and layer of each feature.
+    fn data(features: &[(Point<f64>, f64, Layer)]) -> BoatData {
+        BoatData::from_samples(
+            features
+                .iter()
+                .map(|&(p, depth, layer)| {
+                    BoatDataFeature::sample(p, 28.0, depth, layer, "2024-01-01T00:00:00Z")
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn reports_missed_points() {
+        let points = [
+            point!(x: 101.7, y: 3.1),
+            point!(x: 101.701, y: 3.1),
+            point!(x: 101.702, y: 3.1),
+        ];
+        let home = point!(x: 101.69, y: 3.1);
+        let path = PathData::new(
+            points.iter().copied().collect(),
+            MultiPoint(points.to_vec()),
+        )
+        .with_sampling(vec![
+            Default::default(),
+            Default::default(),
+            SamplingInstructions::new(vec![], vec![1.0, 5.0], None, 2, None).unwrap(),
+        ])
+        .unwrap()
+        .with_home(Some(home), None);
+
+        let near = |p: Point<f64>| p.geodesic_destination(45.0, 3.0);
+        let data = data(&[
+            (near(points[0]), 0.0, Layer::Surface),
+            (near(points[0]), 2.0, Layer::Middle),
+            (near(points[0]), 4.0, Layer::SeaBed),
+            (near(points[2]), 1.2, Layer::Surface),
+            (near(points[2]), 0.9, Layer::Surface),
+            (near(points[2]), 5.0, Layer::SeaBed),
+            (point!(x: 101.8, y: 3.1), 0.0, Layer::Surface),
+        ]);
+        let report = coverage(&path, &data, &CoverageOptions::default()).unwrap();
+
+        let coverages = report
+            .points()
+            .iter()
+            .map(|p| p.coverage())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            coverages,
+            [Coverage::Complete, Coverage::Missing, Coverage::Partial]
+        );
+        assert_eq!(
+            (report.complete(), report.missing(), report.partial()),
+            (1, 1, 1)
+        );
+        assert_eq!(report.unmatched(), 1);
+        assert_eq!(report.points()[2].samples(), 3);
+        assert_eq!(report.points()[2].missing_depths(), [5.0]);
+
+        let follow_up = report.follow_up();
+        assert_eq!(follow_up.collection_points().0, [points[1], points[2]]);
+        assert_eq!(follow_up.path().0.len(), 2);
+        assert_eq!(follow_up.sampling()[0], Default::default());
+        assert_eq!(follow_up.sampling()[1].depths(), [5.0]);
+        assert_eq!(follow_up.home(), Some(home));
+    }
+
+    #[test]
+    fn counts_data_once() {
+        // Data between two close points only counts towards the nearest
+        let points = vec![point!(x: 101.7, y: 3.1), point!(x: 101.7001, y: 3.1)];
+        let path = PathData::new(points.iter().copied().collect(), MultiPoint(points.clone()));
+        let data = data(&[
+            (point!(x: 101.70002, y: 3.1), 0.0, Layer::Surface),
+            (point!(x: 101.70002, y: 3.1), 0.0, Layer::Middle),
+            (point!(x: 101.70002, y: 3.1), 0.0, Layer::SeaBed),
+        ]);
+        let report = coverage(&path, &data, &CoverageOptions::default()).unwrap();
+        assert_eq!(report.points()[0].coverage(), Coverage::Complete);
+        assert_eq!(report.points()[1].coverage(), Coverage::Missing);
+
+        let options = CoverageOptions::new(-1.0, 0.5);
+        assert!(coverage(&path, &data, &options).is_err());
+    }
+}
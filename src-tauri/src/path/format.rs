@@ -0,0 +1,246 @@
+//! File formats paths can be imported from and exported to.
+
+mod gpx;
+mod kml;
+mod qgc;
+
+use std::{fmt::Display, path::Path, str::FromStr};
+
+use geo_types::{LineString, MultiPoint, Point};
+use quick_xml::{events::Event, Reader};
+
+use super::{sampling::SamplingInstructions, PathData};
+
+/// The type of the waypoint or placemark marking the home point in GPX and KML.
+const HOME: &str = "Home";
+
+/// The type of the waypoint or placemark marking the end point in GPX and KML.
+const END: &str = "End";
+
+/// Creates a point from a longitude and latitude, checking they are on the globe.
+fn point(lng: f64, lat: f64) -> Result<Point<f64>, String> {
+    if !((-180.0..=180.0).contains(&lng) && (-90.0..=90.0).contains(&lat)) {
+        return Err(format!("Invalid Coordinate: {lng}, {lat}"));
+    }
+    Ok(Point::new(lng, lat))
+}
+
+/// Gets the name of a collection point in GPX and KML, its label if it has one.
+fn point_name(index: usize, instructions: &SamplingInstructions) -> String {
+    instructions
+        .label()
+        .map(String::from)
+        .unwrap_or_else(|| format!("Collection Point {}", index + 1))
+}
+
+/// Writes sampling instructions as JSON for GPX and KML, if they are not the default.
+fn write_sampling(instructions: &SamplingInstructions) -> Result<Option<String>, String> {
+    if *instructions == SamplingInstructions::default() {
+        return Ok(None);
+    }
+    serde_json::to_string(instructions)
+        .map(Some)
+        .map_err(|e| e.to_string())
+}
+
+/// Reads sampling instructions written by `write_sampling`.
+///
+/// Text that is not sampling instructions, such as a description from another application, is
+/// read as the default instructions.
+fn read_sampling(text: Option<&str>) -> Result<SamplingInstructions, String> {
+    let instructions = match text.map(serde_json::from_str::<SamplingInstructions>) {
+        Some(Ok(v)) => v,
+        Some(Err(e)) => {
+            log::debug!("Not Sampling Instructions: {}", e);
+            SamplingInstructions::default()
+        }
+        None => SamplingInstructions::default(),
+    };
+    instructions.validate()?;
+    Ok(instructions)
+}
+
+/// What a point read from GPX or KML was marked with.
+#[derive(Debug, Clone, Default)]
+struct PointData {
+    /// The type of the point, `HOME` or `END` for the home and end points.
+    kind: Option<String>,
+    /// The sampling instructions written by `write_sampling`.
+    sampling: Option<String>,
+}
+
+/// Builds a path from the points read from GPX or KML.
+///
+/// Points with the `HOME` or `END` type become the home and end points, the other points become
+/// collection points with sampling instructions read with `read_sampling`.
+fn build_path(
+    path: Vec<Point<f64>>,
+    points: Vec<(Point<f64>, PointData)>,
+) -> Result<PathData, String> {
+    let mut home = None;
+    let mut end = None;
+    let mut collection_points = Vec::new();
+    let mut sampling = Vec::new();
+    for (p, data) in points {
+        match data.kind.as_deref() {
+            Some(HOME) if home.is_none() => home = Some(p),
+            Some(END) if end.is_none() => end = Some(p),
+            _ => {
+                collection_points.push(p);
+                sampling.push(read_sampling(data.sampling.as_deref())?);
+            }
+        }
+    }
+    Ok(
+        PathData::new(LineString::from(path), MultiPoint(collection_points))
+            .with_sampling(sampling)?
+            .with_home(home, end),
+    )
+}
+
+/// A file format for paths.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathFormat {
+    /// The two-feature GeoJSON used by the application.
+    GeoJson,
+    /// GPS Exchange Format.
+    Gpx,
+    /// Keyhole Markup Language used by Google Earth.
+    Kml,
+    /// QGroundControl `.plan` mission.
+    QgcPlan,
+    /// Legacy `QGC WPL 110` waypoint file used by ArduPilot.
+    QgcWpl,
+}
+
+impl PathFormat {
+    /// Guesses the format from the extension of a file.
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "geojson" | "json" => Some(Self::GeoJson),
+            "gpx" => Some(Self::Gpx),
+            "kml" => Some(Self::Kml),
+            "plan" => Some(Self::QgcPlan),
+            "waypoints" | "mission" => Some(Self::QgcWpl),
+            _ => None,
+        }
+    }
+
+    /// Guesses the format from the contents of a file.
+    pub fn from_content(content: &str) -> Option<Self> {
+        let content = content.trim_start_matches('\u{feff}').trim_start();
+        if content.starts_with('{') {
+            return Some(if qgc::is_plan(content) {
+                Self::QgcPlan
+            } else {
+                Self::GeoJson
+            });
+        }
+        if content.starts_with(qgc::WPL_HEADER) {
+            return Some(Self::QgcWpl);
+        }
+
+        // Looking for the root element of an XML document
+        let mut reader = Reader::from_str(content);
+        loop {
+            match reader.read_event() {
+                Ok(Event::Start(e)) | Ok(Event::Empty(e)) => {
+                    return match e.local_name().as_ref() {
+                        b"gpx" => Some(Self::Gpx),
+                        b"kml" => Some(Self::Kml),
+                        _ => None,
+                    }
+                }
+                Ok(Event::Eof) | Err(_) => return None,
+                _ => continue,
+            }
+        }
+    }
+
+    /// Detects the format of a file from its contents, falling back to its extension.
+    pub fn detect(path: &Path, content: &str) -> Option<Self> {
+        Self::from_content(content).or_else(|| Self::from_extension(path))
+    }
+
+    /// Parses a path written in this format.
+    pub fn parse(&self, content: &str) -> Result<PathData, String> {
+        log::debug!("Parsing Path as {}", self);
+        match self {
+            Self::GeoJson => PathData::from_str(content),
+            Self::Gpx => gpx::read(content),
+            Self::Kml => kml::read(content),
+            Self::QgcPlan => qgc::read_plan(content),
+            Self::QgcWpl => qgc::read_wpl(content),
+        }
+    }
+
+    /// Writes a path in this format.
+    pub fn write(&self, path: &PathData) -> Result<String, String> {
+        log::debug!("Writing Path as {}", self);
+        match self {
+            Self::GeoJson => Ok(path.to_string()),
+            Self::Gpx => gpx::write(path),
+            Self::Kml => kml::write(path),
+            Self::QgcPlan => qgc::write_plan(path),
+            Self::QgcWpl => qgc::write_wpl(path),
+        }
+    }
+}
+
+impl Display for PathFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::GeoJson => "GeoJSON",
+            Self::Gpx => "GPX",
+            Self::Kml => "KML",
+            Self::QgcPlan => "QGroundControl Plan",
+            Self::QgcWpl => "QGC WPL 110",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_format_from_extension() {
+        let detect = |p: &str| PathFormat::from_extension(Path::new(p));
+        assert_eq!(detect("lake.geojson"), Some(PathFormat::GeoJson));
+        assert_eq!(detect("lake.json"), Some(PathFormat::GeoJson));
+        assert_eq!(detect("lake.GPX"), Some(PathFormat::Gpx));
+        assert_eq!(detect("lake.kml"), Some(PathFormat::Kml));
+        assert_eq!(detect("lake.plan"), Some(PathFormat::QgcPlan));
+        assert_eq!(detect("lake.waypoints"), Some(PathFormat::QgcWpl));
+        assert_eq!(detect("lake.txt"), None);
+        assert_eq!(detect("lake"), None);
+    }
+
+    #[test]
+    fn detects_format_from_content() {
+        let gpx = r#"<?xml version="1.0"?><!-- Route --><gpx version="1.1"></gpx>"#;
+        let kml = r#"<?xml version="1.0"?><kml xmlns="http://www.opengis.net/kml/2.2"/>"#;
+        assert_eq!(PathFormat::from_content(gpx), Some(PathFormat::Gpx));
+        assert_eq!(PathFormat::from_content(kml), Some(PathFormat::Kml));
+        assert_eq!(
+            PathFormat::from_content("\u{feff} {}"),
+            Some(PathFormat::GeoJson)
+        );
+        assert_eq!(
+            PathFormat::from_content(r#"{"fileType": "Plan"}"#),
+            Some(PathFormat::QgcPlan)
+        );
+        assert_eq!(
+            PathFormat::from_content("QGC WPL 110\n"),
+            Some(PathFormat::QgcWpl)
+        );
+        assert_eq!(PathFormat::from_content("<html></html>"), None);
+
+        // Content wins over a misleading extension
+        let path = Path::new("route.json");
+        assert_eq!(PathFormat::detect(path, gpx), Some(PathFormat::Gpx));
+        assert_eq!(PathFormat::detect(path, ""), Some(PathFormat::GeoJson));
+    }
+}
@@ -0,0 +1,273 @@
+//! Reading and writing paths as GPS Exchange Format.
+//!
+//! Routes (`rte`) and tracks (`trk`) become the path and waypoints (`wpt`) become the collection
+//! points, apart from the waypoints marking the home and end points.
+
+use geo_types::Point;
+use quick_xml::{
+    events::{BytesDecl, BytesStart, BytesText, Event},
+    Reader, Writer,
+};
+
+use super::{build_path, point, point_name, write_sampling, PathData, PointData, END, HOME};
+
+/// The GPX namespace.
+const NAMESPACE: &str = "http://www.topografix.com/GPX/1/1";
+
+/// Reads the coordinate of a `wpt`, `rtept` or `trkpt` element.
+fn waypoint(element: &BytesStart) -> Result<Point<f64>, String> {
+    let attribute = |name: &str| -> Result<f64, String> {
+        element
+            .try_get_attribute(name)
+            .map_err(|e| e.to_string())?
+            .ok_or(format!("Invalid GPX: Waypoint Missing {name}"))?
+            .unescape_value()
+            .map_err(|e| e.to_string())?
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid GPX: Invalid Waypoint {name}"))
+    };
+    point(attribute("lon")?, attribute("lat")?)
+}
+
+/// Reads a path from a GPX document.
+///
+/// Routes are used for the path if there are any, otherwise the points of all tracks are joined.
+pub(super) fn read(content: &str) -> Result<PathData, String> {
+    let mut reader = Reader::from_str(content);
+    reader.trim_text(true);
+
+    let mut route = Vec::new();
+    let mut track = Vec::new();
+    // Each waypoint with the contents of its `type` and `desc` elements
+    let mut waypoints = Vec::new();
+    let mut current: Option<(Point<f64>, PointData)> = None;
+    let mut field: Option<Vec<u8>> = None;
+    loop {
+        match reader.read_event().map_err(|e| e.to_string())? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"wpt" => current = Some((waypoint(&e)?, PointData::default())),
+                b"rtept" => route.push(waypoint(&e)?),
+                b"trkpt" => track.push(waypoint(&e)?),
+                name => field = Some(name.to_vec()),
+            },
+            Event::Empty(e) => match e.local_name().as_ref() {
+                b"wpt" => waypoints.push((waypoint(&e)?, PointData::default())),
+                b"rtept" => route.push(waypoint(&e)?),
+                b"trkpt" => track.push(waypoint(&e)?),
+                _ => {}
+            },
+            Event::Text(e) => {
+                if let (Some(w), Some(name)) = (current.as_mut(), field.as_deref()) {
+                    let text = e.unescape().map_err(|e| e.to_string())?.into_owned();
+                    match name {
+                        b"type" => w.1.kind = Some(text),
+                        b"desc" => w.1.sampling = Some(text),
+                        _ => {}
+                    }
+                }
+            }
+            Event::End(e) => {
+                if e.local_name().as_ref() == b"wpt" {
+                    waypoints.extend(current.take());
+                }
+                field = None;
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    log::debug!(
+        "Route Points: {}, Track Points: {}, Waypoints: {}",
+        route.len(),
+        track.len(),
+        waypoints.len()
+    );
+
+    let path = if route.is_empty() { track } else { route };
+    build_path(path, waypoints)
+}
+
+/// Writes a waypoint with a name, a description and a type.
+fn write_waypoint<W: std::io::Write>(
+    writer: &mut Writer<W>,
+    p: Point<f64>,
+    name: &str,
+    description: Option<&str>,
+    kind: Option<&str>,
+) -> Result<(), quick_xml::Error> {
+    writer
+        .create_element("wpt")
+        .with_attribute(("lat", p.y().to_string().as_str()))
+        .with_attribute(("lon", p.x().to_string().as_str()))
+        .write_inner_content(|writer| {
+            writer
+                .create_element("name")
+                .write_text_content(BytesText::new(name))?;
+            if let Some(v) = description {
+                writer
+                    .create_element("desc")
+                    .write_text_content(BytesText::new(v))?;
+            }
+            if let Some(v) = kind {
+                writer
+                    .create_element("type")
+                    .write_text_content(BytesText::new(v))?;
+            }
+            Ok::<(), quick_xml::Error>(())
+        })?;
+    Ok(())
+}
+
+/// Writes a path as a GPX document with a route and waypoints.
+///
+/// The home and end points are waypoints with the `Home` and `End` types, and sampling
+/// instructions other than the default are written as JSON in the description of each
+/// collection point.
+pub(super) fn write(path: &PathData) -> Result<String, String> {
+    let sampling = path
+        .sampling()
+        .iter()
+        .map(write_sampling)
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer
+        .write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))
+        .map_err(|e| e.to_string())?;
+    writer
+        .create_element("gpx")
+        .with_attribute(("version", "1.1"))
+        .with_attribute(("creator", "AWTC-R Desktop"))
+        .with_attribute(("xmlns", NAMESPACE))
+        .write_inner_content(|writer| {
+            if let Some(p) = path.home() {
+                write_waypoint(writer, p, HOME, None, Some(HOME))?;
+            }
+            for (i, (p, instructions)) in path
+                .collection_points()
+                .iter()
+                .zip(path.sampling())
+                .enumerate()
+            {
+                let name = point_name(i, instructions);
+                write_waypoint(writer, *p, &name, sampling[i].as_deref(), None)?;
+            }
+            if let Some(p) = path.end() {
+                write_waypoint(writer, p, END, None, Some(END))?;
+            }
+
+            writer.create_element("rte").write_inner_content(|writer| {
+                writer
+                    .create_element("name")
+                    .write_text_content(BytesText::new("Path"))?;
+                for p in path.path().points() {
+                    writer
+                        .create_element("rtept")
+                        .with_attribute(("lat", p.y().to_string().as_str()))
+                        .with_attribute(("lon", p.x().to_string().as_str()))
+                        .write_empty()?;
+                }
+                Ok::<(), quick_xml::Error>(())
+            })?;
+            Ok::<(), quick_xml::Error>(())
+        })
+        .map_err(|e| e.to_string())?;
+
+    String::from_utf8(writer.into_inner()).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::{line_string, point, MultiPoint};
+
+    use super::*;
+    use crate::{data::Layer, path::sampling::SamplingInstructions};
+
+    #[test]
+    fn reads_routes_and_waypoints() {
+        let gpx = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+                <wpt lat="3.1" lon="101.7"><name>A</name></wpt>
+                <rte>
+                    <rtept lat="3.0" lon="101.6"/>
+                    <rtept lat="3.2" lon="101.8"><ele>0</ele></rtept>
+                </rte>
+                <trk><trkseg><trkpt lat="0" lon="0"/></trkseg></trk>
+            </gpx>"#;
+        let path = read(gpx).unwrap();
+
+        assert_eq!(
+            path.path(),
+            &line_string![(x: 101.6, y: 3.0), (x: 101.8, y: 3.2)]
+        );
+        assert_eq!(path.collection_points().0, vec![point!(x: 101.7, y: 3.1)]);
+    }
+
+    #[test]
+    fn joins_track_segments() {
+        let gpx = r#"<gpx><trk>
+                <trkseg><trkpt lat="1" lon="2"/><trkpt lat="3" lon="4"/></trkseg>
+                <trkseg><trkpt lat="5" lon="6"/></trkseg>
+            </trk></gpx>"#;
+        let path = read(gpx).unwrap();
+
+        assert_eq!(
+            path.path(),
+            &line_string![(x: 2.0, y: 1.0), (x: 4.0, y: 3.0), (x: 6.0, y: 5.0)]
+        );
+        assert!(path.collection_points().0.is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_waypoints() {
+        assert!(read(r#"<gpx><wpt lat="1"/></gpx>"#).is_err());
+        assert!(read(r#"<gpx><wpt lat="north" lon="1"/></gpx>"#).is_err());
+        assert!(read(r#"<gpx><wpt lat="91" lon="1"/></gpx>"#).is_err());
+        assert!(read(r#"<gpx><rte><rtept lat="1" lon="1"></rte></gpx>"#).is_err());
+    }
+
+    #[test]
+    fn round_trips() {
+        let path = PathData::new(
+            line_string![(x: 101.6, y: 3.0), (x: 101.8, y: 3.2), (x: 101.9, y: 3.25)],
+            MultiPoint(vec![point!(x: 101.7, y: 3.1), point!(x: 101.85, y: 3.225)]),
+        );
+        let gpx = write(&path).unwrap();
+        let read = read(&gpx).unwrap();
+
+        assert_eq!(read.path(), path.path());
+        assert_eq!(read.collection_points(), path.collection_points());
+    }
+
+    #[test]
+    fn round_trips_home_end_and_sampling() {
+        let instructions = SamplingInstructions::new(
+            vec![Layer::SeaBed],
+            vec![2.5],
+            Some(30.0),
+            2,
+            Some(String::from("Inlet")),
+        )
+        .unwrap();
+        let path = PathData::new(
+            line_string![(x: 101.6, y: 3.0), (x: 101.8, y: 3.2)],
+            MultiPoint(vec![point!(x: 101.7, y: 3.1), point!(x: 101.75, y: 3.15)]),
+        )
+        .with_sampling(vec![instructions, SamplingInstructions::default()])
+        .unwrap()
+        .with_home(
+            Some(point!(x: 101.5, y: 3.0)),
+            Some(point!(x: 101.9, y: 3.3)),
+        );
+        let written = write(&path).unwrap();
+        assert!(written.contains("Inlet"));
+        assert!(written.contains("Collection Point 2"));
+
+        let read = read(&written).unwrap();
+        assert_eq!(read.collection_points(), path.collection_points());
+        assert_eq!(read.sampling(), path.sampling());
+        assert_eq!(read.home(), path.home());
+        assert_eq!(read.end(), path.end());
+    }
+}
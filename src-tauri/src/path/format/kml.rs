@@ -0,0 +1,321 @@
+//! Reading and writing paths as Keyhole Markup Language.
+//!
+//! `LineString` placemarks become the path and `Point` placemarks become the collection points,
+//! apart from the placemarks marking the home and end points.
+
+use geo_types::Point;
+use quick_xml::{
+    events::{BytesDecl, BytesText, Event},
+    Reader, Writer,
+};
+
+use super::{build_path, point, point_name, write_sampling, PathData, PointData, END, HOME};
+
+/// The KML namespace.
+const NAMESPACE: &str = "http://www.opengis.net/kml/2.2";
+
+/// Parses the contents of a `coordinates` element.
+///
+/// Each tuple is `longitude,latitude[,altitude]` separated by whitespace.
+fn coordinates(text: &str) -> Result<Vec<Point<f64>>, String> {
+    text.split_whitespace()
+        .map(|tuple| {
+            let mut values = tuple.split(',').map(|v| {
+                v.parse::<f64>()
+                    .map_err(|_| format!("Invalid KML: Invalid Coordinates {tuple}"))
+            });
+            match (values.next(), values.next()) {
+                (Some(lng), Some(lat)) => point(lng?, lat?),
+                _ => Err(format!("Invalid KML: Invalid Coordinates {tuple}")),
+            }
+        })
+        .collect()
+}
+
+/// Formats points as the contents of a `coordinates` element.
+fn format_coordinates<I: IntoIterator<Item = Point<f64>>>(points: I) -> String {
+    points
+        .into_iter()
+        .map(|p| format!("{},{}", p.x(), p.y()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Reads a path from a KML document.
+///
+/// The coordinates of every `LineString` are joined in document order.
+pub(super) fn read(content: &str) -> Result<PathData, String> {
+    let mut reader = Reader::from_str(content);
+    reader.trim_text(true);
+
+    let mut elements = Vec::new();
+    let mut path = Vec::new();
+    // Each point with the `type` and `sampling` data of its placemark
+    let mut points = Vec::new();
+    let mut placemark: Option<(Vec<Point<f64>>, PointData)> = None;
+    let mut data_name: Option<String> = None;
+    loop {
+        let text = match reader.read_event().map_err(|e| e.to_string())? {
+            Event::Start(e) => {
+                match e.local_name().as_ref() {
+                    b"Placemark" => placemark = Some((vec![], PointData::default())),
+                    b"Data" => {
+                        data_name = e
+                            .try_get_attribute("name")
+                            .map_err(|e| e.to_string())?
+                            .map(|v| v.unescape_value().map(String::from))
+                            .transpose()
+                            .map_err(|e| e.to_string())?
+                    }
+                    _ => {}
+                }
+                elements.push(e.local_name().as_ref().to_vec());
+                continue;
+            }
+            Event::End(e) => {
+                if e.local_name().as_ref() == b"Placemark" {
+                    if let Some((coordinates, data)) = placemark.take() {
+                        points.extend(coordinates.into_iter().map(|p| (p, data.clone())));
+                    }
+                }
+                elements.pop();
+                continue;
+            }
+            Event::Text(e) => e.unescape().map_err(|e| e.to_string())?.into_owned(),
+            Event::CData(e) => {
+                String::from_utf8(e.into_inner().into_owned()).map_err(|e| e.to_string())?
+            }
+            Event::Eof => break,
+            _ => continue,
+        };
+
+        let parent = elements.iter().rev().nth(1).map(Vec::as_slice);
+        match (elements.last().map(Vec::as_slice), parent) {
+            (Some(b"coordinates"), Some(b"LineString")) => path.extend(coordinates(&text)?),
+            (Some(b"coordinates"), Some(b"Point")) => match placemark.as_mut() {
+                Some(v) => v.0.extend(coordinates(&text)?),
+                None => points.extend(
+                    coordinates(&text)?
+                        .into_iter()
+                        .map(|p| (p, PointData::default())),
+                ),
+            },
+            (Some(b"coordinates"), _) => log::debug!("Ignoring Coordinates in {:?}", parent),
+            (Some(b"value"), Some(b"Data")) => {
+                if let Some(v) = placemark.as_mut() {
+                    match data_name.as_deref() {
+                        Some("type") => v.1.kind = Some(text),
+                        Some("sampling") => v.1.sampling = Some(text),
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    log::debug!("Path Points: {}, Points: {}", path.len(), points.len());
+
+    build_path(path, points)
+}
+
+/// Writes a placemark for a point with a name and extended data.
+fn write_point<W: std::io::Write>(
+    writer: &mut Writer<W>,
+    p: Point<f64>,
+    name: &str,
+    data: &[(&str, &str)],
+) -> Result<(), quick_xml::Error> {
+    writer
+        .create_element("Placemark")
+        .write_inner_content(|writer| {
+            writer
+                .create_element("name")
+                .write_text_content(BytesText::new(name))?;
+            if !data.is_empty() {
+                writer
+                    .create_element("ExtendedData")
+                    .write_inner_content(|writer| {
+                        for (key, value) in data {
+                            writer
+                                .create_element("Data")
+                                .with_attribute(("name", *key))
+                                .write_inner_content(|writer| {
+                                    writer
+                                        .create_element("value")
+                                        .write_text_content(BytesText::new(value))?;
+                                    Ok::<(), quick_xml::Error>(())
+                                })?;
+                        }
+                        Ok::<(), quick_xml::Error>(())
+                    })?;
+            }
+            writer
+                .create_element("Point")
+                .write_inner_content(|writer| {
+                    writer
+                        .create_element("coordinates")
+                        .write_text_content(BytesText::new(&format_coordinates([p])))?;
+                    Ok::<(), quick_xml::Error>(())
+                })?;
+            Ok::<(), quick_xml::Error>(())
+        })?;
+    Ok(())
+}
+
+/// Writes a path as a KML document with one placemark for the path and one for each point.
+///
+/// The home and end points are placemarks with `type` data of `Home` and `End`, and sampling
+/// instructions other than the default are written as JSON in the `sampling` data of each
+/// collection point.
+pub(super) fn write(path: &PathData) -> Result<String, String> {
+    let sampling = path
+        .sampling()
+        .iter()
+        .map(write_sampling)
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer
+        .write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))
+        .map_err(|e| e.to_string())?;
+    writer
+        .create_element("kml")
+        .with_attribute(("xmlns", NAMESPACE))
+        .write_inner_content(|writer| {
+            writer
+                .create_element("Document")
+                .write_inner_content(|writer| {
+                    writer
+                        .create_element("Placemark")
+                        .write_inner_content(|writer| {
+                            writer
+                                .create_element("name")
+                                .write_text_content(BytesText::new("Path"))?;
+                            writer
+                                .create_element("LineString")
+                                .write_inner_content(|writer| {
+                                    writer
+                                        .create_element("tessellate")
+                                        .write_text_content(BytesText::new("1"))?;
+                                    writer.create_element("coordinates").write_text_content(
+                                        BytesText::new(&format_coordinates(path.path().points())),
+                                    )?;
+                                    Ok::<(), quick_xml::Error>(())
+                                })?;
+                            Ok::<(), quick_xml::Error>(())
+                        })?;
+
+                    if let Some(p) = path.home() {
+                        write_point(writer, p, HOME, &[("type", HOME)])?;
+                    }
+                    for (i, (p, instructions)) in path
+                        .collection_points()
+                        .iter()
+                        .zip(path.sampling())
+                        .enumerate()
+                    {
+                        let data = match &sampling[i] {
+                            Some(v) => vec![("sampling", v.as_str())],
+                            None => vec![],
+                        };
+                        write_point(writer, *p, &point_name(i, instructions), &data)?;
+                    }
+                    if let Some(p) = path.end() {
+                        write_point(writer, p, END, &[("type", END)])?;
+                    }
+                    Ok::<(), quick_xml::Error>(())
+                })?;
+            Ok::<(), quick_xml::Error>(())
+        })
+        .map_err(|e| e.to_string())?;
+
+    String::from_utf8(writer.into_inner()).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::{line_string, point, MultiPoint};
+
+    use super::*;
+    use crate::{data::Layer, path::sampling::SamplingInstructions};
+
+    #[test]
+    fn reads_placemarks() {
+        let kml = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <kml xmlns="http://www.opengis.net/kml/2.2"><Document>
+                <Placemark><name>Route</name><LineString>
+                    <coordinates>
+                        101.6,3.0,0 101.8,3.2,0
+                    </coordinates>
+                </LineString></Placemark>
+                <Placemark><Point><coordinates>101.7,3.1</coordinates></Point></Placemark>
+                <Placemark><Polygon><outerBoundaryIs><LinearRing>
+                    <coordinates>0,0 1,0 1,1 0,0</coordinates>
+                </LinearRing></outerBoundaryIs></Polygon></Placemark>
+            </Document></kml>"#;
+        let path = read(kml).unwrap();
+
+        assert_eq!(
+            path.path(),
+            &line_string![(x: 101.6, y: 3.0), (x: 101.8, y: 3.2)]
+        );
+        assert_eq!(path.collection_points().0, vec![point!(x: 101.7, y: 3.1)]);
+    }
+
+    #[test]
+    fn rejects_invalid_coordinates() {
+        let kml = |c: &str| {
+            format!(
+                "<kml><Placemark><Point><coordinates>{c}</coordinates></Point></Placemark></kml>"
+            )
+        };
+        assert!(read(&kml("101.7,3.1")).is_ok());
+        assert!(read(&kml("101.7")).is_err());
+        assert!(read(&kml("east,north")).is_err());
+        assert!(read(&kml("181,0")).is_err());
+    }
+
+    #[test]
+    fn round_trips() {
+        let path = PathData::new(
+            line_string![(x: 101.6, y: 3.0), (x: 101.8, y: 3.2), (x: 101.9, y: 3.25)],
+            MultiPoint(vec![point!(x: 101.7, y: 3.1), point!(x: 101.85, y: 3.225)]),
+        );
+        let kml = write(&path).unwrap();
+        let read = read(&kml).unwrap();
+
+        assert_eq!(read.path(), path.path());
+        assert_eq!(read.collection_points(), path.collection_points());
+    }
+
+    #[test]
+    fn round_trips_home_end_and_sampling() {
+        let instructions = SamplingInstructions::new(
+            vec![Layer::SeaBed],
+            vec![2.5],
+            Some(30.0),
+            2,
+            Some(String::from("Inlet")),
+        )
+        .unwrap();
+        let path = PathData::new(
+            line_string![(x: 101.6, y: 3.0), (x: 101.8, y: 3.2)],
+            MultiPoint(vec![point!(x: 101.7, y: 3.1), point!(x: 101.75, y: 3.15)]),
+        )
+        .with_sampling(vec![instructions, SamplingInstructions::default()])
+        .unwrap()
+        .with_home(
+            Some(point!(x: 101.5, y: 3.0)),
+            Some(point!(x: 101.9, y: 3.3)),
+        );
+        let written = write(&path).unwrap();
+        assert!(written.contains("Inlet"));
+        assert!(written.contains("Collection Point 2"));
+
+        let read = read(&written).unwrap();
+        assert_eq!(read.collection_points(), path.collection_points());
+        assert_eq!(read.sampling(), path.sampling());
+        assert_eq!(read.home(), path.home());
+        assert_eq!(read.end(), path.end());
+    }
+}
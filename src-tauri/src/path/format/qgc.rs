@@ -0,0 +1,530 @@
+//! Reading and writing paths as QGroundControl missions for ArduPilot boats.
+//!
+//! Vertices of the path become navigation waypoints and collection points become loiter items
+//! where the boat holds position while sampling, and the home point is the home position of the
+//! mission. Both the `.plan` JSON format and the legacy
+//! `QGC WPL 110` text format are supported.
+
+use geo_types::{coord, Coord, LineString, MultiPoint, Point};
+use serde_json::{json, Value};
+
+use super::{point, PathData};
+use crate::path::{local::LocalProjection, sampling::SamplingInstructions};
+
+/// Header of a `QGC WPL 110` waypoint file.
+pub(super) const WPL_HEADER: &str = "QGC WPL 110";
+
+/// `MAV_CMD_NAV_WAYPOINT`: Travel to a location.
+const NAV_WAYPOINT: u64 = 16;
+/// `MAV_CMD_NAV_LOITER_UNLIM`: Hold position at a location forever.
+const NAV_LOITER_UNLIM: u64 = 17;
+/// `MAV_CMD_NAV_LOITER_TURNS`: Circle a location a number of times.
+const NAV_LOITER_TURNS: u64 = 18;
+/// `MAV_CMD_NAV_LOITER_TIME`: Hold position at a location for some seconds.
+const NAV_LOITER_TIME: u64 = 19;
+/// `MAV_CMD_NAV_SPLINE_WAYPOINT`: Travel to a location along a spline.
+const NAV_SPLINE_WAYPOINT: u64 = 82;
+
+/// `MAV_FRAME_GLOBAL`: Altitude above mean sea level.
+const FRAME_GLOBAL: u64 = 0;
+/// `MAV_FRAME_GLOBAL_RELATIVE_ALT`: Altitude relative to the home position.
+const FRAME_GLOBAL_RELATIVE_ALT: u64 = 3;
+
+/// `MAV_AUTOPILOT_ARDUPILOTMEGA`.
+const FIRMWARE_ARDUPILOT: u64 = 3;
+/// `MAV_TYPE_GROUND_ROVER`, which ArduPilot also uses for boats.
+const VEHICLE_GROUND_ROVER: u64 = 10;
+
+/// Seconds the boat holds position at collection points without their own dwell time.
+const LOITER_TIME: f64 = 10.0;
+
+/// Distance in metres within which a collection point replaces a waypoint.
+const TOLERANCE: f64 = 1e-3;
+
+/// An item of a mission with a location.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MissionItem {
+    /// Travel through a location.
+    Waypoint(Coord<f64>),
+    /// Hold position at a location for some seconds to collect a sample.
+    Loiter(Coord<f64>, f64),
+}
+
+impl MissionItem {
+    /// Gets the `MAV_CMD` of the item.
+    fn command(&self) -> u64 {
+        match self {
+            Self::Waypoint(_) => NAV_WAYPOINT,
+            Self::Loiter(..) => NAV_LOITER_TIME,
+        }
+    }
+
+    /// Gets the four command parameters of the item.
+    fn params(&self) -> [f64; 4] {
+        match self {
+            Self::Waypoint(_) => [0.0; 4],
+            Self::Loiter(_, time) => [*time, 0.0, 0.0, 0.0],
+        }
+    }
+
+    /// Gets the longitude and latitude of the item.
+    fn coord(&self) -> Coord<f64> {
+        match self {
+            Self::Waypoint(c) | Self::Loiter(c, _) => *c,
+        }
+    }
+
+    /// Creates an item from a `MAV_CMD`, its first parameter and location.
+    ///
+    /// Returns `None` for commands without a location the boat travels to.
+    fn from_command(command: u64, param: f64, lng: f64, lat: f64) -> Result<Option<Self>, String> {
+        Ok(match command {
+            NAV_WAYPOINT | NAV_SPLINE_WAYPOINT => Some(Self::Waypoint(point(lng, lat)?.0)),
+            NAV_LOITER_TIME => Some(Self::Loiter(point(lng, lat)?.0, param)),
+            NAV_LOITER_UNLIM | NAV_LOITER_TURNS => {
+                Some(Self::Loiter(point(lng, lat)?.0, LOITER_TIME))
+            }
+            _ => {
+                log::debug!("Ignoring Mission Command: {}", command);
+                None
+            }
+        })
+    }
+}
+
+/// Orders the vertices and collection points of a path into mission items.
+///
+/// Each collection point is placed at its nearest position along the path, replacing the
+/// waypoint of a vertex it lies on, and held for its dwell time once for each repeat.
+fn mission(path: &PathData) -> Vec<MissionItem> {
+    let vertices = &path.path().0;
+    let points = path.collection_points().0.iter().map(|p| p.0);
+    let default = SamplingInstructions::default();
+    let times = (0..path.collection_points().0.len()).map(|i| {
+        let sampling = path.sampling().get(i).unwrap_or(&default);
+        sampling.dwell_time().unwrap_or(LOITER_TIME) * f64::from(sampling.repeat())
+    });
+    let projection = match LocalProjection::around(vertices.iter().copied().chain(points.clone())) {
+        Some(v) => v,
+        None => return vec![],
+    };
+    let projected = vertices
+        .iter()
+        .map(|&c| projection.project(c))
+        .collect::<Vec<_>>();
+
+    // Position along the path counted in segments, with whole numbers at the vertices
+    let mut items = vertices
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| (i as f64, MissionItem::Waypoint(c)))
+        .collect::<Vec<_>>();
+    for (c, time) in points.zip(times) {
+        let p = projection.project(c);
+        let position = projected
+            .windows(2)
+            .enumerate()
+            .map(|(i, w)| {
+                let (a, b) = (w[0], w[1]);
+                let d = b - a;
+                let length = d.x * d.x + d.y * d.y;
+                let t = if length > 0.0 {
+                    (((p - a).x * d.x + (p - a).y * d.y) / length).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                let closest = a + d * t;
+                let offset = p - closest;
+                (offset.x.hypot(offset.y), i as f64 + t)
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map_or(0.0, |(_, position)| position);
+        items.push((position, MissionItem::Loiter(c, time)));
+    }
+    // Waypoints come before loiters at the same position, which keeps the sort stable
+    items.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut mission: Vec<MissionItem> = Vec::with_capacity(items.len());
+    for (_, item) in items {
+        if let (Some(MissionItem::Waypoint(last)), MissionItem::Loiter(c, _)) =
+            (mission.last(), item)
+        {
+            let offset = projection.project(*last) - projection.project(c);
+            if offset.x.hypot(offset.y) < TOLERANCE {
+                mission.pop();
+            }
+        }
+        mission.push(item);
+    }
+    mission
+}
+
+/// Builds a path from mission items and the home position.
+///
+/// Every item is part of the path and loiters are also collection points, keeping how long they
+/// hold position as their dwell time.
+fn from_mission<I: IntoIterator<Item = MissionItem>>(
+    items: I,
+    home: Option<Point<f64>>,
+) -> Result<PathData, String> {
+    let mut path = Vec::new();
+    let mut points = Vec::new();
+    let mut sampling = Vec::new();
+    for item in items {
+        if let MissionItem::Loiter(c, time) = item {
+            points.push(Point::from(c));
+            let default = SamplingInstructions::default();
+            sampling.push(if time == LOITER_TIME {
+                default
+            } else {
+                SamplingInstructions::new(default.layers().to_vec(), vec![], Some(time), 1, None)?
+            });
+        }
+        path.push(item.coord());
+    }
+    log::debug!("Path Points: {}, Points: {}", path.len(), points.len());
+    Ok(PathData::new(LineString(path), MultiPoint(points))
+        .with_sampling(sampling)?
+        .with_home(home, None))
+}
+
+/// Gets the home position of a mission, which is the start of the path if there is no home point.
+fn home(path: &PathData) -> Option<Coord<f64>> {
+    path.home().map(|p| p.0).or_else(|| {
+        path.path()
+            .0
+            .first()
+            .or_else(|| path.collection_points().0.first().map(|p| &p.0))
+            .copied()
+    })
+}
+
+/// Checks whether some JSON is a QGroundControl plan.
+pub(super) fn is_plan(content: &str) -> bool {
+    serde_json::from_str::<Value>(content)
+        .map(|v| v["fileType"] == "Plan")
+        .unwrap_or(false)
+}
+
+/// Reads the simple items of a plan, including those inside complex items.
+fn plan_items(items: &[Value], mission: &mut Vec<MissionItem>) -> Result<(), String> {
+    for item in items {
+        match item["type"].as_str() {
+            Some("SimpleItem") => {
+                let command = item["command"]
+                    .as_u64()
+                    .ok_or(String::from("Invalid Plan: Missing Command"))?;
+                let params = item["params"]
+                    .as_array()
+                    .filter(|p| p.len() == 7)
+                    .ok_or(String::from("Invalid Plan: Mission Item requires 7 Params"))?;
+                let (lat, lng) = match (params[4].as_f64(), params[5].as_f64()) {
+                    (Some(lat), Some(lng)) => (lat, lng),
+                    _ => {
+                        log::debug!("Ignoring Mission Item without Location");
+                        continue;
+                    }
+                };
+                let param = params[0].as_f64().unwrap_or(LOITER_TIME);
+                mission.extend(MissionItem::from_command(command, param, lng, lat)?);
+            }
+            Some("ComplexItem") => match item["TransectStyleComplexItem"]["Items"].as_array() {
+                Some(items) => plan_items(items, mission)?,
+                None => log::warn!("Ignoring Complex Mission Item: {}", item["complexItemType"]),
+            },
+            _ => return Err(String::from("Invalid Plan: Unknown Mission Item Type")),
+        }
+    }
+    Ok(())
+}
+
+/// Reads a path from a QGroundControl `.plan` file.
+pub(super) fn read_plan(content: &str) -> Result<PathData, String> {
+    let plan: Value = serde_json::from_str(content).map_err(|e| e.to_string())?;
+    if plan["fileType"] != "Plan" {
+        return Err(String::from("Invalid Plan: Not a QGroundControl Plan"));
+    }
+    let items = plan["mission"]["items"]
+        .as_array()
+        .ok_or(String::from("Invalid Plan: Missing Mission Items"))?;
+
+    let home = match plan["mission"]["plannedHomePosition"].as_array() {
+        Some(home) => match (
+            home.first().and_then(Value::as_f64),
+            home.get(1).and_then(Value::as_f64),
+        ) {
+            (Some(lat), Some(lng)) => Some(point(lng, lat)?),
+            _ => return Err(String::from("Invalid Plan: Invalid Home Position")),
+        },
+        None => None,
+    };
+
+    let mut mission = Vec::new();
+    plan_items(items, &mut mission)?;
+    from_mission(mission, home)
+}
+
+/// Writes a path as a QGroundControl `.plan` file for an ArduPilot boat.
+pub(super) fn write_plan(path: &PathData) -> Result<String, String> {
+    let items = mission(path)
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let [p1, p2, p3, p4] = item.params();
+            let c = item.coord();
+            json!({
+                "type": "SimpleItem",
+                "autoContinue": true,
+                "command": item.command(),
+                "doJumpId": i + 1,
+                "frame": FRAME_GLOBAL_RELATIVE_ALT,
+                "params": [p1, p2, p3, p4, c.y, c.x, 0.0],
+                "Altitude": 0.0,
+                "AltitudeMode": 1,
+                "AMSLAltAboveTerrain": null,
+            })
+        })
+        .collect::<Vec<_>>();
+    let home = home(path).map_or(json!([0.0, 0.0, 0.0]), |c| json!([c.y, c.x, 0.0]));
+
+    let plan = json!({
+        "fileType": "Plan",
+        "version": 1,
+        "groundStation": "AWTC-R Desktop",
+        "geoFence": { "circles": [], "polygons": [], "version": 2 },
+        "rallyPoints": { "points": [], "version": 2 },
+        "mission": {
+            "version": 2,
+            "firmwareType": FIRMWARE_ARDUPILOT,
+            "vehicleType": VEHICLE_GROUND_ROVER,
+            "globalPlanAltitudeMode": 1,
+            "cruiseSpeed": 1,
+            "hoverSpeed": 1,
+            "plannedHomePosition": home,
+            "items": items,
+        },
+    });
+    serde_json::to_string_pretty(&plan).map_err(|e| e.to_string())
+}
+
+/// Reads a path from a `QGC WPL 110` waypoint file.
+///
+/// The first item is the home position and becomes the home point instead of part of the path.
+pub(super) fn read_wpl(content: &str) -> Result<PathData, String> {
+    let mut lines = content
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty());
+    if lines.next() != Some(WPL_HEADER) {
+        return Err(String::from(
+            "Invalid Waypoint File: Missing QGC WPL 110 Header",
+        ));
+    }
+
+    let mut mission = Vec::new();
+    let mut home = None;
+    for line in lines {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 12 {
+            return Err(format!(
+                "Invalid Waypoint File: Expected 12 Fields in {line}"
+            ));
+        }
+        let number = |i: usize| -> Result<f64, String> {
+            fields[i]
+                .parse()
+                .map_err(|_| format!("Invalid Waypoint File: Invalid Number {}", fields[i]))
+        };
+        if fields[0] == "0" {
+            log::debug!("Reading Home Position");
+            home = Some(point(number(9)?, number(8)?)?);
+            continue;
+        }
+        let command = fields[3]
+            .parse()
+            .map_err(|_| format!("Invalid Waypoint File: Invalid Command {}", fields[3]))?;
+        mission.extend(MissionItem::from_command(
+            command,
+            number(4)?,
+            number(9)?,
+            number(8)?,
+        )?);
+    }
+    from_mission(mission, home)
+}
+
+/// Writes a path as a `QGC WPL 110` waypoint file for an ArduPilot boat.
+pub(super) fn write_wpl(path: &PathData) -> Result<String, String> {
+    let mut lines = vec![String::from(WPL_HEADER)];
+    let home = home(path).unwrap_or(coord! { x: 0.0, y: 0.0 });
+    lines.push(format!(
+        "0\t1\t{}\t{}\t0\t0\t0\t0\t{}\t{}\t0\t1",
+        FRAME_GLOBAL, NAV_WAYPOINT, home.y, home.x
+    ));
+    for (i, item) in mission(path).into_iter().enumerate() {
+        let [p1, p2, p3, p4] = item.params();
+        let c = item.coord();
+        lines.push(format!(
+            "{}\t0\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t0\t1",
+            i + 1,
+            FRAME_GLOBAL_RELATIVE_ALT,
+            item.command(),
+            p1,
+            p2,
+            p3,
+            p4,
+            c.y,
+            c.x
+        ));
+    }
+    lines.push(String::new());
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::{line_string, point};
+
+    use super::*;
+    use crate::data::Layer;
+
+    fn path() -> PathData {
+        PathData::new(
+            line_string![(x: 101.6, y: 3.0), (x: 101.601, y: 3.0), (x: 101.601, y: 3.001)],
+            MultiPoint(vec![
+                point!(x: 101.6, y: 3.0),
+                point!(x: 101.601, y: 3.0005),
+                point!(x: 101.6005, y: 3.0),
+            ]),
+        )
+    }
+
+    #[test]
+    fn orders_collection_points_along_the_path() {
+        let mission = mission(&path());
+
+        assert_eq!(
+            mission,
+            vec![
+                MissionItem::Loiter(coord! { x: 101.6, y: 3.0 }, LOITER_TIME),
+                MissionItem::Loiter(coord! { x: 101.6005, y: 3.0 }, LOITER_TIME),
+                MissionItem::Waypoint(coord! { x: 101.601, y: 3.0 }),
+                MissionItem::Loiter(coord! { x: 101.601, y: 3.0005 }, LOITER_TIME),
+                MissionItem::Waypoint(coord! { x: 101.601, y: 3.001 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn round_trips_plans() {
+        let plan = write_plan(&path()).unwrap();
+        assert!(is_plan(&plan));
+        let read = read_plan(&plan).unwrap();
+
+        let expected = mission(&path())
+            .into_iter()
+            .map(|i| i.coord())
+            .collect::<LineString<f64>>();
+        assert_eq!(read.path(), &expected);
+        assert_eq!(read.collection_points().0.len(), 3);
+        assert!(read
+            .collection_points()
+            .iter()
+            .all(|p| path().collection_points().0.contains(p)));
+    }
+
+    #[test]
+    fn round_trips_waypoint_files() {
+        let wpl = write_wpl(&path()).unwrap();
+        assert!(wpl.starts_with("QGC WPL 110\n0\t1\t0\t16\t"));
+        let read = read_wpl(&wpl).unwrap();
+
+        assert_eq!(read.path().0.len(), 5);
+        assert_eq!(read.collection_points().0.len(), 3);
+        assert_eq!(read.path().0.last(), path().path().0.last());
+    }
+
+    #[test]
+    fn keeps_home_and_dwell_times() {
+        let sampling =
+            SamplingInstructions::new(vec![Layer::Surface], vec![], Some(30.0), 2, None).unwrap();
+        let path = path()
+            .with_sampling(vec![
+                sampling,
+                SamplingInstructions::default(),
+                SamplingInstructions::default(),
+            ])
+            .unwrap()
+            .with_home(Some(point!(x: 101.5999, y: 3.0)), None);
+        let times = mission(&path)
+            .iter()
+            .map(|i| i.params()[0])
+            .collect::<Vec<_>>();
+        assert_eq!(times, [60.0, LOITER_TIME, 0.0, LOITER_TIME, 0.0]);
+
+        for read in [
+            read_plan(&write_plan(&path).unwrap()).unwrap(),
+            read_wpl(&write_wpl(&path).unwrap()).unwrap(),
+        ] {
+            assert_eq!(read.home(), path.home());
+            assert_eq!(read.sampling()[0].dwell_time(), Some(60.0));
+            assert_eq!(read.sampling()[1], SamplingInstructions::default());
+        }
+    }
+
+    #[test]
+    fn reads_ardupilot_waypoint_files() {
+        let wpl = "QGC WPL 110\n\
+            0\t1\t0\t16\t0\t0\t0\t0\t-35.363262\t149.165237\t584.0\t1\n\
+            1\t0\t3\t22\t0\t0\t0\t0\t0\t0\t20\t1\n\
+            2\t0\t3\t16\t0\t0\t0\t0\t-35.361\t149.164\t20\t1\n\
+            3\t0\t3\t19\t30\t0\t0\t0\t-35.362\t149.166\t20\t1\n\
+            4\t0\t3\t20\t0\t0\t0\t0\t0\t0\t0\t1\n";
+        let read = read_wpl(wpl).unwrap();
+
+        assert_eq!(
+            read.path(),
+            &line_string![(x: 149.164, y: -35.361), (x: 149.166, y: -35.362)]
+        );
+        assert_eq!(
+            read.collection_points().0,
+            vec![point!(x: 149.166, y: -35.362)]
+        );
+        assert_eq!(read.sampling()[0].dwell_time(), Some(30.0));
+        assert_eq!(read.home(), Some(point!(x: 149.165237, y: -35.363262)));
+    }
+
+    #[test]
+    fn reads_plan_survey_items() {
+        let plan = json!({
+            "fileType": "Plan",
+            "mission": { "items": [
+                { "type": "SimpleItem", "command": 22, "params": [0, 0, 0, null, 0, 0, 10] },
+                { "type": "ComplexItem", "complexItemType": "survey", "TransectStyleComplexItem": {
+                    "Items": [
+                        { "type": "SimpleItem", "command": 16, "params": [0, 0, 0, null, 1.0, 2.0, 0] },
+                        { "type": "SimpleItem", "command": 16, "params": [0, 0, 0, null, 1.5, 2.5, 0] },
+                    ]
+                }},
+                { "type": "SimpleItem", "command": 19, "params": [5, 0, 0, null, 2.0, 3.0, 0] },
+            ]},
+        });
+        let read = read_plan(&plan.to_string()).unwrap();
+
+        assert_eq!(
+            read.path(),
+            &line_string![(x: 2.0, y: 1.0), (x: 2.5, y: 1.5), (x: 3.0, y: 2.0)]
+        );
+        assert_eq!(read.collection_points().0, vec![point!(x: 3.0, y: 2.0)]);
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        assert!(read_wpl("QGC WPL 120\n").is_err());
+        assert!(read_wpl("QGC WPL 110\n1\t0\t3\t16\t0\t0\t0\t0\t1\n").is_err());
+        assert!(read_wpl("QGC WPL 110\n1\t0\t3\t16\t0\t0\t0\t0\tnorth\t1\t0\t1\n").is_err());
+        assert!(read_plan(r#"{"type": "FeatureCollection"}"#).is_err());
+        assert!(read_plan(r#"{"fileType": "Plan", "mission": {}}"#).is_err());
+    }
+}
@@ -0,0 +1,421 @@
+//! Undo and redo history of the paths in the library.
+//!
+//! The revisions of each path are kept in memory and in `paths/<id>.history.json` so edits can
+//! still be undone after restarting the application.
+
+use std::{
+    collections::{hash_map::Entry, HashMap, VecDeque},
+    fs,
+    io::ErrorKind,
+    path::Path,
+    sync::Mutex,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use super::{library::PathLibrary, PathData};
+
+/// The most revisions kept for each path.
+const MAX_REVISIONS: usize = 100;
+
+/// A saved version of a path.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Revision {
+    /// The identifier of the revision, increasing with each revision.
+    id: u64,
+    /// When the revision was saved.
+    time: DateTime<Utc>,
+    /// The path at the revision.
+    path: PathData,
+}
+
+/// Information about a revision of a path.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RevisionSummary {
+    /// The identifier of the revision.
+    id: u64,
+    /// When the revision was saved.
+    time: DateTime<Utc>,
+    /// The number of vertices in the path.
+    vertices: usize,
+    /// The number of collection points.
+    collection_points: usize,
+    /// Whether this is the revision being edited.
+    current: bool,
+}
+
+impl RevisionSummary {
+    /// Gets the identifier of the revision.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Checks whether this is the revision being edited.
+    pub fn current(&self) -> bool {
+        self.current
+    }
+}
+
+/// The revisions of a path, oldest first.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PathHistory {
+    /// The saved revisions.
+    revisions: VecDeque<Revision>,
+    /// The index of the revision being edited.
+    current: usize,
+    /// The identifier of the next revision.
+    next_id: u64,
+}
+
+impl PathHistory {
+    /// Reads a history from a file, returning an empty history if it does not exist.
+    ///
+    /// A current revision past the end of the history is moved to the newest revision.
+    pub fn load(file: &Path) -> Result<Self, String> {
+        let mut history: Self = match fs::read_to_string(file) {
+            Ok(v) => serde_json::from_str(&v).map_err(|e| e.to_string())?,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e.to_string()),
+        };
+        history.current = history
+            .current
+            .min(history.revisions.len().saturating_sub(1));
+        Ok(history)
+    }
+
+    /// Writes the history to a file.
+    pub fn save(&self, file: &Path) -> Result<(), String> {
+        let content = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        let temp = file.with_extension("tmp");
+        fs::write(&temp, content).map_err(|e| e.to_string())?;
+        fs::rename(temp, file).map_err(|e| e.to_string())
+    }
+
+    /// Checks whether there are no revisions.
+    pub fn is_empty(&self) -> bool {
+        self.revisions.is_empty()
+    }
+
+    /// Adds a revision after the current one, discarding any revisions that were undone.
+    ///
+    /// Returns `false` if the path is the same as the current revision.
+    pub fn record(&mut self, path: PathData) -> bool {
+        if self.revisions.get(self.current).map(|r| &r.path) == Some(&path) {
+            return false;
+        }
+
+        self.revisions.truncate(self.current + 1);
+        self.revisions.push_back(Revision {
+            id: self.next_id,
+            time: Utc::now(),
+            path,
+        });
+        self.next_id += 1;
+        while self.revisions.len() > MAX_REVISIONS {
+            self.revisions.pop_front();
+        }
+        self.current = self.revisions.len() - 1;
+        true
+    }
+
+    /// Moves to the previous revision, returning it.
+    pub fn undo(&mut self) -> Option<&PathData> {
+        let previous = self.current.checked_sub(1)?;
+        let revision = self.revisions.get(previous)?;
+        self.current = previous;
+        Some(&revision.path)
+    }
+
+    /// Moves to the next revision, returning it.
+    pub fn redo(&mut self) -> Option<&PathData> {
+        let next = self.current + 1;
+        let revision = self.revisions.get(next)?;
+        self.current = next;
+        Some(&revision.path)
+    }
+
+    /// Adds a copy of an older revision as the newest revision, returning it.
+    pub fn restore(&mut self, id: u64) -> Result<&PathData, String> {
+        let path = self
+            .revisions
+            .iter()
+            .find(|r| r.id == id)
+            .ok_or(format!("Unable to Find Revision: {}", id))?
+            .path
+            .clone();
+        // Restoring onto the end so no revision is lost
+        self.current = self.revisions.len() - 1;
+        self.record(path);
+        Ok(&self.revisions[self.current].path)
+    }
+
+    /// Lists the revisions, oldest first.
+    pub fn list(&self) -> Vec<RevisionSummary> {
+        self.revisions
+            .iter()
+            .enumerate()
+            .map(|(i, r)| RevisionSummary {
+                id: r.id,
+                time: r.time,
+                vertices: r.path.path().0.len(),
+                collection_points: r.path.collection_points().0.len(),
+                current: i == self.current,
+            })
+            .collect()
+    }
+}
+
+/// The histories of the paths edited since the application started.
+#[derive(Debug, Default)]
+pub struct PathHistories {
+    /// The histories by the identifier of the path.
+    histories: Mutex<HashMap<u64, PathHistory>>,
+}
+
+impl PathHistories {
+    /// Reads the history of a path in the library if it is not already in memory.
+    ///
+    /// The history starts from the saved path if there is none, or if it cannot be read.
+    pub fn load(&self, library: &PathLibrary, id: u64) -> Result<(), String> {
+        self.update(library, id, |_| ())
+    }
+
+    /// Changes the history of a path in the library, saving it if it changed.
+    ///
+    /// The history is read from disk the first time, the same way as `load`.
+    pub fn update<T, F>(&self, library: &PathLibrary, id: u64, f: F) -> Result<T, String>
+    where
+        F: FnOnce(&mut PathHistory) -> T,
+    {
+        let file = library.history_file(id);
+        let mut histories = self.histories.lock().unwrap();
+        let history = match histories.entry(id) {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => {
+                log::debug!("Loading History: {}", file.display());
+                let mut history = PathHistory::load(&file).unwrap_or_else(|error| {
+                    log::warn!(
+                        "Unable to read History: {}, starting a new History: {}",
+                        file.display(),
+                        error
+                    );
+                    PathHistory::default()
+                });
+                if history.is_empty() {
+                    history.record(library.read(id)?);
+                }
+                e.insert(history)
+            }
+        };
+
+        // Only writing when a revision is recorded or the current revision moves
+        let before = (history.current, history.next_id);
+        let result = f(history);
+        if (history.current, history.next_id) != before {
+            history.save(&file)?;
+        }
+        Ok(result)
+    }
+
+    /// Forgets the history of a path kept in memory.
+    pub fn remove(&self, id: u64) {
+        self.histories.lock().unwrap().remove(&id);
+    }
+}
+
+/// Gets the identifier of the active path.
+fn active_id(library: &PathLibrary) -> Result<u64, String> {
+    library
+        .active()
+        .map(|m| m.id())
+        .ok_or(String::from("No Active Path"))
+}
+
+/// Move the active path back to its previous revision.
+///
+/// Returns `None` if there is nothing to undo.
+#[tauri::command]
+pub fn undo_path(
+    app_handle: AppHandle,
+    state: tauri::State<'_, PathHistories>,
+) -> Result<Option<PathData>, String> {
+    log::info!("Undoing Path Edit");
+    let mut library = PathLibrary::open(&app_handle)?;
+    let id = active_id(&library)?;
+    let path = state.update(&library, id, |h| h.undo().cloned())?;
+    if let Some(path) = &path {
+        library.write(id, path)?;
+    }
+    Ok(path)
+}
+
+/// Move the active path forward to the revision that was undone.
+///
+/// Returns `None` if there is nothing to redo.
+#[tauri::command]
+pub fn redo_path(
+    app_handle: AppHandle,
+    state: tauri::State<'_, PathHistories>,
+) -> Result<Option<PathData>, String> {
+    log::info!("Redoing Path Edit");
+    let mut library = PathLibrary::open(&app_handle)?;
+    let id = active_id(&library)?;
+    let path = state.update(&library, id, |h| h.redo().cloned())?;
+    if let Some(path) = &path {
+        library.write(id, path)?;
+    }
+    Ok(path)
+}
+
+/// List the revisions of the active path, oldest first.
+#[tauri::command]
+pub fn list_path_revisions(
+    app_handle: AppHandle,
+    state: tauri::State<'_, PathHistories>,
+) -> Result<Vec<RevisionSummary>, String> {
+    log::debug!("Listing Path Revisions");
+    let library = PathLibrary::open(&app_handle)?;
+    let id = active_id(&library)?;
+    state.update(&library, id, |h| h.list())
+}
+
+/// Restore an older revision of the active path, returning it.
+#[tauri::command]
+pub fn restore_path_revision(
+    app_handle: AppHandle,
+    state: tauri::State<'_, PathHistories>,
+    revision: u64,
+) -> Result<PathData, String> {
+    log::info!("Restoring Path Revision: {}", revision);
+    let mut library = PathLibrary::open(&app_handle)?;
+    let id = active_id(&library)?;
+    let path = state.update(&library, id, |h| h.restore(revision).cloned())??;
+    library.write(id, &path)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::{line_string, MultiPoint};
+
+    use super::*;
+
+    fn path(x: f64) -> PathData {
+        PathData::new(
+            line_string![(x: 0.0, y: 0.0), (x: x, y: 1.0)],
+            MultiPoint(vec![]),
+        )
+    }
+
+    #[test]
+    fn undoes_and_redoes_edits() {
+        let mut history = PathHistory::default();
+        assert!(history.undo().is_none());
+        assert!(history.record(path(1.0)));
+        assert!(!history.record(path(1.0)));
+        history.record(path(2.0));
+        history.record(path(3.0));
+
+        assert_eq!(history.undo(), Some(&path(2.0)));
+        assert_eq!(history.undo(), Some(&path(1.0)));
+        assert_eq!(history.undo(), None);
+        assert_eq!(history.redo(), Some(&path(2.0)));
+
+        // Editing after undoing discards the undone revisions
+        history.record(path(4.0));
+        assert_eq!(history.redo(), None);
+        let ids = history.list().iter().map(|r| r.id()).collect::<Vec<_>>();
+        assert_eq!(ids, vec![0, 1, 3]);
+        assert!(history.list()[2].current());
+    }
+
+    #[test]
+    fn keeps_bounded_history() {
+        let mut history = PathHistory::default();
+        for i in 0..MAX_REVISIONS + 10 {
+            history.record(path(i as f64));
+        }
+
+        let revisions = history.list();
+        assert_eq!(revisions.len(), MAX_REVISIONS);
+        assert_eq!(revisions[0].id(), 10);
+        assert!(revisions[MAX_REVISIONS - 1].current());
+    }
+
+    #[test]
+    fn restores_revisions_after_reloading() {
+        let dir = std::env::temp_dir().join(format!("path-history-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut library = PathLibrary::open_in(&dir).unwrap();
+        let id = library.create("Lake", None, "", &path(1.0)).unwrap().id();
+
+        let histories = PathHistories::default();
+        histories
+            .update(&library, id, |h| h.record(path(2.0)))
+            .unwrap();
+        library.write(id, &path(2.0)).unwrap();
+        histories.remove(id);
+
+        // The saved path before the first edit is kept
+        let revisions = histories.update(&library, id, |h| h.list()).unwrap();
+        assert_eq!(revisions.len(), 2);
+        let restored = histories
+            .update(&library, id, |h| h.restore(revisions[0].id()).cloned())
+            .unwrap()
+            .unwrap();
+        assert_eq!(restored, path(1.0));
+        assert_eq!(
+            histories.update(&library, id, |h| h.list().len()).unwrap(),
+            3
+        );
+        assert_eq!(
+            histories
+                .update(&library, id, |h| h.undo().cloned())
+                .unwrap(),
+            Some(path(2.0))
+        );
+    }
+
+    #[test]
+    fn starts_new_history_when_unreadable() {
+        let dir = std::env::temp_dir().join(format!("path-history-bad-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut library = PathLibrary::open_in(&dir).unwrap();
+        let id = library.create("Lake", None, "", &path(1.0)).unwrap().id();
+        let file = library.history_file(id);
+        fs::write(&file, "{").unwrap();
+
+        let histories = PathHistories::default();
+        let revisions = histories.update(&library, id, |h| h.list()).unwrap();
+        assert_eq!(revisions.len(), 1);
+        // Listing does not change the history, so it is not written
+        assert_eq!(fs::read_to_string(&file).unwrap(), "{");
+
+        histories
+            .update(&library, id, |h| h.record(path(2.0)))
+            .unwrap();
+        assert_eq!(PathHistory::load(&file).unwrap().list().len(), 2);
+    }
+
+    #[test]
+    fn moves_current_past_the_end_to_newest() {
+        let mut history = PathHistory::default();
+        history.record(path(1.0));
+        history.record(path(2.0));
+        let mut value = serde_json::to_value(&history).unwrap();
+        value["current"] = serde_json::json!(5);
+
+        let dir = std::env::temp_dir().join(format!("path-history-edit-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("1.history.json");
+        fs::write(&file, value.to_string()).unwrap();
+
+        let mut history = PathHistory::load(&file).unwrap();
+        assert!(history.list()[1].current());
+        assert_eq!(history.redo(), None);
+        assert_eq!(history.undo(), Some(&path(1.0)));
+    }
+}
@@ -0,0 +1,266 @@
+//! Collection points spaced evenly along a path on the WGS84 ellipsoid.
+
+use geo::{GeodesicBearing, GeodesicDestination};
+use geo_types::{LineString, MultiPoint, Point};
+
+use super::{PathData, SamplingInstructions};
+
+/// Points closer than this in metres are treated as the same point.
+const TOLERANCE: f64 = 1e-3;
+
+/// The most collection points a single path may have.
+const MAX_POINTS: usize = 1_000_000;
+
+/// Walks along a path placing points at a fixed distance in metres.
+///
+/// If `keep_vertices` is set, every vertex is also a point, otherwise only the first vertex and
+/// those falling on the spacing are.
+fn along_track(
+    path: &LineString<f64>,
+    spacing: f64,
+    reset_at_vertices: bool,
+    keep_vertices: bool,
+) -> Result<Vec<Point<f64>>, String> {
+    if !(spacing.is_finite() && spacing > 0.0) {
+        return Err(String::from("Spacing must be Positive"));
+    }
+
+    let mut points = Vec::new();
+    match path.points().next() {
+        Some(first) => points.push(first),
+        None => {
+            log::info!("Path is Empty");
+            return Ok(points);
+        }
+    }
+
+    // Distance along the next segment to the next point
+    let mut offset = spacing;
+    for line in path.lines() {
+        let (start, end) = (Point::from(line.start), Point::from(line.end));
+        let (bearing, length) = start.geodesic_bearing_distance(end);
+        log::debug!("Segment Bearing: {}, Length: {}", bearing, length);
+        if length < TOLERANCE {
+            log::debug!("Skipping Empty Segment");
+            continue;
+        }
+
+        let mut along = offset;
+        while along < length - TOLERANCE {
+            if points.len() >= MAX_POINTS {
+                return Err(String::from("Too Many Points"));
+            }
+            points.push(start.geodesic_destination(bearing, along));
+            along += spacing;
+        }
+
+        // The vertex lies where the next point would have been
+        let on_spacing = along - length <= TOLERANCE;
+        if keep_vertices || on_spacing {
+            points.push(end);
+        }
+        offset = if reset_at_vertices {
+            spacing
+        } else if on_spacing {
+            along - length + spacing
+        } else {
+            along - length
+        };
+    }
+    Ok(points)
+}
+
+/// Places collection points along a path at a fixed distance in metres.
+///
+/// Distances are measured along geodesics on the WGS84 ellipsoid. Every vertex of the path is
+/// kept as a collection point so that corners are always sampled. If `reset_at_vertices` is set,
+/// the spacing starts again from each vertex, otherwise it carries on along the whole track.
+///
+/// The collection points are replaced, each with the default sampling instructions.
+pub fn interpolate(
+    path: &PathData,
+    spacing: f64,
+    reset_at_vertices: bool,
+) -> Result<PathData, String> {
+    let points = along_track(&path.path, spacing, reset_at_vertices, true)?;
+    log::debug!("Interpolated {} Collection Points", points.len());
+
+    Ok(PathData {
+        sampling: vec![SamplingInstructions::default(); points.len()],
+        collection_points: MultiPoint(points),
+        ..path.clone()
+    })
+}
+
+/// Replaces the vertices of a path with vertices at a fixed distance in metres along it.
+///
+/// The first and last vertices are kept, so the last leg may be shorter than the spacing.
+pub fn resample(path: &LineString<f64>, spacing: f64) -> Result<LineString<f64>, String> {
+    let mut points = along_track(path, spacing, false, false)?;
+    if let Some(&last) = path.0.last() {
+        if points.last().map(|p| p.0) != Some(last) {
+            points.push(Point::from(last));
+        }
+    }
+    log::debug!("Resampled to {} Vertices", points.len());
+
+    Ok(LineString::from(points))
+}
+
+/// Places collection points along a path at a fixed distance in metres.
+#[tauri::command]
+pub fn interpolate_path(
+    path: PathData,
+    spacing: f64,
+    reset_at_vertices: Option<bool>,
+) -> Result<PathData, String> {
+    log::info!("Interpolating Collection Points");
+    interpolate(&path, spacing, reset_at_vertices.unwrap_or(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::GeodesicDistance;
+    use geo_types::line_string;
+
+    use super::*;
+
+    /// Length of one degree of longitude along the equator in metres.
+    const EQUATOR_DEGREE: f64 = 111_319.490_793;
+
+    /// Length of the meridian arc from the equator to 1° north in metres.
+    const MERIDIAN_DEGREE: f64 = 110_574.388_558;
+
+    /// A path without collection points.
+    fn track(path: &LineString<f64>) -> PathData {
+        PathData::new(path.clone(), MultiPoint(vec![]))
+    }
+
+    /// Geodesic distances between neighbouring collection points.
+    fn gaps(data: &PathData) -> Vec<f64> {
+        data.collection_points()
+            .0
+            .windows(2)
+            .map(|w| w[0].geodesic_distance(&w[1]))
+            .collect()
+    }
+
+    #[test]
+    fn spaces_points_along_the_equator() {
+        let path = line_string![(x: 0.0, y: 0.0), (x: 1.0, y: 0.0)];
+        let data = interpolate(&track(&path), 10_000.0, false).unwrap();
+
+        assert_eq!(data.path(), &path);
+        let gaps = gaps(&data);
+        assert_eq!(gaps.len(), 12);
+        for gap in &gaps[..11] {
+            assert!((gap - 10_000.0).abs() < 1e-6, "{gap}");
+        }
+        assert!((gaps[11] - (EQUATOR_DEGREE - 110_000.0)).abs() < 1e-3);
+        assert_eq!(
+            data.collection_points().0.last(),
+            Some(&Point::new(1.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn spaces_points_along_a_meridian() {
+        let path = line_string![(x: 0.0, y: 0.0), (x: 0.0, y: 1.0)];
+        let data = interpolate(&track(&path), 25_000.0, false).unwrap();
+
+        let points = &data.collection_points().0;
+        assert_eq!(points.len(), 6);
+        assert!(points[..5].iter().all(|p| p.x().abs() < 1e-9));
+        let total: f64 = gaps(&data).iter().sum();
+        assert!((total - MERIDIAN_DEGREE).abs() < 1e-3, "{total}");
+        assert!((gaps(&data)[4] - (MERIDIAN_DEGREE - 100_000.0)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn carries_spacing_around_corners() {
+        let path = line_string![(x: 0.0, y: 0.0), (x: 0.001, y: 0.0), (x: 0.001, y: 0.001)];
+        let data = interpolate(&track(&path), 50.0, false).unwrap();
+
+        // Distances along the track of each collection point
+        let along = gaps(&data)
+            .into_iter()
+            .scan(0.0, |total, gap| {
+                *total += gap;
+                Some(*total)
+            })
+            .collect::<Vec<_>>();
+        let corner = EQUATOR_DEGREE / 1000.0;
+        let end = corner + Point::from(path.0[1]).geodesic_distance(&Point::from(path.0[2]));
+        let expected = [50.0, 100.0, corner, 150.0, 200.0, end];
+        assert_eq!(along.len(), expected.len());
+        for (actual, expected) in along.iter().zip(expected) {
+            assert!((actual - expected).abs() < 1e-6, "{actual} != {expected}");
+        }
+        assert!(data.collection_points().0.contains(&Point::new(0.001, 0.0)));
+    }
+
+    #[test]
+    fn resets_spacing_at_vertices() {
+        let path = line_string![(x: 0.0, y: 0.0), (x: 0.001, y: 0.0), (x: 0.001, y: 0.001)];
+        let data = interpolate(&track(&path), 50.0, true).unwrap();
+
+        let points = &data.collection_points().0;
+        assert_eq!(points.len(), 7);
+        assert_eq!(points[3], Point::new(0.001, 0.0));
+        assert!((points[3].geodesic_distance(&points[4]) - 50.0).abs() < 1e-6);
+        assert!((points[3].geodesic_distance(&points[5]) - 100.0).abs() < 1e-6);
+        assert_eq!(points[6], Point::new(0.001, 0.001));
+    }
+
+    #[test]
+    fn keeps_vertices_on_the_spacing() {
+        let path = line_string![(x: 0.0, y: 0.0), (x: 0.0, y: 0.0), (x: 1.0, y: 0.0)];
+        let data = interpolate(&track(&path), EQUATOR_DEGREE / 2.0, false).unwrap();
+
+        let points = &data.collection_points().0;
+        assert_eq!(points.len(), 3);
+        assert_eq!(points[0], Point::new(0.0, 0.0));
+        assert!((points[1].x() - 0.5).abs() < 1e-9);
+        assert_eq!(points[2], Point::new(1.0, 0.0));
+    }
+
+    #[test]
+    fn resamples_vertices() {
+        let path = line_string![(x: 0.0, y: 0.0), (x: 0.001, y: 0.0), (x: 0.001, y: 0.001)];
+        let resampled = resample(&path, 50.0).unwrap();
+
+        let points = resampled.points().collect::<Vec<_>>();
+        assert_eq!(points.len(), 6);
+        assert!(!points.contains(&Point::new(0.001, 0.0)));
+        assert_eq!(points.last(), Some(&Point::new(0.001, 0.001)));
+        for w in points[..5].windows(2) {
+            assert!(w[0].geodesic_distance(&w[1]) <= 50.0 + 1e-6);
+        }
+        assert!((points[1].geodesic_distance(&points[2]) - 50.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rejects_invalid_spacing() {
+        let path = line_string![(x: 0.0, y: 0.0), (x: 1.0, y: 0.0)];
+        for spacing in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(interpolate(&track(&path), spacing, false).is_err());
+        }
+        assert!(resample(&path, 0.0).is_err());
+        assert!(interpolate(&track(&LineString(vec![])), 1.0, false)
+            .unwrap()
+            .collection_points()
+            .0
+            .is_empty());
+    }
+
+    #[test]
+    fn keeps_home_and_end() {
+        let path = track(&line_string![(x: 0.0, y: 0.0), (x: 0.001, y: 0.0)])
+            .with_home(Some(Point::new(-0.001, 0.0)), Some(Point::new(0.002, 0.0)));
+        let data = interpolate(&path, 50.0, false).unwrap();
+
+        assert_eq!(data.home(), path.home());
+        assert_eq!(data.end(), path.end());
+        assert_eq!(data.sampling().len(), data.collection_points().0.len());
+    }
+}
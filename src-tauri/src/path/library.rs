@@ -0,0 +1,481 @@
+//! A library of named paths stored in the application data directory.
+//!
+//! Each path is stored as `paths/<id>.geojson` with the metadata of every path and the active
+//! path kept in `paths/library.json`.
+
+use std::{
+    cmp::Reverse,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use super::{history::PathHistories, read_path_file, write_path, PathData};
+
+/// The name of the directory storing the path library.
+const LIBRARY_DIR: &str = "paths";
+
+/// The name of the file storing the metadata of the library.
+const INDEX_FILE: &str = "library.json";
+
+/// The name of the single path file used before the library existed.
+const LEGACY_PATH_FILE: &str = "path.geojson";
+
+/// Information about a path in the library.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PathMetadata {
+    /// The identifier of the path.
+    id: u64,
+    /// The name of the path.
+    name: String,
+    /// The lake, river or sea the path is on.
+    water_body: Option<String>,
+    /// When the path was created.
+    created: DateTime<Utc>,
+    /// When the path was last changed.
+    modified: DateTime<Utc>,
+    /// Notes about the path.
+    notes: String,
+}
+
+impl PathMetadata {
+    /// Gets the identifier of the path.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Gets the name of the path.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Gets the lake, river or sea the path is on.
+    pub fn water_body(&self) -> Option<&str> {
+        self.water_body.as_deref()
+    }
+
+    /// Gets when the path was created.
+    pub fn created(&self) -> DateTime<Utc> {
+        self.created
+    }
+
+    /// Gets when the path was last changed.
+    pub fn modified(&self) -> DateTime<Utc> {
+        self.modified
+    }
+
+    /// Gets the notes about the path.
+    pub fn notes(&self) -> &str {
+        &self.notes
+    }
+}
+
+/// The contents of the library index file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LibraryIndex {
+    /// The identifier of the path being edited.
+    active: Option<u64>,
+    /// The paths in the library.
+    paths: Vec<PathMetadata>,
+}
+
+/// Checks that a name can be given to a path.
+fn validate_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(String::from("Path Name must not be Empty"));
+    }
+    Ok(String::from(name))
+}
+
+/// A collection of named paths.
+#[derive(Debug)]
+pub struct PathLibrary {
+    /// The directory storing the library.
+    dir: PathBuf,
+    /// The metadata of the library.
+    index: LibraryIndex,
+}
+
+impl PathLibrary {
+    /// Opens the library in the application data directory.
+    pub fn open(app_handle: &AppHandle) -> Result<Self, String> {
+        let data_dir = app_handle
+            .path_resolver()
+            .app_data_dir()
+            .ok_or(String::from("Unable to Get App Data Directory"))?;
+        Self::open_in(&data_dir)
+    }
+
+    /// Opens the library in a data directory, creating it if needed.
+    ///
+    /// A path saved before the library existed is added as the active path.
+    pub fn open_in(data_dir: &Path) -> Result<Self, String> {
+        let dir = data_dir.join(LIBRARY_DIR);
+        log::debug!("Path Library Directory: {}", dir.display());
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+        let index = match fs::read_to_string(dir.join(INDEX_FILE)) {
+            Ok(v) => serde_json::from_str(&v).map_err(|e| e.to_string())?,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                let mut library = Self {
+                    dir,
+                    index: LibraryIndex::default(),
+                };
+                let legacy = data_dir.join(LEGACY_PATH_FILE);
+                if legacy.exists() {
+                    log::info!("Adding Existing Path to Library");
+                    match read_path_file(&legacy, None) {
+                        Ok(path) => {
+                            let metadata = library.create("Path", None, "", &path)?;
+                            library.set_active(metadata.id)?;
+                        }
+                        Err(e) => {
+                            // Moving the file aside so every later open does not fail the same way
+                            let unreadable = legacy.with_extension("geojson.unreadable");
+                            log::warn!(
+                                "Unable to read Existing Path: {}, moving it to {}",
+                                e,
+                                unreadable.display()
+                            );
+                            if let Err(e) = fs::rename(&legacy, &unreadable) {
+                                log::warn!("Unable to move Existing Path: {}", e);
+                            }
+                            library.save()?;
+                        }
+                    }
+                }
+                return Ok(library);
+            }
+            Err(e) => return Err(e.to_string()),
+        };
+        Ok(Self { dir, index })
+    }
+
+    /// Writes the metadata of the library to disk.
+    fn save(&self) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(&self.index).map_err(|e| e.to_string())?;
+        // Writing to a temporary file first so the index is never left half written
+        let temp = self.dir.join(format!("{}.tmp", INDEX_FILE));
+        fs::write(&temp, content).map_err(|e| e.to_string())?;
+        fs::rename(temp, self.dir.join(INDEX_FILE)).map_err(|e| e.to_string())
+    }
+
+    /// Gets the file storing a path.
+    fn path_file(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{}.geojson", id))
+    }
+
+    /// Gets the file storing the revision history of a path.
+    pub(crate) fn history_file(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{}.history.json", id))
+    }
+
+    /// Gets the metadata of a path.
+    pub fn get(&self, id: u64) -> Result<&PathMetadata, String> {
+        self.index
+            .paths
+            .iter()
+            .find(|m| m.id == id)
+            .ok_or(format!("Unable to Find Path: {}", id))
+    }
+
+    /// Gets the metadata of a path to change it.
+    fn get_mut(&mut self, id: u64) -> Result<&mut PathMetadata, String> {
+        self.index
+            .paths
+            .iter_mut()
+            .find(|m| m.id == id)
+            .ok_or(format!("Unable to Find Path: {}", id))
+    }
+
+    /// Lists the paths in the library, most recently changed first.
+    pub fn list(&self) -> Vec<PathMetadata> {
+        let mut paths = self.index.paths.clone();
+        paths.sort_by_key(|m| Reverse(m.modified));
+        paths
+    }
+
+    /// Gets the metadata of the path being edited.
+    pub fn active(&self) -> Option<&PathMetadata> {
+        self.index.active.and_then(|id| self.get(id).ok())
+    }
+
+    /// Sets the path being edited.
+    pub fn set_active(&mut self, id: u64) -> Result<(), String> {
+        self.get(id)?;
+        self.index.active = Some(id);
+        self.save()
+    }
+
+    /// Adds a new path to the library.
+    pub fn create(
+        &mut self,
+        name: &str,
+        water_body: Option<&str>,
+        notes: &str,
+        path: &PathData,
+    ) -> Result<PathMetadata, String> {
+        let now = Utc::now();
+        let metadata = PathMetadata {
+            id: self.index.paths.iter().map(|m| m.id + 1).max().unwrap_or(1),
+            name: validate_name(name)?,
+            water_body: water_body.map(String::from),
+            created: now,
+            modified: now,
+            notes: String::from(notes),
+        };
+        log::debug!("Creating Path: {:?}", metadata);
+
+        write_path(&self.path_file(metadata.id), path, None)?;
+        self.index.paths.push(metadata.clone());
+        self.save()?;
+        Ok(metadata)
+    }
+
+    /// Reads a path from the library.
+    pub fn read(&self, id: u64) -> Result<PathData, String> {
+        self.get(id)?;
+        read_path_file(&self.path_file(id), None)
+    }
+
+    /// Replaces a path in the library.
+    pub fn write(&mut self, id: u64, path: &PathData) -> Result<(), String> {
+        self.get(id)?;
+        write_path(&self.path_file(id), path, None)?;
+        self.get_mut(id)?.modified = Utc::now();
+        self.save()
+    }
+
+    /// Renames a path.
+    pub fn rename(&mut self, id: u64, name: &str) -> Result<PathMetadata, String> {
+        let name = validate_name(name)?;
+        let metadata = self.get_mut(id)?;
+        metadata.name = name;
+        metadata.modified = Utc::now();
+        let metadata = metadata.clone();
+        self.save()?;
+        Ok(metadata)
+    }
+
+    /// Changes the water body and notes of a path.
+    pub fn update(
+        &mut self,
+        id: u64,
+        water_body: Option<&str>,
+        notes: &str,
+    ) -> Result<PathMetadata, String> {
+        let metadata = self.get_mut(id)?;
+        metadata.water_body = water_body.map(String::from);
+        metadata.notes = String::from(notes);
+        metadata.modified = Utc::now();
+        let metadata = metadata.clone();
+        self.save()?;
+        Ok(metadata)
+    }
+
+    /// Copies a path under a new name.
+    pub fn duplicate(&mut self, id: u64, name: Option<&str>) -> Result<PathMetadata, String> {
+        let original = self.get(id)?.clone();
+        let name = match name {
+            Some(v) => String::from(v),
+            None => format!("{} (Copy)", original.name),
+        };
+        let path = self.read(id)?;
+        self.create(
+            &name,
+            original.water_body.as_deref(),
+            &original.notes,
+            &path,
+        )
+    }
+
+    /// Removes a path from the library.
+    pub fn delete(&mut self, id: u64) -> Result<(), String> {
+        self.get(id)?;
+        self.index.paths.retain(|m| m.id != id);
+        if self.index.active == Some(id) {
+            self.index.active = None;
+        }
+        self.save()?;
+
+        for file in [self.path_file(id), self.history_file(id)] {
+            match fs::remove_file(file) {
+                Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.to_string()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// List the paths in the library, most recently changed first.
+#[tauri::command]
+pub fn list_paths(app_handle: AppHandle) -> Result<Vec<PathMetadata>, String> {
+    log::debug!("Listing Paths");
+    Ok(PathLibrary::open(&app_handle)?.list())
+}
+
+/// Get the metadata of the path being edited.
+#[tauri::command]
+pub fn active_path(app_handle: AppHandle) -> Result<Option<PathMetadata>, String> {
+    log::debug!("Getting Active Path");
+    Ok(PathLibrary::open(&app_handle)?.active().cloned())
+}
+
+/// Set the path being edited, returning it.
+#[tauri::command]
+pub fn set_active_path(app_handle: AppHandle, id: u64) -> Result<PathData, String> {
+    log::debug!("Setting Active Path: {}", id);
+    let mut library = PathLibrary::open(&app_handle)?;
+    library.set_active(id)?;
+    library.read(id)
+}
+
+/// Create a new empty path and make it the active path.
+#[tauri::command]
+pub fn create_path(
+    app_handle: AppHandle,
+    name: String,
+    water_body: Option<String>,
+    notes: Option<String>,
+) -> Result<PathMetadata, String> {
+    log::debug!("Creating Path: {}", name);
+    let mut library = PathLibrary::open(&app_handle)?;
+    let metadata = library.create(
+        &name,
+        water_body.as_deref(),
+        notes.as_deref().unwrap_or_default(),
+        &PathData::default(),
+    )?;
+    library.set_active(metadata.id)?;
+    Ok(metadata)
+}
+
+/// Rename a path.
+#[tauri::command]
+pub fn rename_path(app_handle: AppHandle, id: u64, name: String) -> Result<PathMetadata, String> {
+    log::debug!("Renaming Path {} to: {}", id, name);
+    PathLibrary::open(&app_handle)?.rename(id, &name)
+}
+
+/// Change the water body and notes of a path.
+#[tauri::command]
+pub fn update_path_metadata(
+    app_handle: AppHandle,
+    id: u64,
+    water_body: Option<String>,
+    notes: String,
+) -> Result<PathMetadata, String> {
+    log::debug!("Updating Metadata of Path: {}", id);
+    PathLibrary::open(&app_handle)?.update(id, water_body.as_deref(), &notes)
+}
+
+/// Copy a path under a new name.
+#[tauri::command]
+pub fn duplicate_path(
+    app_handle: AppHandle,
+    id: u64,
+    name: Option<String>,
+) -> Result<PathMetadata, String> {
+    log::debug!("Duplicating Path: {}", id);
+    PathLibrary::open(&app_handle)?.duplicate(id, name.as_deref())
+}
+
+/// Delete a path and its history from the library.
+#[tauri::command]
+pub fn delete_path(
+    app_handle: AppHandle,
+    state: tauri::State<'_, PathHistories>,
+    id: u64,
+) -> Result<(), String> {
+    log::debug!("Deleting Path: {}", id);
+    PathLibrary::open(&app_handle)?.delete(id)?;
+    state.remove(id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::{line_string, MultiPoint};
+
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn manages_paths() {
+        let dir = temp_dir("path-library");
+        let mut library = PathLibrary::open_in(&dir).unwrap();
+        assert!(library.list().is_empty());
+        assert!(library.active().is_none());
+
+        let path = PathData::new(
+            line_string![(x: 101.6, y: 3.0), (x: 101.8, y: 3.2)],
+            MultiPoint(vec![]),
+        );
+        let lake = library
+            .create("Lake", Some("Tasik Biru"), "", &path)
+            .unwrap();
+        let copy = library.duplicate(lake.id(), None).unwrap();
+        assert_ne!(lake.id(), copy.id());
+        assert_eq!(copy.name(), "Lake (Copy)");
+        assert_eq!(copy.water_body(), Some("Tasik Biru"));
+        assert_eq!(library.read(copy.id()).unwrap().path(), path.path());
+
+        library.rename(copy.id(), " River ").unwrap();
+        library.update(copy.id(), None, "Shallow").unwrap();
+        library.set_active(copy.id()).unwrap();
+        assert!(library.rename(copy.id(), " ").is_err());
+
+        // Changes are kept after reopening the library
+        let mut library = PathLibrary::open_in(&dir).unwrap();
+        let active = library.active().unwrap().clone();
+        assert_eq!(active.name(), "River");
+        assert_eq!(active.notes(), "Shallow");
+        assert_eq!(active.water_body(), None);
+        assert_eq!(library.list().len(), 2);
+
+        library.delete(copy.id()).unwrap();
+        assert!(library.active().is_none());
+        assert!(library.read(copy.id()).is_err());
+        assert_eq!(library.list(), vec![lake]);
+    }
+
+    #[test]
+    fn adds_legacy_path() {
+        let dir = temp_dir("path-library-legacy");
+        fs::create_dir_all(&dir).unwrap();
+        let path = PathData::new(
+            line_string![(x: 101.6, y: 3.0), (x: 101.8, y: 3.2)],
+            MultiPoint(vec![]),
+        );
+        write_path(&dir.join(LEGACY_PATH_FILE), &path, None).unwrap();
+
+        let library = PathLibrary::open_in(&dir).unwrap();
+        let active = library.active().unwrap();
+        assert_eq!(active.name(), "Path");
+        assert_eq!(library.read(active.id()).unwrap().path(), path.path());
+    }
+
+    #[test]
+    fn moves_unreadable_legacy_path_aside() {
+        let dir = temp_dir("path-library-legacy-bad");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(LEGACY_PATH_FILE), "{").unwrap();
+
+        let library = PathLibrary::open_in(&dir).unwrap();
+        assert!(library.list().is_empty());
+        assert!(!dir.join(LEGACY_PATH_FILE).exists());
+        assert!(dir.join("path.geojson.unreadable").exists());
+        assert!(PathLibrary::open_in(&dir).is_ok());
+    }
+}
@@ -0,0 +1,96 @@
+//! Planar approximation of the WGS84 ellipsoid around a point.
+
+use geo_types::{coord, Coord};
+
+/// The semi-major axis of the WGS84 ellipsoid in metres.
+pub(crate) const WGS84_A: f64 = 6_378_137.0;
+
+/// The flattening of the WGS84 ellipsoid.
+pub(crate) const WGS84_F: f64 = 1.0 / 298.257_223_563;
+
+/// Equirectangular projection in metres around a reference coordinate.
+///
+/// The scale is taken from the radii of curvature of the ellipsoid at the reference, which keeps
+/// distances within a few centimetres over an area a few kilometres across.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LocalProjection {
+    /// The longitude and latitude projected to the origin.
+    origin: Coord<f64>,
+    /// Metres per degree of longitude at the origin.
+    metres_per_lng: f64,
+    /// Metres per degree of latitude at the origin.
+    metres_per_lat: f64,
+}
+
+impl LocalProjection {
+    /// Creates a new projection centred on a longitude and latitude.
+    pub(crate) fn new(origin: Coord<f64>) -> Self {
+        let e2 = WGS84_F * (2.0 - WGS84_F);
+        let lat = origin.y.to_radians();
+        let w = (1.0 - e2 * lat.sin().powi(2)).sqrt();
+        let prime_vertical = WGS84_A / w;
+        let meridional = WGS84_A * (1.0 - e2) / w.powi(3);
+
+        Self {
+            origin,
+            metres_per_lng: (prime_vertical * lat.cos()).to_radians(),
+            metres_per_lat: meridional.to_radians(),
+        }
+    }
+
+    /// Creates a new projection centred on the bounding box of some coordinates.
+    ///
+    /// Returns `None` if there are no coordinates.
+    pub(crate) fn around<I: IntoIterator<Item = Coord<f64>>>(coords: I) -> Option<Self> {
+        let mut coords = coords.into_iter();
+        let first = coords.next()?;
+        let (min, max) = coords.fold((first, first), |(min, max), c| {
+            (
+                coord! { x: min.x.min(c.x), y: min.y.min(c.y) },
+                coord! { x: max.x.max(c.x), y: max.y.max(c.y) },
+            )
+        });
+        Some(Self::new((min + max) / 2.0))
+    }
+
+    /// Projects a longitude and latitude into metres east and north of the origin.
+    pub(crate) fn project(&self, coord: Coord<f64>) -> Coord<f64> {
+        coord! {
+            x: (coord.x - self.origin.x) * self.metres_per_lng,
+            y: (coord.y - self.origin.y) * self.metres_per_lat,
+        }
+    }
+
+    /// Converts metres east and north of the origin back into a longitude and latitude.
+    pub(crate) fn unproject(&self, coord: Coord<f64>) -> Coord<f64> {
+        coord! {
+            x: self.origin.x + coord.x / self.metres_per_lng,
+            y: self.origin.y + coord.y / self.metres_per_lat,
+        }
+    }
+}
+
+/// Creates a projection around a point on a lake, which test paths and data are placed around.
+#[cfg(test)]
+pub(crate) fn lake() -> LocalProjection {
+    LocalProjection::new(coord! { x: 101.7, y: 3.1 })
+}
+
+/// Builds a path from a line and collection points in metres around a point on a lake.
+#[cfg(test)]
+pub(crate) fn lake_path(
+    line: &[(f64, f64)],
+    points: &[(f64, f64)],
+) -> (super::PathData, LocalProjection) {
+    let projection = lake();
+    let unproject = |&(x, y): &(f64, f64)| projection.unproject(coord! { x: x, y: y });
+    let path = super::PathData::new(
+        line.iter().map(unproject).collect(),
+        points
+            .iter()
+            .map(unproject)
+            .map(geo_types::Point::from)
+            .collect(),
+    );
+    (path, projection)
+}
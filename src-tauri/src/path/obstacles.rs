@@ -0,0 +1,444 @@
+//! Routing paths around areas the boat has to keep out of, such as islands and marinas.
+//!
+//! The keep-out areas are grown by a clearance and each leg that crosses one is replaced by the
+//! shortest route through the corners of the grown areas, found on a visibility graph in a local
+//! projection.
+
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    f64::consts::{FRAC_PI_4, TAU},
+};
+
+use geo::{line_intersection::line_intersection, Area, Contains, LineIntersection};
+use geo_types::{coord, Coord, Geometry, GeometryCollection, Line, LineString, Point, Polygon};
+use geojson::GeoJson;
+use serde::Serialize;
+
+use super::{local::LocalProjection, PathData};
+
+/// The largest angle in radians between the points on the rounded corners of a keep-out area.
+const ARC_STEP: f64 = FRAC_PI_4;
+
+/// A path routed around keep-out areas.
+#[derive(Debug, Clone, Serialize)]
+pub struct RoutedPath {
+    /// The path with the original collection points.
+    path: PathData,
+    /// The number of legs that were routed around a keep-out area.
+    detours: usize,
+}
+
+impl RoutedPath {
+    /// Gets the routed path.
+    pub fn path(&self) -> &PathData {
+        &self.path
+    }
+
+    /// Gets the number of legs that were routed around a keep-out area.
+    pub fn detours(&self) -> usize {
+        self.detours
+    }
+}
+
+/// Reads the polygons of the keep-out areas.
+///
+/// Holes in the polygons are ignored, so the whole area inside the exterior is kept out of.
+fn read_polygons(obstacles: &GeoJson) -> Result<Vec<Polygon<f64>>, String> {
+    let collection = GeometryCollection::try_from(obstacles).map_err(|e| e.to_string())?;
+    let mut polygons = vec![];
+    for geometry in collection {
+        match geometry {
+            Geometry::Polygon(p) => polygons.push(p),
+            Geometry::MultiPolygon(p) => polygons.extend(p),
+            Geometry::Rect(r) => polygons.push(r.to_polygon()),
+            Geometry::Triangle(t) => polygons.push(t.to_polygon()),
+            _ => return Err(String::from("Keep-Out Areas must be Polygons")),
+        }
+    }
+    Ok(polygons)
+}
+
+/// Gets the unit normal to the right of the direction from one coordinate to another.
+fn outward_normal(from: Coord<f64>, to: Coord<f64>) -> Coord<f64> {
+    let d = to - from;
+    let length = d.x.hypot(d.y);
+    coord! { x: d.y / length, y: -d.x / length }
+}
+
+/// Grows a polygon outwards by a distance, rounding its outer corners.
+///
+/// The rounded corners lie outside the true offset so every point of the result is at least the
+/// clearance from the original polygon.
+pub(super) fn grow(polygon: &Polygon<f64>, clearance: f64) -> Polygon<f64> {
+    let mut ring = polygon.exterior().0.clone();
+    ring.dedup();
+    if ring.len() > 1 && ring.first() == ring.last() {
+        ring.pop();
+    }
+    // Winding anticlockwise so the outside is to the right of each edge
+    if Polygon::new(LineString(ring.clone()), vec![]).signed_area() < 0.0 {
+        ring.reverse();
+    }
+    if clearance == 0.0 || ring.len() < 3 {
+        return Polygon::new(LineString(ring), vec![]);
+    }
+
+    let n = ring.len();
+    let mut grown = Vec::with_capacity(n * 3);
+    for i in 0..n {
+        let (previous, p, next) = (ring[(i + n - 1) % n], ring[i], ring[(i + 1) % n]);
+        let n1 = outward_normal(previous, p);
+        let n2 = outward_normal(p, next);
+        let turn = (p - previous).x * (next - p).y - (p - previous).y * (next - p).x;
+        let dot = n1.x * n2.x + n1.y * n2.y;
+
+        if turn > 0.0 {
+            // Rounding outer corners with points on lines tangent to the circle around the corner
+            let start = n1.y.atan2(n1.x);
+            let mut end = n2.y.atan2(n2.x);
+            if end < start {
+                end += TAU;
+            }
+            let steps = ((end - start) / ARC_STEP).ceil().max(1.0);
+            let step = (end - start) / steps;
+            let radius = clearance / (step / 2.0).cos();
+            grown.push(p + n1 * clearance);
+            for k in 0..steps as usize {
+                let angle = start + (k as f64 + 0.5) * step;
+                grown.push(p + coord! { x: angle.cos(), y: angle.sin() } * radius);
+            }
+            grown.push(p + n2 * clearance);
+        } else if turn < 0.0 && dot > -0.5 {
+            // Inner corners meet where the offset edges cross
+            grown.push(p + (n1 + n2) * (clearance / (1.0 + dot)));
+        } else {
+            grown.push(p + n1 * clearance);
+            grown.push(p + n2 * clearance);
+        }
+    }
+    Polygon::new(LineString(grown), vec![])
+}
+
+/// An entry in the queue of the search, ordered so the cheapest is popped first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Visit {
+    /// The length of the route to the node plus the straight distance to the target.
+    estimate: f64,
+    /// The node being visited.
+    node: usize,
+}
+
+impl Eq for Visit {}
+
+impl Ord for Visit {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+impl PartialOrd for Visit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Gets the distance between two projected coordinates.
+fn distance(a: Coord<f64>, b: Coord<f64>) -> f64 {
+    (a - b).x.hypot((a - b).y)
+}
+
+/// The grown keep-out areas and which of their corners can see each other.
+pub(super) struct Obstacles {
+    /// The grown keep-out areas in the local projection.
+    polygons: Vec<Polygon<f64>>,
+    /// The corners of the grown areas.
+    corners: Vec<Coord<f64>>,
+    /// The corners each corner can see.
+    visible: Vec<Vec<usize>>,
+}
+
+impl Obstacles {
+    /// Grows keep-out areas and finds the corners that can see each other.
+    pub(super) fn new(polygons: Vec<Polygon<f64>>) -> Self {
+        let corners = polygons
+            .iter()
+            .flat_map(|p| p.exterior().0.iter().copied())
+            .collect::<Vec<_>>();
+        let mut obstacles = Self {
+            polygons,
+            corners,
+            visible: vec![],
+        };
+        obstacles.visible = (0..obstacles.corners.len())
+            .map(|i| obstacles.visible_corners(obstacles.corners[i]))
+            .collect();
+        obstacles
+    }
+
+    /// Checks whether a coordinate is inside a keep-out area.
+    fn blocked(&self, c: Coord<f64>) -> bool {
+        self.polygons.iter().any(|p| p.contains(&Point::from(c)))
+    }
+
+    /// Checks whether a straight line between two coordinates stays out of the keep-out areas.
+    ///
+    /// Lines may run along the edges and through the corners of the areas.
+    fn clear(&self, a: Coord<f64>, b: Coord<f64>) -> bool {
+        let line = Line::new(a, b);
+        let crosses = self.polygons.iter().any(|p| {
+            p.exterior().lines().any(|edge| {
+                matches!(
+                    line_intersection(line, edge),
+                    Some(LineIntersection::SinglePoint {
+                        is_proper: true,
+                        ..
+                    })
+                )
+            })
+        });
+        !crosses && !self.blocked(a + (b - a) * 0.5)
+    }
+
+    /// Finds the corners that can be reached in a straight line from a coordinate.
+    fn visible_corners(&self, c: Coord<f64>) -> Vec<usize> {
+        (0..self.corners.len())
+            .filter(|&i| self.corners[i] != c && self.clear(c, self.corners[i]))
+            .collect()
+    }
+
+    /// Finds the shortest route between two coordinates, returning the corners passed.
+    pub(super) fn route(&self, start: Coord<f64>, end: Coord<f64>) -> Option<Vec<Coord<f64>>> {
+        if self.clear(start, end) {
+            return Some(vec![]);
+        }
+
+        // The start and end are added after the corners
+        let (source, target) = (self.corners.len(), self.corners.len() + 1);
+        let position = |node: usize| match node {
+            n if n == source => start,
+            n if n == target => end,
+            n => self.corners[n],
+        };
+        let to_target = self.visible_corners(end);
+        let neighbours = |node: usize| -> Vec<usize> {
+            let mut nodes = if node == source {
+                self.visible_corners(start)
+            } else {
+                self.visible[node].clone()
+            };
+            if node != source && to_target.contains(&node) {
+                nodes.push(target);
+            }
+            nodes
+        };
+
+        let mut lengths = vec![f64::INFINITY; self.corners.len() + 2];
+        let mut previous = vec![None; self.corners.len() + 2];
+        let mut queue = BinaryHeap::new();
+        lengths[source] = 0.0;
+        queue.push(Visit {
+            estimate: distance(start, end),
+            node: source,
+        });
+        while let Some(Visit { node, .. }) = queue.pop() {
+            if node == target {
+                let mut corners = vec![];
+                let mut current = previous[target];
+                while let Some(n) = current.filter(|&n| n != source) {
+                    corners.push(self.corners[n]);
+                    current = previous[n];
+                }
+                corners.reverse();
+                return Some(corners);
+            }
+            for next in neighbours(node) {
+                let length = lengths[node] + distance(position(node), position(next));
+                if length < lengths[next] {
+                    lengths[next] = length;
+                    previous[next] = Some(node);
+                    queue.push(Visit {
+                        estimate: length + distance(position(next), end),
+                        node: next,
+                    });
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Routes the legs of a path around keep-out areas, keeping its collection points.
+///
+/// The clearance is the smallest distance in metres kept from the keep-out areas.
+pub fn avoid(path: &PathData, obstacles: &GeoJson, clearance: f64) -> Result<RoutedPath, String> {
+    if !(clearance.is_finite() && clearance >= 0.0) {
+        return Err(String::from("Clearance must not be Negative"));
+    }
+    let polygons = read_polygons(obstacles)?;
+    log::debug!("Keep-Out Areas: {}", polygons.len());
+
+    let line = path.path();
+    let coords = line
+        .0
+        .iter()
+        .copied()
+        .chain(polygons.iter().flat_map(|p| p.exterior().0.iter().copied()));
+    let projection = match LocalProjection::around(coords) {
+        Some(v) if !polygons.is_empty() && line.0.len() > 1 => v,
+        _ => {
+            return Ok(RoutedPath {
+                path: path.clone(),
+                detours: 0,
+            })
+        }
+    };
+
+    log::info!("Growing Keep-Out Areas");
+    let obstacles = Obstacles::new(
+        polygons
+            .iter()
+            .map(|p| {
+                let projected = p
+                    .exterior()
+                    .0
+                    .iter()
+                    .map(|&c| projection.project(c))
+                    .collect::<LineString<f64>>();
+                grow(&Polygon::new(projected, vec![]), clearance)
+            })
+            .collect(),
+    );
+
+    log::info!("Routing Legs");
+    let projected = line
+        .0
+        .iter()
+        .map(|&c| projection.project(c))
+        .collect::<Vec<_>>();
+    if let Some(i) = projected.iter().position(|&c| obstacles.blocked(c)) {
+        return Err(format!(
+            "Path Vertex in Keep-Out Area: ({}, {})",
+            line.0[i].x, line.0[i].y
+        ));
+    }
+    let mut routed = vec![line.0[0]];
+    let mut detours = 0;
+    for (i, pair) in projected.windows(2).enumerate() {
+        let corners = obstacles.route(pair[0], pair[1]).ok_or(format!(
+            "Unable to Route Leg {} around Keep-Out Areas",
+            i + 1
+        ))?;
+        if !corners.is_empty() {
+            detours += 1;
+        }
+        routed.extend(corners.into_iter().map(|c| projection.unproject(c)));
+        routed.push(line.0[i + 1]);
+    }
+    log::debug!("Detours: {}", detours);
+
+    Ok(RoutedPath {
+        path: PathData {
+            path: LineString(routed),
+            ..path.clone()
+        },
+        detours,
+    })
+}
+
+/// Preview routing the legs of a path around keep-out areas.
+///
+/// The keep-out areas are GeoJSON polygons and the clearance is in metres.
+#[tauri::command]
+pub fn avoid_obstacles(
+    path: PathData,
+    obstacles: GeoJson,
+    clearance: f64,
+) -> Result<RoutedPath, String> {
+    log::info!("Routing Path around Keep-Out Areas");
+    avoid(&path, &obstacles, clearance)
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::EuclideanDistance;
+    use geo_types::Point;
+
+    use super::*;
+    use crate::path::local::{lake, lake_path};
+
+    /// Builds a square island in metres as GeoJSON.
+    fn island(min: (f64, f64), max: (f64, f64)) -> (GeoJson, Polygon<f64>) {
+        let projection = lake();
+        let square = Polygon::new(
+            LineString::from(vec![min, (max.0, min.1), max, (min.0, max.1), min]),
+            vec![],
+        );
+        let coords = square
+            .exterior()
+            .0
+            .iter()
+            .map(|&c| projection.unproject(c))
+            .collect::<LineString<f64>>();
+        let geometry = geojson::Geometry::from(&Polygon::new(coords, vec![]));
+        (GeoJson::from(geometry), square)
+    }
+
+    #[test]
+    fn routes_around_island() {
+        let track = lake_path(&[(0.0, 0.0), (1000.0, 0.0)], &[(0.0, 0.0)]).0;
+        let (obstacles, square) = island((400.0, -100.0), (600.0, 50.0));
+        let routed = avoid(&track, &obstacles, 20.0).unwrap();
+
+        assert_eq!(routed.detours(), 1);
+        assert_eq!(routed.path().collection_points(), track.collection_points());
+        let line = routed
+            .path()
+            .path()
+            .0
+            .iter()
+            .map(|&c| lake().project(c))
+            .collect::<Vec<_>>();
+        assert!(line.len() > 2);
+        assert_eq!(line[0], lake().project(track.path().0[0]));
+        // Passing on the short side of the island with the clearance
+        assert!(line.iter().all(|c| c.y >= 0.0));
+        for leg in line.windows(2) {
+            let leg = Line::new(leg[0], leg[1]);
+            let gap = leg.euclidean_distance(&square);
+            assert!(gap > 19.9, "{:?} is {} m from the island", leg, gap);
+        }
+    }
+
+    #[test]
+    fn keeps_clear_legs() {
+        let track = lake_path(&[(0.0, 0.0), (1000.0, 0.0)], &[(0.0, 0.0)]).0;
+        let (obstacles, _) = island((400.0, 100.0), (600.0, 200.0));
+        let routed = avoid(&track, &obstacles, 20.0).unwrap();
+
+        assert_eq!(routed.detours(), 0);
+        assert_eq!(routed.path(), &track);
+    }
+
+    #[test]
+    fn rejects_vertices_in_keep_out_areas() {
+        let track = lake_path(&[(0.0, 0.0), (500.0, 10.0), (1000.0, 0.0)], &[(0.0, 0.0)]).0;
+        let (obstacles, _) = island((400.0, -100.0), (600.0, 50.0));
+        assert!(avoid(&track, &obstacles, 20.0).is_err());
+
+        // The clearance also has to be kept
+        let track = lake_path(&[(0.0, 0.0), (500.0, 60.0)], &[(0.0, 0.0)]).0;
+        assert!(avoid(&track, &obstacles, 5.0).is_ok());
+        assert!(avoid(&track, &obstacles, 20.0).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_keep_out_areas() {
+        let track = lake_path(&[(0.0, 0.0), (1000.0, 0.0)], &[(0.0, 0.0)]).0;
+        let point = GeoJson::from(geojson::Geometry::from(&Point::new(101.7, 3.1)));
+        assert!(avoid(&track, &point, 20.0).is_err());
+
+        let (obstacles, _) = island((400.0, -100.0), (600.0, 50.0));
+        assert!(avoid(&track, &obstacles, -1.0).is_err());
+    }
+}
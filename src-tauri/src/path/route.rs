@@ -0,0 +1,328 @@
+//! Ordering collection points to shorten the route of the boat.
+//!
+//! The order is found with a nearest neighbour tour improved by 2-opt moves, using distances in a
+//! local projection around the points.
+
+use geo::GeodesicLength;
+use geo_types::{Coord, LineString, MultiPoint};
+use serde::{Deserialize, Serialize};
+
+use super::{local::LocalProjection, PathData};
+
+/// The most passes of 2-opt moves made over the route.
+const MAX_PASSES: usize = 1000;
+
+/// Improvements smaller than this in metres are ignored so the search ends.
+const TOLERANCE: f64 = 1e-6;
+
+/// Where the route has to start and end.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct RouteOptions {
+    /// The longitude and latitude the route starts from, such as the launch site.
+    start: Option<(f64, f64)>,
+    /// The longitude and latitude the route ends at.
+    end: Option<(f64, f64)>,
+}
+
+impl RouteOptions {
+    /// Creates new options.
+    pub fn new(start: Option<Coord<f64>>, end: Option<Coord<f64>>) -> Self {
+        Self {
+            start: start.map(|c| c.x_y()),
+            end: end.map(|c| c.x_y()),
+        }
+    }
+
+    /// Gets the coordinate the route starts from.
+    fn start(&self) -> Option<Coord<f64>> {
+        self.start.map(Coord::from)
+    }
+
+    /// Gets the coordinate the route ends at.
+    fn end(&self) -> Option<Coord<f64>> {
+        self.end.map(Coord::from)
+    }
+}
+
+/// A path through the reordered collection points and how much shorter it is.
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderedRoute {
+    /// The path visiting the collection points in their new order.
+    path: PathData,
+    /// The geodesic length in metres visiting the points in their original order.
+    original_length: f64,
+    /// The geodesic length in metres of the new path.
+    length: f64,
+}
+
+impl OrderedRoute {
+    /// Gets the path visiting the collection points in their new order.
+    pub fn path(&self) -> &PathData {
+        &self.path
+    }
+
+    /// Gets the length in metres visiting the points in their original order.
+    pub fn original_length(&self) -> f64 {
+        self.original_length
+    }
+
+    /// Gets the length in metres of the new path.
+    pub fn length(&self) -> f64 {
+        self.length
+    }
+}
+
+/// The stops of a route in a local projection.
+///
+/// Free ends are represented by stops with no position that are no distance from every other
+/// stop, so the same moves work whether or not the ends are fixed.
+struct Stops {
+    /// The positions in metres, `None` for a free end.
+    positions: Vec<Option<Coord<f64>>>,
+}
+
+impl Stops {
+    /// Gets the distance in metres between two stops.
+    fn distance(&self, a: usize, b: usize) -> f64 {
+        match (self.positions[a], self.positions[b]) {
+            (Some(a), Some(b)) => (a - b).x.hypot((a - b).y),
+            _ => 0.0,
+        }
+    }
+
+    /// Visits the closest unvisited stop each time, starting from the first stop and ending at
+    /// the last.
+    fn nearest_neighbour(&self) -> Vec<usize> {
+        let last = self.positions.len() - 1;
+        let mut unvisited = (1..last).collect::<Vec<_>>();
+        let mut tour = Vec::with_capacity(self.positions.len());
+        tour.push(0);
+        while !unvisited.is_empty() {
+            let current = tour[tour.len() - 1];
+            let (i, _) = unvisited
+                .iter()
+                .enumerate()
+                .min_by(|(_, &a), (_, &b)| {
+                    self.distance(current, a)
+                        .total_cmp(&self.distance(current, b))
+                })
+                .unwrap();
+            tour.push(unvisited.swap_remove(i));
+        }
+        tour.push(last);
+        tour
+    }
+
+    /// Reverses sections of the tour while doing so shortens it, keeping the ends in place.
+    fn two_opt(&self, tour: &mut [usize]) {
+        let n = tour.len();
+        for _ in 0..MAX_PASSES {
+            let mut improved = false;
+            for i in 1..n - 2 {
+                for j in i + 1..n - 1 {
+                    let before =
+                        self.distance(tour[i - 1], tour[i]) + self.distance(tour[j], tour[j + 1]);
+                    let after =
+                        self.distance(tour[i - 1], tour[j]) + self.distance(tour[i], tour[j + 1]);
+                    if after < before - TOLERANCE {
+                        tour[i..=j].reverse();
+                        improved = true;
+                    }
+                }
+            }
+            if !improved {
+                return;
+            }
+        }
+        log::warn!("Stopped Improving Route after {} Passes", MAX_PASSES);
+    }
+}
+
+/// Builds the line through the collection points between the ends of the route.
+fn route_line<I: IntoIterator<Item = Coord<f64>>>(
+    options: &RouteOptions,
+    points: I,
+) -> LineString<f64> {
+    options
+        .start()
+        .into_iter()
+        .chain(points)
+        .chain(options.end())
+        .collect()
+}
+
+/// Reorders the collection points of a path to shorten the route between them.
+///
+/// The path is rebuilt as straight lines through the points in their new order, and the sampling
+/// instructions move with their points.
+pub fn order_points(path: &PathData, options: &RouteOptions) -> Result<OrderedRoute, String> {
+    for c in options.start().into_iter().chain(options.end()) {
+        if !(c.x.is_finite() && c.y.is_finite()) {
+            return Err(String::from("Invalid Route End"));
+        }
+    }
+
+    let points = &path.collection_points().0;
+    let coords = points.iter().map(|p| p.0).collect::<Vec<_>>();
+    let original = route_line(options, coords.iter().copied());
+
+    let order = match LocalProjection::around(original.0.iter().copied()) {
+        Some(projection) => {
+            log::info!("Ordering {} Collection Points", coords.len());
+            let project = |c: Option<Coord<f64>>| c.map(|c| projection.project(c));
+            let positions = std::iter::once(project(options.start()))
+                .chain(coords.iter().map(|&c| project(Some(c))))
+                .chain(std::iter::once(project(options.end())))
+                .collect::<Vec<_>>();
+            let stops = Stops { positions };
+
+            let mut tour = stops.nearest_neighbour();
+            stops.two_opt(&mut tour);
+            // Removing the ends and shifting back to the index of the point
+            tour[1..tour.len() - 1].iter().map(|i| i - 1).collect()
+        }
+        None => vec![],
+    };
+    log::debug!("Order: {:?}", order);
+
+    let collection_points = order.iter().map(|&i| points[i]).collect::<Vec<_>>();
+    let sampling = order
+        .iter()
+        .map(|&i| path.sampling()[i].clone())
+        .collect::<Vec<_>>();
+    let line = route_line(options, collection_points.iter().map(|p| p.0));
+    let length = line.geodesic_length();
+    let ordered = PathData {
+        path: line,
+        collection_points: MultiPoint(collection_points),
+        sampling,
+        ..path.clone()
+    };
+
+    Ok(OrderedRoute {
+        path: ordered,
+        original_length: original.geodesic_length(),
+        length,
+    })
+}
+
+/// Preview reordering the collection points of a path to shorten its route.
+///
+/// The route runs between the home and end points of the path if no options are given.
+#[tauri::command]
+pub fn order_collection_points(
+    path: PathData,
+    options: Option<RouteOptions>,
+) -> Result<OrderedRoute, String> {
+    log::info!("Ordering Collection Points");
+    let options = options
+        .unwrap_or_else(|| RouteOptions::new(path.home().map(|p| p.0), path.finish().map(|p| p.0)));
+    order_points(&path, &options)
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::coord;
+
+    use super::*;
+    use crate::{
+        data::Layer,
+        path::{local::lake_path, sampling::SamplingInstructions},
+    };
+
+    /// Gets the collection points of a path in metres.
+    fn positions(path: &PathData, projection: &LocalProjection) -> Vec<(f64, f64)> {
+        path.collection_points()
+            .0
+            .iter()
+            .map(|p| {
+                let c = projection.project(p.0);
+                ((c.x * 1e3).round() / 1e3, (c.y * 1e3).round() / 1e3)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn orders_points_along_line() {
+        let (track, projection) = lake_path(
+            &[],
+            &[
+                (0.0, 0.0),
+                (300.0, 0.0),
+                (100.0, 0.0),
+                (400.0, 0.0),
+                (200.0, 0.0),
+            ],
+        );
+        let route = order_points(&track, &RouteOptions::default()).unwrap();
+
+        let order = positions(route.path(), &projection);
+        assert_eq!(order.len(), 5);
+        assert!(
+            order.windows(2).all(|w| w[0].0 < w[1].0) || order.windows(2).all(|w| w[0].0 > w[1].0)
+        );
+        assert!((route.length() - 400.0).abs() < 0.5);
+        assert!(route.original_length() > route.length());
+        assert_eq!(route.path().path().0.len(), 5);
+    }
+
+    #[test]
+    fn keeps_fixed_ends() {
+        let (track, projection) = lake_path(&[], &[(100.0, 0.0), (300.0, 0.0), (200.0, 0.0)]);
+        let start = projection.unproject(coord! { x: 400.0, y: 0.0 });
+        let end = projection.unproject(coord! { x: 0.0, y: 0.0 });
+        let route = order_points(&track, &RouteOptions::new(Some(start), Some(end))).unwrap();
+
+        assert_eq!(
+            positions(route.path(), &projection),
+            vec![(300.0, 0.0), (200.0, 0.0), (100.0, 0.0)]
+        );
+        let line = &route.path().path().0;
+        assert_eq!(line.len(), 5);
+        assert_eq!(line[0], start);
+        assert_eq!(line[4], end);
+    }
+
+    #[test]
+    fn untangles_crossing_route() {
+        // Corners of a square visited across the diagonals
+        let (track, projection) = lake_path(
+            &[],
+            &[(0.0, 0.0), (100.0, 100.0), (100.0, 0.0), (0.0, 100.0)],
+        );
+        let route = order_points(&track, &RouteOptions::default()).unwrap();
+
+        assert!((route.length() - 300.0).abs() < 0.5);
+        assert_eq!(positions(route.path(), &projection).len(), 4);
+    }
+
+    #[test]
+    fn moves_sampling_with_points() {
+        let (track, _) = lake_path(&[], &[(0.0, 0.0), (200.0, 0.0), (100.0, 0.0)]);
+        let labelled = |label: &str| {
+            SamplingInstructions::new(vec![Layer::Surface], vec![], None, 1, Some(label.into()))
+                .unwrap()
+        };
+        let track = track
+            .with_sampling(vec![labelled("A"), labelled("C"), labelled("B")])
+            .unwrap();
+        let route = order_points(&track, &RouteOptions::default()).unwrap();
+
+        let labels = route
+            .path()
+            .sampling()
+            .iter()
+            .map(|s| s.label().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(labels, vec!["A", "B", "C"]);
+    }
+
+    #[test]
+    fn handles_empty_path() {
+        let (track, _) = lake_path(&[], &[]);
+        let route = order_points(&track, &RouteOptions::default()).unwrap();
+        assert!(route.path().collection_points().0.is_empty());
+        assert_eq!(route.length(), 0.0);
+    }
+}
@@ -0,0 +1,164 @@
+//! Instructions for what the boat samples at each collection point.
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::Layer;
+
+/// What the boat samples at a collection point.
+///
+/// Missing fields take their default values, so `{}` samples every layer once.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SamplingInstructions {
+    /// The layers of water to sample.
+    layers: Vec<Layer>,
+    /// Depths to sample in metres below the surface, in addition to the layers.
+    depths: Vec<f64>,
+    /// The time to hold position at the point in seconds.
+    ///
+    /// The dwell time of the boat is used if this is not given.
+    dwell_time: Option<f64>,
+    /// The number of times to take each sample.
+    repeat: u32,
+    /// A name for the point shown to the user.
+    label: Option<String>,
+}
+
+impl SamplingInstructions {
+    /// Creates new sampling instructions.
+    pub fn new(
+        layers: Vec<Layer>,
+        depths: Vec<f64>,
+        dwell_time: Option<f64>,
+        repeat: u32,
+        label: Option<String>,
+    ) -> Result<Self, String> {
+        let instructions = Self {
+            layers,
+            depths,
+            dwell_time,
+            repeat,
+            label,
+        };
+        instructions.validate()?;
+        Ok(instructions)
+    }
+
+    /// Checks that the instructions can be carried out.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.layers.is_empty() && self.depths.is_empty() {
+            return Err(String::from("Sampling requires a Layer or Depth"));
+        }
+        if self.depths.iter().any(|d| !(d.is_finite() && *d >= 0.0)) {
+            return Err(String::from("Sampling Depths must not be Negative"));
+        }
+        if matches!(self.dwell_time, Some(v) if !(v.is_finite() && v >= 0.0)) {
+            return Err(String::from("Dwell Time must not be Negative"));
+        }
+        if self.repeat == 0 {
+            return Err(String::from("Sampling Repeat must be Positive"));
+        }
+        Ok(())
+    }
+
+    /// Gets the layers of water to sample.
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    /// Gets the depths to sample in metres below the surface.
+    pub fn depths(&self) -> &[f64] {
+        &self.depths
+    }
+
+    /// Gets the time to hold position at the point in seconds.
+    pub fn dwell_time(&self) -> Option<f64> {
+        self.dwell_time
+    }
+
+    /// Gets the number of times to take each sample.
+    pub fn repeat(&self) -> u32 {
+        self.repeat
+    }
+
+    /// Gets the name for the point.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+}
+
+impl Default for SamplingInstructions {
+    fn default() -> Self {
+        Self {
+            layers: vec![Layer::Surface, Layer::Middle, Layer::SeaBed],
+            depths: vec![],
+            dwell_time: None,
+            repeat: 1,
+            label: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::{line_string, point, MultiPoint};
+
+    use super::*;
+    use crate::path::PathData;
+
+    fn path() -> PathData {
+        PathData::new(
+            line_string![(x: 0.0, y: 0.0), (x: 1.0, y: 0.0)],
+            MultiPoint(vec![point!(x: 0.0, y: 0.0), point!(x: 1.0, y: 0.0)]),
+        )
+    }
+
+    #[test]
+    fn keeps_sampling_in_geojson() {
+        let instructions = SamplingInstructions::new(
+            vec![Layer::SeaBed],
+            vec![2.5],
+            Some(30.0),
+            2,
+            Some(String::from("Inlet")),
+        )
+        .unwrap();
+        let path = path()
+            .with_sampling(vec![SamplingInstructions::default(), instructions])
+            .unwrap();
+
+        let parsed = path.to_string().parse::<PathData>().unwrap();
+        assert_eq!(parsed, path);
+        assert_eq!(parsed.sampling()[1].label(), Some("Inlet"));
+    }
+
+    #[test]
+    fn defaults_missing_sampling() {
+        let geojson = r#"{"type": "FeatureCollection", "version": "0.2.0", "features": [
+            {"type": "Feature", "properties": null,
+             "geometry": {"type": "MultiPoint", "coordinates": [[0.0, 0.0]]}},
+            {"type": "Feature", "properties": null,
+             "geometry": {"type": "LineString", "coordinates": [[0.0, 0.0], [1.0, 0.0]]}}
+        ]}"#;
+        let path = geojson.parse::<PathData>().unwrap();
+        assert_eq!(path.sampling(), &[SamplingInstructions::default()]);
+
+        let partial = geojson.replace(r#""properties": null,"#, r#""sampling": [{"repeat": 3}],"#);
+        let path = partial.parse::<PathData>().unwrap();
+        assert_eq!(path.sampling()[0].repeat(), 3);
+        assert_eq!(path.sampling()[0].layers().len(), 3);
+    }
+
+    #[test]
+    fn rejects_invalid_sampling() {
+        assert!(path().with_sampling(vec![]).is_err());
+        assert!(path()
+            .with_sampling(vec![SamplingInstructions::default(); 3])
+            .is_err());
+        assert!(SamplingInstructions::new(vec![], vec![], None, 1, None).is_err());
+        assert!(
+            SamplingInstructions::new(vec![Layer::Surface], vec![-1.0], None, 1, None).is_err()
+        );
+        assert!(SamplingInstructions::new(vec![Layer::Surface], vec![], None, 0, None).is_err());
+    }
+}
@@ -0,0 +1,273 @@
+//! Reducing the number of vertices in a path.
+//!
+//! Tolerances are in metres, measured in a local projection around the path.
+
+use geo::{EuclideanDistance, GeodesicDistance, Simplify, SimplifyVw};
+use geo_types::{LineString, Point};
+use serde::{Deserialize, Serialize};
+
+use super::{interpolate::resample, local::LocalProjection, PathData};
+
+/// A way of reducing the vertices of a path.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Simplification {
+    /// Douglas–Peucker simplification keeping the path within a distance of the original.
+    DouglasPeucker {
+        /// The largest distance the path may move in metres.
+        tolerance: f64,
+    },
+    /// Visvalingam–Whyatt simplification removing vertices that add little area.
+    Visvalingam {
+        /// Vertices whose triangle with their neighbours is smaller than the square of this
+        /// distance in metres are removed.
+        tolerance: f64,
+    },
+    /// Replace the vertices with vertices at a fixed distance along the path.
+    Resample {
+        /// The distance between vertices in metres.
+        spacing: f64,
+    },
+    /// Remove vertices that are almost on top of the previous vertex.
+    RemoveDuplicates {
+        /// Vertices closer than this in metres are removed.
+        tolerance: f64,
+    },
+}
+
+impl Simplification {
+    /// Gets the distance of the simplification in metres.
+    fn distance(&self) -> f64 {
+        match *self {
+            Self::DouglasPeucker { tolerance }
+            | Self::Visvalingam { tolerance }
+            | Self::RemoveDuplicates { tolerance } => tolerance,
+            Self::Resample { spacing } => spacing,
+        }
+    }
+}
+
+/// A simplified path and how much it changed.
+#[derive(Debug, Clone, Serialize)]
+pub struct SimplifiedPath {
+    /// The simplified path with the original collection points and sampling.
+    path: PathData,
+    /// The number of vertices before simplifying.
+    original_vertices: usize,
+    /// The number of vertices after simplifying.
+    vertices: usize,
+    /// The largest distance in metres from a vertex of the original path to the new path.
+    max_deviation: f64,
+}
+
+impl SimplifiedPath {
+    /// Gets the simplified path.
+    pub fn path(&self) -> &PathData {
+        &self.path
+    }
+
+    /// Gets the number of vertices before simplifying.
+    pub fn original_vertices(&self) -> usize {
+        self.original_vertices
+    }
+
+    /// Gets the number of vertices after simplifying.
+    pub fn vertices(&self) -> usize {
+        self.vertices
+    }
+
+    /// Gets the largest distance in metres from the original path to the new path.
+    pub fn max_deviation(&self) -> f64 {
+        self.max_deviation
+    }
+}
+
+/// Removes vertices within a distance in metres of the previous kept vertex.
+///
+/// The last vertex is always kept so the path ends in the same place.
+fn remove_duplicates(path: &LineString<f64>, tolerance: f64) -> LineString<f64> {
+    let mut vertices: Vec<Point<f64>> = Vec::with_capacity(path.0.len());
+    let last = path.0.len().saturating_sub(1);
+    for (i, p) in path.points().enumerate() {
+        match vertices.last() {
+            Some(previous) if previous.geodesic_distance(&p) < tolerance => {
+                if i == last {
+                    // Moving the kept vertex to the end
+                    vertices.pop();
+                    if vertices.is_empty() {
+                        vertices.push(path.0[0].into());
+                    }
+                    vertices.push(p);
+                }
+            }
+            _ => vertices.push(p),
+        }
+    }
+    LineString::from(vertices)
+}
+
+/// Reduces the vertices of a path, keeping its collection points.
+pub fn simplify(path: &PathData, method: &Simplification) -> Result<SimplifiedPath, String> {
+    let distance = method.distance();
+    if !(distance.is_finite() && distance > 0.0) {
+        return Err(String::from("Tolerance must be Positive"));
+    }
+    log::debug!("Simplifying Path with {:?}", method);
+
+    let original = path.path();
+    let projection = match LocalProjection::around(original.0.iter().copied()) {
+        Some(v) => v,
+        None => {
+            return Ok(SimplifiedPath {
+                path: path.clone(),
+                original_vertices: 0,
+                vertices: 0,
+                max_deviation: 0.0,
+            })
+        }
+    };
+    let project = |line: &LineString<f64>| -> LineString<f64> {
+        line.0.iter().map(|&c| projection.project(c)).collect()
+    };
+    let unproject = |line: LineString<f64>| -> LineString<f64> {
+        line.0
+            .into_iter()
+            .map(|c| projection.unproject(c))
+            .collect()
+    };
+
+    let simplified = match *method {
+        Simplification::DouglasPeucker { tolerance } => {
+            unproject(project(original).simplify(&tolerance))
+        }
+        Simplification::Visvalingam { tolerance } => {
+            unproject(project(original).simplify_vw(&(tolerance * tolerance)))
+        }
+        Simplification::Resample { spacing } => resample(original, spacing)?,
+        Simplification::RemoveDuplicates { tolerance } => remove_duplicates(original, tolerance),
+    };
+
+    log::info!("Measuring Deviation");
+    let projected = project(&simplified);
+    let max_deviation = original
+        .0
+        .iter()
+        .map(|&c| Point::from(projection.project(c)).euclidean_distance(&projected))
+        .fold(0.0, f64::max);
+    log::debug!(
+        "Vertices: {} -> {}, Max Deviation: {}",
+        original.0.len(),
+        simplified.0.len(),
+        max_deviation
+    );
+
+    Ok(SimplifiedPath {
+        original_vertices: original.0.len(),
+        vertices: simplified.0.len(),
+        max_deviation,
+        path: PathData {
+            path: simplified,
+            ..path.clone()
+        },
+    })
+}
+
+/// Preview reducing the vertices of a path, reporting how far the simplified path strays.
+#[tauri::command]
+pub fn simplify_path(path: PathData, method: Simplification) -> Result<SimplifiedPath, String> {
+    log::info!("Simplifying Path");
+    simplify(&path, &method)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::path::local::lake_path;
+
+    /// A zig-zag track 1 km long wandering up to 2 m from a straight line.
+    fn noisy_track() -> (PathData, LocalProjection) {
+        let coords = (0..=1000)
+            .map(|i| {
+                (
+                    i as f64,
+                    if i % 2 == 0 { 2.0 } else { -2.0 } * (i % 3) as f64 / 2.0,
+                )
+            })
+            .collect::<Vec<_>>();
+        lake_path(&coords, &[])
+    }
+
+    #[test]
+    fn simplifies_within_tolerance() {
+        let (track, _) = noisy_track();
+        for method in [
+            Simplification::DouglasPeucker { tolerance: 5.0 },
+            Simplification::Visvalingam { tolerance: 5.0 },
+        ] {
+            // Visvalingam–Whyatt does not bound the distance but stays close on small noise
+            let simplified = simplify(&track, &method).unwrap();
+            assert_eq!(simplified.original_vertices(), 1001);
+            assert!(simplified.vertices() < 100, "{:?}", method);
+            assert!(simplified.max_deviation() <= 5.0, "{:?}", method);
+            assert!(simplified.max_deviation() > 1.0, "{:?}", method);
+        }
+
+        let simplified = simplify(&track, &Simplification::DouglasPeucker { tolerance: 0.5 });
+        assert_eq!(simplified.unwrap().vertices(), 1001);
+    }
+
+    #[test]
+    fn resamples_vertices() {
+        let (track, _) = lake_path(
+            &(0..=1050).map(|i| (i as f64, 0.0)).collect::<Vec<_>>(),
+            &[],
+        );
+        let simplified = simplify(&track, &Simplification::Resample { spacing: 100.0 }).unwrap();
+
+        assert_eq!(simplified.vertices(), 12);
+        assert!(simplified.max_deviation() < 1e-3);
+        assert_eq!(simplified.path().path().0.last(), track.path().0.last());
+    }
+
+    #[test]
+    fn removes_near_duplicates() {
+        let (track, projection) = lake_path(
+            &[
+                (0.0, 0.0),
+                (0.1, 0.0),
+                (10.0, 0.0),
+                (10.0, 0.2),
+                (20.0, 0.0),
+                (20.3, 0.0),
+            ],
+            &[],
+        );
+        let simplified =
+            simplify(&track, &Simplification::RemoveDuplicates { tolerance: 0.5 }).unwrap();
+
+        let coords = simplified
+            .path()
+            .path()
+            .0
+            .iter()
+            .map(|&c| projection.project(c))
+            .collect::<Vec<_>>();
+        assert_eq!(coords.len(), 3);
+        assert!((coords[1].x - 10.0).abs() < 1e-6);
+        assert!((coords[2].x - 20.3).abs() < 1e-6);
+        assert!(simplified.max_deviation() < 0.5);
+    }
+
+    #[test]
+    fn rejects_invalid_tolerance() {
+        let (track, _) = noisy_track();
+        for method in [
+            Simplification::DouglasPeucker { tolerance: 0.0 },
+            Simplification::Visvalingam { tolerance: -1.0 },
+            Simplification::Resample { spacing: f64::NAN },
+        ] {
+            assert!(simplify(&track, &method).is_err());
+        }
+    }
+}
@@ -0,0 +1,344 @@
+//! Estimates of how long a path takes and how much energy it uses.
+
+use geo::GeodesicBearing;
+use geo_types::{Line, Point};
+use serde::{Deserialize, Serialize};
+
+use super::PathData;
+
+/// Joules in a watt hour.
+const JOULES_PER_WATT_HOUR: f64 = 3600.0;
+
+/// Power drawn by the boat in different states.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct EnergyModel {
+    /// Power drawn by the motors while cruising in watts.
+    cruise_power: f64,
+    /// Power drawn while holding position to collect a sample in watts.
+    sample_power: f64,
+    /// Power always drawn by the electronics in watts.
+    idle_power: f64,
+    /// Usable capacity of the battery in watt hours.
+    battery_capacity: f64,
+}
+
+impl EnergyModel {
+    /// Creates a new energy model.
+    pub fn new(
+        cruise_power: f64,
+        sample_power: f64,
+        idle_power: f64,
+        battery_capacity: f64,
+    ) -> Self {
+        Self {
+            cruise_power,
+            sample_power,
+            idle_power,
+            battery_capacity,
+        }
+    }
+
+    /// Checks that the model has physically sensible values.
+    fn validate(&self) -> Result<(), String> {
+        for (name, value) in [
+            ("Cruise Power", self.cruise_power),
+            ("Sample Power", self.sample_power),
+            ("Idle Power", self.idle_power),
+        ] {
+            if !(value.is_finite() && value >= 0.0) {
+                return Err(format!("{name} must not be Negative"));
+            }
+        }
+        if !(self.battery_capacity.is_finite() && self.battery_capacity > 0.0) {
+            return Err(String::from("Battery Capacity must be Positive"));
+        }
+        Ok(())
+    }
+}
+
+/// How the boat travels along a path.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct StatsOptions {
+    /// The speed of the boat between collection points in metres per second.
+    cruise_speed: f64,
+    /// The time spent collecting each sample in seconds.
+    ///
+    /// Collection points with their own dwell time use that instead.
+    dwell_time: f64,
+    /// The power drawn by the boat.
+    energy_model: EnergyModel,
+}
+
+impl StatsOptions {
+    /// Creates new options.
+    pub fn new(cruise_speed: f64, dwell_time: f64, energy_model: EnergyModel) -> Self {
+        Self {
+            cruise_speed,
+            dwell_time,
+            energy_model,
+        }
+    }
+
+    /// Checks that the options describe a boat that can move.
+    fn validate(&self) -> Result<(), String> {
+        if !(self.cruise_speed.is_finite() && self.cruise_speed > 0.0) {
+            return Err(String::from("Cruise Speed must be Positive"));
+        }
+        if !(self.dwell_time.is_finite() && self.dwell_time >= 0.0) {
+            return Err(String::from("Dwell Time must not be Negative"));
+        }
+        self.energy_model.validate()
+    }
+}
+
+/// A straight section of a path between two vertices.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Leg {
+    /// The geodesic length of the leg in metres.
+    length: f64,
+    /// The initial bearing of the leg in degrees clockwise from north.
+    bearing: f64,
+}
+
+impl Leg {
+    /// Gets the geodesic length of the leg in metres.
+    pub fn length(&self) -> f64 {
+        self.length
+    }
+
+    /// Gets the initial bearing of the leg in degrees clockwise from north.
+    pub fn bearing(&self) -> f64 {
+        self.bearing
+    }
+}
+
+/// Estimates for running a path.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PathStats {
+    /// The total geodesic length of the path in metres.
+    length: f64,
+    /// The legs of the path in order, from home to the finish.
+    legs: Vec<Leg>,
+    /// The number of collection points.
+    collection_points: usize,
+    /// The time spent cruising in seconds.
+    travel_time: f64,
+    /// The time spent collecting samples in seconds.
+    sampling_time: f64,
+    /// The total time to run the path in seconds.
+    duration: f64,
+    /// The energy used in watt hours.
+    energy: f64,
+    /// The fraction of the battery used, above one if the boat cannot finish.
+    battery_used: f64,
+}
+
+impl PathStats {
+    /// Computes estimates for a path.
+    pub fn new(path: &PathData, options: &StatsOptions) -> Result<Self, String> {
+        options.validate()?;
+
+        log::info!("Measuring Legs");
+        // Including the legs from home to the start and from the end of the path to the finish
+        let line = path.path();
+        let first = path.home().map(|p| p.0).zip(line.0.first().copied());
+        let last = line.0.last().copied().zip(path.finish().map(|p| p.0));
+        let legs = first
+            .into_iter()
+            .map(|(start, end)| Line::new(start, end))
+            .chain(line.lines())
+            .chain(last.map(|(start, end)| Line::new(start, end)))
+            .map(|line| {
+                let (bearing, length) =
+                    Point::from(line.start).geodesic_bearing_distance(Point::from(line.end));
+                Leg {
+                    length,
+                    bearing: bearing.rem_euclid(360.0),
+                }
+            })
+            .collect::<Vec<_>>();
+        let length = legs.iter().map(|l| l.length).sum::<f64>();
+        log::debug!("Path Length: {}", length);
+
+        let collection_points = path.collection_points().0.len();
+        let travel_time = length / options.cruise_speed;
+        let sampling_time = path
+            .sampling()
+            .iter()
+            .map(|s| s.dwell_time().unwrap_or(options.dwell_time) * f64::from(s.repeat()))
+            .sum::<f64>();
+
+        log::info!("Estimating Energy");
+        let model = &options.energy_model;
+        let energy = (travel_time * model.cruise_power
+            + sampling_time * model.sample_power
+            + (travel_time + sampling_time) * model.idle_power)
+            / JOULES_PER_WATT_HOUR;
+        log::debug!("Energy: {} Wh", energy);
+
+        Ok(Self {
+            length,
+            legs,
+            collection_points,
+            travel_time,
+            sampling_time,
+            duration: travel_time + sampling_time,
+            energy,
+            battery_used: energy / model.battery_capacity,
+        })
+    }
+
+    /// Gets the total geodesic length of the path in metres.
+    pub fn length(&self) -> f64 {
+        self.length
+    }
+
+    /// Gets the legs of the path in order, from home to the finish.
+    pub fn legs(&self) -> &[Leg] {
+        &self.legs
+    }
+
+    /// Gets the number of collection points.
+    pub fn collection_points(&self) -> usize {
+        self.collection_points
+    }
+
+    /// Gets the total time to run the path in seconds.
+    pub fn duration(&self) -> f64 {
+        self.duration
+    }
+
+    /// Gets the energy used in watt hours.
+    pub fn energy(&self) -> f64 {
+        self.energy
+    }
+
+    /// Gets the fraction of the battery used.
+    pub fn battery_used(&self) -> f64 {
+        self.battery_used
+    }
+
+    /// Checks whether the battery lasts for the whole path.
+    pub fn is_feasible(&self) -> bool {
+        self.battery_used <= 1.0
+    }
+}
+
+/// Estimates the length, duration and energy use of a path.
+#[tauri::command]
+pub fn path_stats(path: PathData, options: StatsOptions) -> Result<PathStats, String> {
+    log::info!("Calculating Path Statistics");
+    PathStats::new(&path, &options)
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::{line_string, point, MultiPoint};
+
+    use super::*;
+    use crate::{data::Layer, path::sampling::SamplingInstructions};
+
+    /// Length of one degree of longitude along the equator in metres.
+    const EQUATOR_DEGREE: f64 = 111_319.490_793;
+
+    /// Length of the meridian arc from the equator to 1° north in metres.
+    const MERIDIAN_DEGREE: f64 = 110_574.388_558;
+
+    fn path() -> PathData {
+        PathData::new(
+            line_string![(x: 0.0, y: 0.0), (x: 1.0, y: 0.0), (x: 1.0, y: 1.0), (x: 1.0, y: 0.0)],
+            MultiPoint(vec![point!(x: 0.0, y: 0.0), point!(x: 1.0, y: 0.0)]),
+        )
+    }
+
+    #[test]
+    fn measures_legs() {
+        let options = StatsOptions::new(1.0, 0.0, EnergyModel::new(0.0, 0.0, 0.0, 1.0));
+        let stats = PathStats::new(&path(), &options).unwrap();
+
+        let legs = stats.legs();
+        assert_eq!(legs.len(), 3);
+        assert!((legs[0].length() - EQUATOR_DEGREE).abs() < 1e-3);
+        assert!((legs[0].bearing() - 90.0).abs() < 1e-9);
+        assert!((legs[1].length() - MERIDIAN_DEGREE).abs() < 1e-3);
+        assert!(legs[1].bearing().abs() < 1e-9);
+        assert!((legs[2].bearing() - 180.0).abs() < 1e-9);
+
+        let length = EQUATOR_DEGREE + 2.0 * MERIDIAN_DEGREE;
+        assert!((stats.length() - length).abs() < 1e-3);
+        assert_eq!(stats.collection_points(), 2);
+    }
+
+    #[test]
+    fn measures_legs_from_home_to_finish() {
+        let options = StatsOptions::new(1.0, 0.0, EnergyModel::new(0.0, 0.0, 0.0, 1.0));
+        let home = path().with_home(Some(point!(x: 0.0, y: 1.0)), None);
+        let stats = PathStats::new(&home, &options).unwrap();
+
+        let legs = stats.legs();
+        assert_eq!(legs.len(), 5);
+        assert!((legs[0].length() - MERIDIAN_DEGREE).abs() < 1e-3);
+        assert!((legs[0].bearing() - 180.0).abs() < 1e-9);
+        let back = legs[4];
+        assert!(back.length() > EQUATOR_DEGREE);
+        let length = EQUATOR_DEGREE + 3.0 * MERIDIAN_DEGREE + back.length();
+        assert!((stats.length() - length).abs() < 1e-3);
+        assert!((stats.duration() - length).abs() < 1e-3);
+
+        let end = path().with_home(Some(point!(x: 0.0, y: 0.0)), Some(point!(x: 1.0, y: 0.0)));
+        let stats = PathStats::new(&end, &options).unwrap();
+        assert_eq!(stats.legs().len(), 5);
+        assert!(stats.legs()[4].length().abs() < 1e-9);
+        let length = EQUATOR_DEGREE + 2.0 * MERIDIAN_DEGREE;
+        assert!((stats.length() - length).abs() < 1e-3);
+    }
+
+    #[test]
+    fn estimates_duration_and_energy() {
+        let path = PathData::new(
+            line_string![(x: 0.0, y: 0.0), (x: 1.0, y: 0.0)],
+            MultiPoint(vec![point!(x: 0.0, y: 0.0); 10]),
+        );
+        let model = EnergyModel::new(100.0, 20.0, 5.0, 5000.0);
+        let stats = PathStats::new(&path, &StatsOptions::new(2.0, 60.0, model)).unwrap();
+
+        let travel_time = EQUATOR_DEGREE / 2.0;
+        assert!((stats.duration() - (travel_time + 600.0)).abs() < 1e-3);
+        let energy = (travel_time * 105.0 + 600.0 * 25.0) / 3600.0;
+        assert!((stats.energy() - energy).abs() < 1e-6);
+        assert!((stats.battery_used() - energy / 5000.0).abs() < 1e-9);
+        assert!(stats.is_feasible());
+
+        let model = EnergyModel::new(100.0, 20.0, 5.0, 1.0);
+        let stats = PathStats::new(&path, &StatsOptions::new(2.0, 60.0, model)).unwrap();
+        assert!(!stats.is_feasible());
+    }
+
+    #[test]
+    fn uses_sampling_dwell_time() {
+        let path = path()
+            .with_sampling(vec![
+                SamplingInstructions::default(),
+                SamplingInstructions::new(vec![Layer::Surface], vec![], Some(30.0), 3, None)
+                    .unwrap(),
+            ])
+            .unwrap();
+        let options = StatsOptions::new(1.0, 60.0, EnergyModel::new(0.0, 0.0, 0.0, 1.0));
+        let stats = PathStats::new(&path, &options).unwrap();
+
+        assert!((stats.duration() - 2.0 * MERIDIAN_DEGREE - EQUATOR_DEGREE - 150.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        let model = EnergyModel::new(1.0, 1.0, 1.0, 1.0);
+        for options in [
+            StatsOptions::new(0.0, 1.0, model),
+            StatsOptions::new(1.0, -1.0, model),
+            StatsOptions::new(1.0, 1.0, EnergyModel::new(-1.0, 1.0, 1.0, 1.0)),
+            StatsOptions::new(1.0, 1.0, EnergyModel::new(1.0, 1.0, 1.0, 0.0)),
+        ] {
+            assert!(PathStats::new(&path(), &options).is_err());
+        }
+    }
+}
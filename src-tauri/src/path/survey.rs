@@ -0,0 +1,593 @@
+//! Lawnmower survey patterns covering a polygon.
+
+use geo::ConvexHull;
+use geo_types::{coord, Coord, LineString, MultiPoint, Point, Polygon};
+use serde::Deserialize;
+
+use super::{
+    local::LocalProjection,
+    obstacles::{grow, Obstacles},
+    PathData,
+};
+
+/// The most lanes a survey may have.
+const MAX_LANES: usize = 10_000;
+
+/// Lanes shorter than this in metres are ignored.
+const MIN_SEGMENT_LENGTH: f64 = 1e-3;
+
+/// Options for generating a lawnmower survey.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct SurveyOptions {
+    /// The distance between neighbouring lanes in metres.
+    lane_spacing: f64,
+    /// The direction of the lanes in degrees clockwise from north.
+    ///
+    /// The direction needing the fewest turns is used if this is not given.
+    heading: Option<f64>,
+    /// The distance between collection points along a lane in metres.
+    point_spacing: f64,
+}
+
+impl SurveyOptions {
+    /// Creates new survey options.
+    pub fn new(lane_spacing: f64, heading: Option<f64>, point_spacing: f64) -> Self {
+        Self {
+            lane_spacing,
+            heading,
+            point_spacing,
+        }
+    }
+
+    /// Checks that the options describe a survey that can be generated.
+    fn validate(&self) -> Result<(), String> {
+        if !(self.lane_spacing.is_finite() && self.lane_spacing > 0.0) {
+            return Err(String::from("Lane Spacing must be Positive"));
+        }
+        if !(self.point_spacing.is_finite() && self.point_spacing > 0.0) {
+            return Err(String::from("Collection Point Spacing must be Positive"));
+        }
+        if matches!(self.heading, Some(v) if !v.is_finite()) {
+            return Err(String::from("Invalid Heading"));
+        }
+        Ok(())
+    }
+}
+
+/// Coordinates rotated so the lanes run along the x axis.
+///
+/// `x` is the distance along the lanes and `y` the distance across them, both in metres.
+#[derive(Debug, Clone, Copy)]
+struct LaneFrame {
+    /// Projection of the survey area into metres.
+    projection: LocalProjection,
+    /// Sine of the heading of the lanes.
+    sin: f64,
+    /// Cosine of the heading of the lanes.
+    cos: f64,
+}
+
+impl LaneFrame {
+    /// Creates a new frame for lanes in the direction of a heading in degrees.
+    fn new(projection: LocalProjection, heading: f64) -> Self {
+        let (sin, cos) = heading.to_radians().sin_cos();
+        Self {
+            projection,
+            sin,
+            cos,
+        }
+    }
+
+    /// Rotates a coordinate in metres east and north into the frame.
+    fn rotate(&self, c: Coord<f64>) -> Coord<f64> {
+        coord! {
+            x: c.x * self.sin + c.y * self.cos,
+            y: -c.x * self.cos + c.y * self.sin,
+        }
+    }
+
+    /// Converts a coordinate of the frame back into a longitude and latitude.
+    fn unproject(&self, c: Coord<f64>) -> Coord<f64> {
+        self.projection.unproject(coord! {
+            x: c.x * self.sin - c.y * self.cos,
+            y: c.x * self.cos + c.y * self.sin,
+        })
+    }
+}
+
+/// The part of a lane inside the survey area.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Segment {
+    /// Position of the lane across the frame.
+    y: f64,
+    /// Where the segment starts along the lane.
+    start: f64,
+    /// Where the segment ends along the lane.
+    end: f64,
+}
+
+impl Segment {
+    /// Checks if two segments on neighbouring lanes overlap along the lanes.
+    fn overlaps(&self, other: &Segment) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+
+    /// Gets an end of the segment.
+    fn point(&self, at_end: bool) -> Coord<f64> {
+        coord! { x: if at_end { self.end } else { self.start }, y: self.y }
+    }
+}
+
+/// Cuts lanes across the rings of a polygon in the lane frame.
+///
+/// Returns the segments of each lane inside the exterior ring and outside the holes.
+fn sweep(rings: &[Vec<Coord<f64>>], spacing: f64) -> Result<Vec<Vec<Segment>>, String> {
+    let (min, max) = rings[0]
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), c| {
+            (min.min(c.y), max.max(c.y))
+        });
+    let width = max - min;
+    if width.is_nan() || width <= 0.0 {
+        return Err(String::from("Survey Area is Empty"));
+    }
+
+    // Each lane covers half the spacing on either side, and the lanes are centred so both sides
+    // of the area are left with the same margin. Rounding errors from projecting the area should
+    // not add a lane.
+    let count = (width / spacing - 1e-6).ceil().max(1.0);
+    if count > MAX_LANES as f64 {
+        return Err(String::from(
+            "Lane Spacing is too Small for the Survey Area",
+        ));
+    }
+    let count = count as usize;
+    let first = min + (width - (count - 1) as f64 * spacing) / 2.0;
+
+    Ok((0..count)
+        .map(|i| {
+            let y = first + i as f64 * spacing;
+            let mut crossings: Vec<f64> = rings
+                .iter()
+                .flat_map(|ring| ring.windows(2))
+                .filter(|edge| (edge[0].y > y) != (edge[1].y > y))
+                .map(|edge| {
+                    let (a, b) = (edge[0], edge[1]);
+                    a.x + (y - a.y) * (b.x - a.x) / (b.y - a.y)
+                })
+                .collect();
+            crossings.sort_by(f64::total_cmp);
+            crossings
+                .chunks_exact(2)
+                .map(|v| Segment {
+                    y,
+                    start: v[0],
+                    end: v[1],
+                })
+                .filter(|v| v.end - v.start > MIN_SEGMENT_LENGTH)
+                .collect()
+        })
+        .collect())
+}
+
+/// Groups lane segments into cells.
+///
+/// A cell holds one segment on each of a run of neighbouring lanes, so it can be covered back and
+/// forth without crossing the boundary of the area.
+fn decompose(lanes: Vec<Vec<Segment>>) -> Vec<Vec<Segment>> {
+    let mut cells: Vec<Vec<Segment>> = vec![];
+    let mut open: Vec<usize> = vec![];
+
+    for lane in lanes {
+        let mut next_open = Vec::with_capacity(lane.len());
+        for segment in &lane {
+            let overlapping: Vec<usize> = open
+                .iter()
+                .copied()
+                .filter(|&i| cells[i].last().unwrap().overlaps(segment))
+                .collect();
+
+            // Only continue a cell when the segments pair up one to one
+            let continues = match overlapping[..] {
+                [i] => {
+                    lane.iter()
+                        .filter(|v| cells[i].last().unwrap().overlaps(v))
+                        .count()
+                        == 1
+                }
+                _ => false,
+            };
+            if continues {
+                cells[overlapping[0]].push(*segment);
+                next_open.push(overlapping[0]);
+            } else {
+                cells.push(vec![*segment]);
+                next_open.push(cells.len() - 1);
+            }
+        }
+        open = next_open;
+    }
+    cells
+}
+
+/// Signed distance of a point from the line through two other points.
+fn side(a: Coord<f64>, b: Coord<f64>, p: Coord<f64>) -> f64 {
+    let d = b - a;
+    (d.x * (p.y - a.y) - d.y * (p.x - a.x)) / d.x.hypot(d.y)
+}
+
+/// Checks if the line between two points crosses an edge of any ring.
+///
+/// Touching an edge, such as starting on the boundary, does not count as crossing.
+fn crosses(a: Coord<f64>, b: Coord<f64>, rings: &[Vec<Coord<f64>>]) -> bool {
+    const TOLERANCE: f64 = 1e-6;
+    let strictly_opposite =
+        |v: f64, w: f64| v.abs() > TOLERANCE && w.abs() > TOLERANCE && v.signum() != w.signum();
+
+    if a == b {
+        return false;
+    }
+    rings
+        .iter()
+        .flat_map(|ring| ring.windows(2))
+        .filter(|edge| edge[0] != edge[1])
+        .any(|edge| {
+            strictly_opposite(side(edge[0], edge[1], a), side(edge[0], edge[1], b))
+                && strictly_opposite(side(a, b, edge[0]), side(a, b, edge[1]))
+        })
+}
+
+/// A lane and the turns taken to reach it from the previous lane.
+struct Lane {
+    /// The corners of the holes passed on the way to the lane.
+    detour: Vec<Coord<f64>>,
+    /// Where the lane starts.
+    start: Coord<f64>,
+    /// Where the lane ends.
+    end: Coord<f64>,
+}
+
+/// Orders the lanes of every cell into a single back and forth route.
+///
+/// Starting from the first lane, the cell nearest around the holes is visited next, preferring
+/// transits that stay inside the exterior. Returns the lanes in the order they are driven.
+fn route(cells: Vec<Vec<Segment>>, rings: &[Vec<Coord<f64>>]) -> Result<Vec<Lane>, String> {
+    let holes = Obstacles::new(
+        rings[1..]
+            .iter()
+            .map(|ring| grow(&Polygon::new(LineString(ring.clone()), vec![]), 0.0))
+            .collect(),
+    );
+    let mut remaining = cells;
+    let mut lanes: Vec<Lane> = vec![];
+
+    while !remaining.is_empty() {
+        // Entering a cell from either end of its first or last lane
+        let (index, reverse, at_end, detour) = match lanes.last() {
+            None => (0, false, false, vec![]),
+            Some(lane) => {
+                let p = lane.end;
+                let mut best = None;
+                let mut best_cost = (true, f64::INFINITY);
+                for (i, cell) in remaining.iter().enumerate() {
+                    for reverse in [false, true] {
+                        let segment = if reverse { cell.last() } else { cell.first() }.unwrap();
+                        for at_end in [false, true] {
+                            let entry = segment.point(at_end);
+                            let detour = match holes.route(p, entry) {
+                                Some(v) => v,
+                                None => continue,
+                            };
+                            let transit = std::iter::once(p)
+                                .chain(detour.iter().copied())
+                                .chain(std::iter::once(entry))
+                                .collect::<Vec<_>>();
+                            let length = transit
+                                .windows(2)
+                                .map(|w| (w[1] - w[0]).x.hypot((w[1] - w[0]).y))
+                                .sum::<f64>();
+                            let outside =
+                                transit.windows(2).any(|w| crosses(w[0], w[1], &rings[..1]));
+                            if (outside, length) < best_cost {
+                                best_cost = (outside, length);
+                                best = Some((i, reverse, at_end, detour));
+                            }
+                        }
+                    }
+                }
+                best.ok_or(String::from(
+                    "Unable to Route around the Holes of the Survey Area",
+                ))?
+            }
+        };
+
+        let mut cell = remaining.remove(index);
+        if reverse {
+            cell.reverse();
+        }
+        let mut at_end = at_end;
+        let mut detour = detour;
+        for segment in cell {
+            lanes.push(Lane {
+                detour: std::mem::take(&mut detour),
+                start: segment.point(at_end),
+                end: segment.point(!at_end),
+            });
+            at_end = !at_end;
+        }
+    }
+    Ok(lanes)
+}
+
+/// Places collection points along a lane, at both ends and every `spacing` metres in between.
+fn collection_points(start: Coord<f64>, end: Coord<f64>, spacing: f64) -> Vec<Coord<f64>> {
+    let delta = end - start;
+    let length = delta.x.hypot(delta.y);
+    let count = (length / spacing).floor() as usize;
+
+    let mut points: Vec<Coord<f64>> = (0..=count)
+        .map(|i| start + delta * (i as f64 * spacing / length))
+        .collect();
+    if length - count as f64 * spacing > MIN_SEGMENT_LENGTH {
+        points.push(end);
+    }
+    points
+}
+
+/// Lanes and the number of turns needed to cover an area with a heading.
+struct Sweep {
+    /// The heading of the lanes.
+    frame: LaneFrame,
+    /// The rings of the area in the lane frame.
+    rings: Vec<Vec<Coord<f64>>>,
+    /// The lane segments inside the area.
+    lanes: Vec<Vec<Segment>>,
+    /// The number of lane segments driven.
+    segments: usize,
+}
+
+impl Sweep {
+    /// Cuts lanes across an area with a heading.
+    fn new(
+        area: &[Vec<Coord<f64>>],
+        projection: LocalProjection,
+        heading: f64,
+        spacing: f64,
+    ) -> Result<Self, String> {
+        let frame = LaneFrame::new(projection, heading);
+        let rings: Vec<Vec<Coord<f64>>> = area
+            .iter()
+            .map(|ring| ring.iter().map(|&c| frame.rotate(c)).collect())
+            .collect();
+        let lanes = sweep(&rings, spacing)?;
+        let segments = lanes.iter().map(Vec::len).sum();
+        Ok(Self {
+            frame,
+            rings,
+            lanes,
+            segments,
+        })
+    }
+}
+
+/// Candidate headings for the lanes of an area in metres.
+///
+/// The fewest lanes are needed when they run parallel to an edge of the convex hull.
+fn candidate_headings(exterior: &[Coord<f64>]) -> Vec<f64> {
+    let hull = LineString::from(exterior.to_vec()).convex_hull();
+    let mut headings: Vec<f64> = hull
+        .exterior()
+        .lines()
+        .map(|line| {
+            let delta = line.delta();
+            (delta.x.atan2(delta.y).to_degrees().rem_euclid(180.0) * 10.0).round() / 10.0
+        })
+        .collect();
+    headings.extend([0.0, 90.0]);
+    headings.sort_by(f64::total_cmp);
+    headings.dedup();
+    headings
+}
+
+/// Generates a lawnmower survey covering a polygon.
+///
+/// Lanes are spaced evenly across the polygon, skipping over its holes, and driven back and
+/// forth. Collection points are placed along each lane.
+pub fn lawnmower(area: &Polygon<f64>, options: &SurveyOptions) -> Result<PathData, String> {
+    options.validate()?;
+
+    let projection = LocalProjection::around(area.exterior().coords().copied())
+        .ok_or(String::from("Survey Area is Empty"))?;
+    let rings: Vec<Vec<Coord<f64>>> = std::iter::once(area.exterior())
+        .chain(area.interiors())
+        .map(|ring| ring.coords().map(|&c| projection.project(c)).collect())
+        .collect();
+
+    log::info!("Sweeping Lanes");
+    let sweep = match options.heading {
+        Some(heading) => Sweep::new(&rings, projection, heading, options.lane_spacing)?,
+        None => {
+            // Headings that cannot cover the area are skipped, failing only if none can
+            let mut error = String::from("Survey Area is Empty");
+            let sweeps = candidate_headings(&rings[0])
+                .into_iter()
+                .filter_map(|heading| {
+                    match Sweep::new(&rings, projection, heading, options.lane_spacing) {
+                        Ok(v) => Some(v),
+                        Err(e) => {
+                            log::debug!("Skipping Heading {}: {}", heading, e);
+                            error = e;
+                            None
+                        }
+                    }
+                })
+                .collect::<Vec<_>>();
+            sweeps.into_iter().min_by_key(|v| v.segments).ok_or(error)?
+        }
+    };
+    log::debug!("Lane Segments: {}", sweep.segments);
+    if sweep.segments == 0 {
+        return Err(String::from("Survey Area is Empty"));
+    }
+
+    log::info!("Routing Lanes");
+    let lanes = route(decompose(sweep.lanes), &sweep.rings)?;
+
+    let frame = sweep.frame;
+    let path: LineString<f64> = lanes
+        .iter()
+        .flat_map(|lane| lane.detour.iter().copied().chain([lane.start, lane.end]))
+        .map(|c| frame.unproject(c))
+        .collect();
+    let points: MultiPoint<f64> = lanes
+        .iter()
+        .flat_map(|lane| collection_points(lane.start, lane.end, options.point_spacing))
+        .map(|c| Point::from(frame.unproject(c)))
+        .collect();
+    log::debug!("Generated Path: {:?}", path);
+
+    Ok(PathData::new(path, points))
+}
+
+/// Generate a lawnmower survey path covering a polygon.
+#[tauri::command]
+pub fn generate_survey(
+    area: geojson::Geometry,
+    options: SurveyOptions,
+) -> Result<PathData, String> {
+    log::debug!("Generating Survey over: {}", area);
+    let area = Polygon::try_from(area.value).map_err(|e| e.to_string())?;
+    lawnmower(&area, &options)
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::Contains;
+
+    use super::*;
+    use crate::path::local::lake;
+
+    /// Builds a polygon from rings in metres around a point on a lake.
+    fn polygon(
+        exterior: &[(f64, f64)],
+        interiors: &[&[(f64, f64)]],
+    ) -> (Polygon<f64>, LocalProjection) {
+        let projection = lake();
+        let ring = |ring: &[(f64, f64)]| -> LineString<f64> {
+            ring.iter()
+                .map(|&(x, y)| projection.unproject(coord! { x: x, y: y }))
+                .collect()
+        };
+        let polygon = Polygon::new(ring(exterior), interiors.iter().map(|v| ring(v)).collect());
+        (polygon, projection)
+    }
+
+    const RECTANGLE: [(f64, f64); 4] = [(0.0, 0.0), (200.0, 0.0), (200.0, 100.0), (0.0, 100.0)];
+
+    #[test]
+    fn covers_rectangle_with_given_heading() {
+        let (area, projection) = polygon(&RECTANGLE, &[]);
+        let data = lawnmower(&area, &SurveyOptions::new(20.0, Some(90.0), 50.0)).unwrap();
+
+        // Five east-west lanes, each with a point every 50 metres
+        assert_eq!(data.path().0.len(), 10);
+        assert_eq!(data.collection_points().0.len(), 25);
+
+        let path: Vec<Coord<f64>> = data
+            .path()
+            .coords()
+            .map(|&c| projection.project(c))
+            .collect();
+        for (i, lane) in path.chunks(2).enumerate() {
+            assert!((lane[0].y - (10.0 + 20.0 * i as f64)).abs() < 0.01);
+            assert!((lane[0].y - lane[1].y).abs() < 0.01);
+            assert!(((lane[1].x - lane[0].x).abs() - 200.0).abs() < 0.01);
+        }
+        // Lanes alternate direction
+        assert!(path[1].x > path[0].x);
+        assert!(path[3].x < path[2].x);
+    }
+
+    #[test]
+    fn chooses_heading_with_fewest_turns() {
+        let (area, _) = polygon(&RECTANGLE, &[]);
+        let data = lawnmower(&area, &SurveyOptions::new(20.0, None, 50.0)).unwrap();
+        assert_eq!(data.path().0.len(), 10);
+    }
+
+    #[test]
+    fn avoids_holes() {
+        let hole = [(80.0, 30.0), (120.0, 30.0), (120.0, 70.0), (80.0, 70.0)];
+        let (area, projection) = polygon(&RECTANGLE, &[&hole]);
+        let data = lawnmower(&area, &SurveyOptions::new(10.0, Some(90.0), 10.0)).unwrap();
+
+        let hole_polygon = polygon(&hole, &[]).0;
+        assert!(data
+            .collection_points()
+            .iter()
+            .all(|p| !hole_polygon.contains(p)));
+
+        let hole: Vec<Coord<f64>> = hole_polygon
+            .exterior()
+            .coords()
+            .map(|&c| projection.project(c))
+            .collect();
+        let path: Vec<Coord<f64>> = data
+            .path()
+            .coords()
+            .map(|&c| projection.project(c))
+            .collect();
+        assert!(path
+            .windows(2)
+            .all(|v| !crosses(v[0], v[1], std::slice::from_ref(&hole))));
+
+        // Ten lanes, four of them split by the hole
+        assert_eq!(data.path().0.len(), 2 * 14);
+    }
+
+    #[test]
+    fn goes_around_holes_between_cells() {
+        // Every lane is split by the hole, so the cells on either side are only joined around it
+        let hole = [(40.0, 2.0), (160.0, 2.0), (160.0, 98.0), (40.0, 98.0)];
+        let (area, projection) = polygon(&RECTANGLE, &[&hole]);
+        let data = lawnmower(&area, &SurveyOptions::new(20.0, Some(90.0), 10.0)).unwrap();
+
+        let path: Vec<Coord<f64>> = data
+            .path()
+            .coords()
+            .map(|&c| projection.project(c))
+            .collect();
+        let hole: Vec<Coord<f64>> = polygon(&hole, &[])
+            .0
+            .exterior()
+            .coords()
+            .map(|&c| projection.project(c))
+            .collect();
+        assert!(path
+            .windows(2)
+            .all(|v| !crosses(v[0], v[1], std::slice::from_ref(&hole))));
+        // Ten lanes and the two corners of the hole passed between the cells
+        assert_eq!(path.len(), 22);
+        assert_eq!(data.collection_points().0.len(), 50);
+    }
+
+    #[test]
+    fn skips_headings_that_cannot_cover_the_area() {
+        // Too many lanes across the long side, but not across the short side
+        let (area, _) = polygon(
+            &[(0.0, 0.0), (50_000.0, 0.0), (50_000.0, 5.0), (0.0, 5.0)],
+            &[],
+        );
+        let data = lawnmower(&area, &SurveyOptions::new(1.0, None, 1000.0)).unwrap();
+        assert_eq!(data.path().0.len(), 10);
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        let (area, _) = polygon(&RECTANGLE, &[]);
+        assert!(lawnmower(&area, &SurveyOptions::new(0.0, None, 10.0)).is_err());
+        assert!(lawnmower(&area, &SurveyOptions::new(10.0, None, -1.0)).is_err());
+        assert!(lawnmower(&area, &SurveyOptions::new(10.0, Some(f64::NAN), 10.0)).is_err());
+        assert!(lawnmower(&area, &SurveyOptions::new(0.001, None, 10.0)).is_err());
+    }
+}
@@ -0,0 +1,347 @@
+//! Moving a whole path to repeat it somewhere else or in the other direction.
+//!
+//! Every transform is applied to both the path and its collection points. The home and end
+//! points are where the boat is launched and recovered so they are left where they are.
+
+use geo::GeodesicDestination;
+use geo_types::{coord, Coord, LineString, MultiPoint, Point};
+use serde::Deserialize;
+
+use super::{local::LocalProjection, PathData};
+
+/// The longest a corner may be moved by an offset, as a multiple of the offset distance.
+const MITER_LIMIT: f64 = 4.0;
+
+/// Segments shorter than this in metres have no direction.
+const TOLERANCE: f64 = 1e-6;
+
+/// A way of moving a whole path.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Transform {
+    /// Follow the path and visit the collection points in the opposite direction.
+    Reverse,
+    /// Move the path sideways, keeping it parallel to the original.
+    Offset {
+        /// The distance in metres to the right of the direction of travel, negative for left.
+        distance: f64,
+    },
+    /// Turn the path around a pivot.
+    Rotate {
+        /// The angle in degrees clockwise.
+        angle: f64,
+        /// The longitude and latitude to turn around, the centre of the path if missing.
+        pivot: Option<(f64, f64)>,
+    },
+    /// Move the path in a direction.
+    Translate {
+        /// The direction in degrees clockwise from north.
+        bearing: f64,
+        /// The distance in metres.
+        distance: f64,
+    },
+}
+
+/// Applies a function to every coordinate of the path and the collection points.
+fn map_coords<F: Fn(Coord<f64>) -> Coord<f64>>(path: &PathData, f: F) -> PathData {
+    PathData {
+        path: path.path.0.iter().map(|&c| f(c)).collect(),
+        collection_points: path.collection_points.iter().map(|p| f(p.0)).collect(),
+        ..path.clone()
+    }
+}
+
+/// Gets the unit vector to the right of a segment in metres, if it has a direction.
+fn right_normal(start: Coord<f64>, end: Coord<f64>) -> Option<Coord<f64>> {
+    let d = end - start;
+    let length = d.x.hypot(d.y);
+    (length > TOLERANCE).then(|| coord! { x: d.y / length, y: -d.x / length })
+}
+
+/// Moves each vertex of a projected line sideways, joining the segments at mitred corners.
+fn offset_line(line: &[Coord<f64>], distance: f64) -> Result<Vec<Coord<f64>>, String> {
+    let normals = line
+        .windows(2)
+        .map(|w| right_normal(w[0], w[1]))
+        .collect::<Vec<_>>();
+    if normals.iter().all(Option::is_none) {
+        return Err(String::from("Offset requires a Path with Length"));
+    }
+
+    Ok(line
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| {
+            // Using the nearest segments with a direction on either side of the vertex
+            let before = normals[..i].iter().rev().find_map(|n| *n);
+            let after = normals[i.min(normals.len())..].iter().find_map(|n| *n);
+            let shift = match (before, after) {
+                (Some(a), Some(b)) => {
+                    let cos = (a.x * b.x + a.y * b.y).max(2.0 / MITER_LIMIT.powi(2) - 1.0);
+                    (a + b) / (1.0 + cos)
+                }
+                (Some(n), None) | (None, Some(n)) => n,
+                (None, None) => unreachable!("the line has a segment with a direction"),
+            };
+            c + shift * distance
+        })
+        .collect())
+}
+
+/// Moves a path sideways by a distance in metres.
+///
+/// Collection points move with the nearest segment of the path so they stay the same distance
+/// from it.
+fn offset(path: &PathData, distance: f64) -> Result<PathData, String> {
+    let projection = LocalProjection::around(path.path.0.iter().copied())
+        .ok_or(String::from("Offset requires a Path with Length"))?;
+    let line = path
+        .path
+        .0
+        .iter()
+        .map(|&c| projection.project(c))
+        .collect::<Vec<_>>();
+    let moved = offset_line(&line, distance)?;
+
+    let points = path.collection_points.iter().map(|p| {
+        let c = projection.project(p.0);
+        let nearest = line
+            .windows(2)
+            .filter_map(|w| Some((w, right_normal(w[0], w[1])?)))
+            .map(|(w, normal)| {
+                let d = w[1] - w[0];
+                let t = (((c - w[0]).x * d.x + (c - w[0]).y * d.y) / (d.x * d.x + d.y * d.y))
+                    .clamp(0.0, 1.0);
+                let closest = w[0] + d * t;
+                ((c - closest).x.hypot((c - closest).y), normal)
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+        // The path has a segment with a direction as it was offset above
+        let normal = nearest.map_or(coord! { x: 0.0, y: 0.0 }, |(_, n)| n);
+        Point::from(projection.unproject(c + normal * distance))
+    });
+
+    Ok(PathData {
+        path: moved.into_iter().map(|c| projection.unproject(c)).collect(),
+        collection_points: points.collect(),
+        ..path.clone()
+    })
+}
+
+/// Turns a path clockwise by an angle in degrees around a pivot.
+fn rotate(path: &PathData, angle: f64, pivot: Option<Coord<f64>>) -> PathData {
+    let pivot = pivot.or_else(|| {
+        LocalProjection::around(
+            path.path
+                .0
+                .iter()
+                .copied()
+                .chain(path.collection_points.iter().map(|p| p.0)),
+        )
+        .map(|p| p.unproject(coord! { x: 0.0, y: 0.0 }))
+    });
+    let projection = match pivot {
+        Some(v) => LocalProjection::new(v),
+        None => return path.clone(),
+    };
+    let (sin, cos) = angle.to_radians().sin_cos();
+    map_coords(path, |c| {
+        let c = projection.project(c);
+        projection.unproject(coord! { x: c.x * cos + c.y * sin, y: c.y * cos - c.x * sin })
+    })
+}
+
+/// Moves a whole path, keeping its sampling instructions with their collection points.
+pub fn transform(path: &PathData, transform: &Transform) -> Result<PathData, String> {
+    log::debug!("Transforming Path with {:?}", transform);
+    match *transform {
+        Transform::Reverse => {
+            let mut sampling = path.sampling.clone();
+            sampling.reverse();
+            Ok(PathData {
+                path: path
+                    .path
+                    .0
+                    .iter()
+                    .rev()
+                    .copied()
+                    .collect::<LineString<f64>>(),
+                collection_points: path
+                    .collection_points
+                    .0
+                    .iter()
+                    .rev()
+                    .copied()
+                    .collect::<MultiPoint<f64>>(),
+                sampling,
+                ..path.clone()
+            })
+        }
+        Transform::Offset { distance } => {
+            if !distance.is_finite() {
+                return Err(String::from("Offset Distance must be Finite"));
+            }
+            offset(path, distance)
+        }
+        Transform::Rotate { angle, pivot } => {
+            if !angle.is_finite() {
+                return Err(String::from("Rotation Angle must be Finite"));
+            }
+            let pivot = pivot.map(|(x, y)| coord! { x: x, y: y });
+            if pivot.is_some_and(|p| {
+                !((-180.0..=180.0).contains(&p.x) && (-90.0..=90.0).contains(&p.y))
+            }) {
+                return Err(String::from("Invalid Rotation Pivot"));
+            }
+            Ok(rotate(path, angle, pivot))
+        }
+        Transform::Translate { bearing, distance } => {
+            if !(bearing.is_finite() && distance.is_finite()) {
+                return Err(String::from("Translation must be Finite"));
+            }
+            Ok(map_coords(path, |c| {
+                Point::from(c).geodesic_destination(bearing, distance).0
+            }))
+        }
+    }
+}
+
+/// Preview moving, rotating or reversing a whole path, including its collection points.
+#[tauri::command]
+pub fn transform_path(path: PathData, transform: Transform) -> Result<PathData, String> {
+    log::info!("Transforming Path");
+    self::transform(&path, &transform)
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::GeodesicDistance;
+    use geo_types::point;
+
+    use super::{
+        super::{local::lake_path, sampling::SamplingInstructions},
+        *,
+    };
+
+    fn assert_near(projection: &LocalProjection, actual: Coord<f64>, expected: (f64, f64)) {
+        let actual = projection.project(actual);
+        assert!(
+            (actual.x - expected.0).abs() < 0.05 && (actual.y - expected.1).abs() < 0.05,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn reverses_paths() {
+        let (original, _) = lake_path(
+            &[(0.0, 0.0), (100.0, 0.0), (100.0, 50.0)],
+            &[(0.0, 0.0), (100.0, 0.0)],
+        );
+        let sampling = vec![
+            Default::default(),
+            SamplingInstructions::new(vec![], vec![2.0], None, 1, None).unwrap(),
+        ];
+        let original = original.with_sampling(sampling).unwrap();
+
+        let reversed = transform(&original, &Transform::Reverse).unwrap();
+        assert_eq!(reversed.path().0[0], original.path().0[2]);
+        assert_eq!(
+            reversed.collection_points().0[0],
+            original.collection_points().0[1]
+        );
+        assert_eq!(reversed.sampling()[0], original.sampling()[1]);
+        assert_eq!(transform(&reversed, &Transform::Reverse).unwrap(), original);
+    }
+
+    #[test]
+    fn offsets_parallel_paths() {
+        let (original, projection) = lake_path(
+            &[
+                (0.0, 0.0),
+                (100.0, 0.0),
+                (100.0, 100.0),
+                (100.0, 100.0),
+                (0.0, 100.0),
+            ],
+            &[(50.0, 2.0), (98.0, 60.0)],
+        );
+        let moved = transform(&original, &Transform::Offset { distance: 10.0 }).unwrap();
+
+        // Right of travelling east is south, and the corners are mitred
+        let expected = [
+            (0.0, -10.0),
+            (110.0, -10.0),
+            (110.0, 110.0),
+            (110.0, 110.0),
+            (0.0, 110.0),
+        ];
+        for (&c, e) in moved.path().0.iter().zip(expected) {
+            assert_near(&projection, c, e);
+        }
+        assert_near(&projection, moved.collection_points().0[0].0, (50.0, -8.0));
+        assert_near(&projection, moved.collection_points().0[1].0, (108.0, 60.0));
+
+        let back = transform(&moved, &Transform::Offset { distance: -10.0 }).unwrap();
+        for (&c, e) in back.path().0.iter().zip(original.path().0.iter()) {
+            assert!(Point::from(c).geodesic_distance(&Point::from(*e)) < 0.05);
+        }
+
+        let (still, _) = lake_path(&[(0.0, 0.0), (0.0, 0.0)], &[]);
+        assert!(transform(&still, &Transform::Offset { distance: 10.0 }).is_err());
+        assert!(transform(&original, &Transform::Offset { distance: f64::NAN }).is_err());
+    }
+
+    #[test]
+    fn rotates_around_pivots() {
+        let (original, projection) = lake_path(&[(-50.0, 0.0), (50.0, 0.0)], &[(50.0, 0.0)]);
+        let rotated = transform(
+            &original,
+            &Transform::Rotate {
+                angle: 90.0,
+                pivot: None,
+            },
+        )
+        .unwrap();
+        assert_near(&projection, rotated.path().0[0], (0.0, 50.0));
+        assert_near(&projection, rotated.path().0[1], (0.0, -50.0));
+        assert_near(
+            &projection,
+            rotated.collection_points().0[0].0,
+            (0.0, -50.0),
+        );
+
+        let pivot = projection.unproject(coord! { x: -50.0, y: 0.0 });
+        let rotated = transform(
+            &original,
+            &Transform::Rotate {
+                angle: -90.0,
+                pivot: Some((pivot.x, pivot.y)),
+            },
+        )
+        .unwrap();
+        assert_near(&projection, rotated.path().0[0], (-50.0, 0.0));
+        assert_near(&projection, rotated.path().0[1], (-50.0, 100.0));
+    }
+
+    #[test]
+    fn translates_paths() {
+        let home = point!(x: 101.69, y: 3.1);
+        let (original, projection) = lake_path(&[(0.0, 0.0), (100.0, 0.0)], &[(100.0, 0.0)]);
+        let original = original.with_home(Some(home), None);
+        let moved = transform(
+            &original,
+            &Transform::Translate {
+                bearing: 0.0,
+                distance: 25.0,
+            },
+        )
+        .unwrap();
+
+        assert_near(&projection, moved.path().0[0], (0.0, 25.0));
+        assert_near(&projection, moved.path().0[1], (100.0, 25.0));
+        assert_near(&projection, moved.collection_points().0[0].0, (100.0, 25.0));
+        assert_eq!(moved.home(), Some(home));
+    }
+}
@@ -0,0 +1,549 @@
+//! Checking a path before it is sent to the boat.
+
+use geo::{EuclideanDistance, GeodesicDistance, Intersects};
+use geo_types::{Coord, Line, LineString, Point};
+use serde::{Deserialize, Serialize};
+
+use super::{local::LocalProjection, sampling::SamplingInstructions, PathData};
+
+/// How serious a problem with a path is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// The path can be run but may not do what was intended.
+    Warning,
+    /// The path cannot be run.
+    Error,
+}
+
+/// The kinds of problems found with a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    /// The path has no vertices.
+    EmptyPath,
+    /// The path has only one vertex.
+    SingleVertex,
+    /// A coordinate is not a valid longitude and latitude.
+    InvalidCoordinate,
+    /// The path crosses itself.
+    SelfIntersection,
+    /// A collection point is far from the path.
+    FarCollectionPoint,
+    /// A leg is longer than the range of the radio.
+    LongLeg,
+    /// Two vertices or collection points are in the same place.
+    DuplicatePoint,
+    /// A coordinate is outside of the loaded map.
+    OutsideMap,
+    /// The path has no home point for the boat to return to.
+    MissingHome,
+    /// The path goes too far from the home point.
+    FarFromHome,
+    /// The home or end point cannot be sent to the boat with the current communication protocol.
+    HomeNotSent,
+    /// Sampling instructions other than the default cannot be sent to the boat with the current
+    /// communication protocol.
+    SamplingNotSent,
+}
+
+/// A problem found with a path.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Issue {
+    /// How serious the problem is.
+    severity: Severity,
+    /// The kind of problem.
+    kind: IssueKind,
+    /// A description of the problem shown to the user.
+    message: String,
+    /// The longitude and latitude of the problem, if it has one.
+    coordinate: Option<(f64, f64)>,
+}
+
+impl Issue {
+    /// Creates a new issue at a coordinate.
+    fn new(
+        severity: Severity,
+        kind: IssueKind,
+        message: String,
+        coordinate: Option<Coord<f64>>,
+    ) -> Self {
+        Self {
+            severity,
+            kind,
+            message,
+            coordinate: coordinate.map(|c| c.x_y()),
+        }
+    }
+
+    /// Gets how serious the problem is.
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// Gets the kind of problem.
+    pub fn kind(&self) -> IssueKind {
+        self.kind
+    }
+
+    /// Gets a description of the problem.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// Limits used when checking a path.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct ValidationOptions {
+    /// The furthest a collection point may be from the path in metres.
+    max_point_distance: f64,
+    /// The range of the radio in metres.
+    radio_range: f64,
+    /// Points closer than this in metres are duplicates.
+    duplicate_tolerance: f64,
+    /// The furthest the path may go from the home point in metres.
+    max_home_distance: f64,
+    /// The bounds of the loaded map as west, south, east and north.
+    bounds: Option<(f64, f64, f64, f64)>,
+}
+
+impl ValidationOptions {
+    /// Creates new options.
+    pub fn new(
+        max_point_distance: f64,
+        radio_range: f64,
+        duplicate_tolerance: f64,
+        max_home_distance: f64,
+        bounds: Option<(f64, f64, f64, f64)>,
+    ) -> Self {
+        Self {
+            max_point_distance,
+            radio_range,
+            duplicate_tolerance,
+            max_home_distance,
+            bounds,
+        }
+    }
+
+    /// Checks whether a coordinate is inside the bounds of the map.
+    fn in_bounds(&self, c: Coord<f64>) -> bool {
+        match self.bounds {
+            Some((west, south, east, north)) => {
+                (west..=east).contains(&c.x) && (south..=north).contains(&c.y)
+            }
+            None => true,
+        }
+    }
+}
+
+impl Default for ValidationOptions {
+    fn default() -> Self {
+        Self {
+            max_point_distance: 50.0,
+            radio_range: 1000.0,
+            duplicate_tolerance: 0.5,
+            max_home_distance: 2000.0,
+            bounds: None,
+        }
+    }
+}
+
+/// The problems found with a path.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ValidationReport {
+    /// The problems in the order they were found.
+    issues: Vec<Issue>,
+}
+
+impl ValidationReport {
+    /// Gets the problems found.
+    pub fn issues(&self) -> &[Issue] {
+        &self.issues
+    }
+
+    /// Checks whether any problem stops the path from being run.
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    /// Gets the problems that stop the path from being run.
+    pub fn errors(&self) -> impl Iterator<Item = &Issue> {
+        self.issues.iter().filter(|i| i.severity == Severity::Error)
+    }
+
+    /// Adds a problem to the report.
+    fn push(
+        &mut self,
+        severity: Severity,
+        kind: IssueKind,
+        message: String,
+        coordinate: Option<Coord<f64>>,
+    ) {
+        self.issues
+            .push(Issue::new(severity, kind, message, coordinate));
+    }
+}
+
+/// Checks whether a coordinate is a valid longitude and latitude.
+fn valid_coordinate(c: Coord<f64>) -> bool {
+    (-180.0..=180.0).contains(&c.x) && (-90.0..=90.0).contains(&c.y)
+}
+
+/// Finds the legs that cross another leg of the path, ignoring neighbouring legs.
+fn self_intersections(path: &LineString<f64>) -> Vec<Coord<f64>> {
+    let lines = path.lines().collect::<Vec<Line<f64>>>();
+    let closed = path.is_closed();
+    let mut crossings = vec![];
+    for (i, a) in lines.iter().enumerate() {
+        for (j, b) in lines.iter().enumerate().skip(i + 2) {
+            // The first and last legs of a loop meet at the start
+            if closed && i == 0 && j == lines.len() - 1 {
+                continue;
+            }
+            if a.intersects(b) {
+                crossings.push(b.start);
+            }
+        }
+    }
+    crossings
+}
+
+/// Checks a path for problems before it is run.
+pub fn validate(path: &PathData, options: &ValidationOptions) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    let line = path.path();
+    let points = &path.collection_points().0;
+
+    log::info!("Checking Vertices");
+    match line.0.len() {
+        0 => report.push(
+            Severity::Error,
+            IssueKind::EmptyPath,
+            String::from("Path has no Vertices"),
+            None,
+        ),
+        1 => report.push(
+            Severity::Error,
+            IssueKind::SingleVertex,
+            String::from("Path has only one Vertex"),
+            Some(line.0[0]),
+        ),
+        _ => {}
+    }
+
+    log::info!("Checking Coordinates");
+    let ends = path
+        .home()
+        .into_iter()
+        .chain(path.end())
+        .collect::<Vec<_>>();
+    let coords = line
+        .0
+        .iter()
+        .chain(points.iter().map(|p| &p.0))
+        .chain(ends.iter().map(|p| &p.0));
+    for &c in coords.clone() {
+        if !valid_coordinate(c) {
+            report.push(
+                Severity::Error,
+                IssueKind::InvalidCoordinate,
+                format!("Invalid Coordinate: ({}, {})", c.x, c.y),
+                None,
+            );
+        } else if !options.in_bounds(c) {
+            report.push(
+                Severity::Warning,
+                IssueKind::OutsideMap,
+                format!("Coordinate outside of the Map: ({}, {})", c.x, c.y),
+                Some(c),
+            );
+        }
+    }
+    if report.has_errors() {
+        // The remaining checks need a path with valid coordinates
+        return report;
+    }
+
+    log::info!("Checking Home");
+    match path.home() {
+        Some(home) => {
+            let furthest = line
+                .points()
+                .chain(points.iter().copied())
+                .chain(path.end())
+                .map(|p| (home.geodesic_distance(&p), p))
+                .max_by(|(a, _), (b, _)| a.total_cmp(b));
+            if let Some((distance, p)) = furthest {
+                if distance > options.max_home_distance {
+                    report.push(
+                        Severity::Warning,
+                        IssueKind::FarFromHome,
+                        format!("Path goes {:.0} m from Home", distance),
+                        Some(p.0),
+                    );
+                }
+            }
+        }
+        None => report.push(
+            Severity::Warning,
+            IssueKind::MissingHome,
+            String::from("Path has no Home Point"),
+            None,
+        ),
+    }
+    // The boat only receives the collection points until the protocol has fields for the rest
+    if let Some(p) = path.home().or(path.end()) {
+        report.push(
+            Severity::Warning,
+            IssueKind::HomeNotSent,
+            String::from("Home and End Points are not sent to the Boat"),
+            Some(p.0),
+        );
+    }
+    let default = SamplingInstructions::default();
+    let custom = points
+        .iter()
+        .zip(path.sampling())
+        .filter(|(_, s)| **s != default)
+        .map(|(p, _)| p.0)
+        .collect::<Vec<_>>();
+    if let Some(&p) = custom.first() {
+        report.push(
+            Severity::Warning,
+            IssueKind::SamplingNotSent,
+            format!(
+                "Sampling Instructions of {} Collection Points are not sent to the Boat",
+                custom.len()
+            ),
+            Some(p),
+        );
+    }
+
+    log::info!("Checking Legs");
+    // Including the legs from home to the start and from the end of the path to the finish
+    let first = path.home().map(|p| p.0).zip(line.0.first().copied());
+    let last = line.0.last().copied().zip(path.finish().map(|p| p.0));
+    let ends = first
+        .into_iter()
+        .chain(last)
+        .map(|(start, end)| (Line::new(start, end), false));
+    let legs = line.lines().map(|leg| (leg, true)).chain(ends);
+    for (leg, on_path) in legs {
+        let length = Point::from(leg.start).geodesic_distance(&Point::from(leg.end));
+        if length > options.radio_range {
+            report.push(
+                Severity::Warning,
+                IssueKind::LongLeg,
+                format!("Leg of {:.0} m is longer than the Radio Range", length),
+                Some(leg.start),
+            );
+        } else if on_path && length < options.duplicate_tolerance {
+            report.push(
+                Severity::Warning,
+                IssueKind::DuplicatePoint,
+                String::from("Duplicate Path Vertex"),
+                Some(leg.start),
+            );
+        }
+    }
+    for c in self_intersections(line) {
+        report.push(
+            Severity::Warning,
+            IssueKind::SelfIntersection,
+            String::from("Path crosses itself"),
+            Some(c),
+        );
+    }
+
+    log::info!("Checking Collection Points");
+    for (i, a) in points.iter().enumerate() {
+        if points[..i]
+            .iter()
+            .any(|b| a.geodesic_distance(b) < options.duplicate_tolerance)
+        {
+            report.push(
+                Severity::Warning,
+                IssueKind::DuplicatePoint,
+                String::from("Duplicate Collection Point"),
+                Some(a.0),
+            );
+        }
+    }
+    if let Some(projection) = LocalProjection::around(coords.copied()) {
+        let projected = line
+            .0
+            .iter()
+            .map(|&c| projection.project(c))
+            .collect::<LineString<f64>>();
+        for p in points.iter().filter(|_| !projected.0.is_empty()) {
+            let distance = Point::from(projection.project(p.0)).euclidean_distance(&projected);
+            if distance > options.max_point_distance {
+                report.push(
+                    Severity::Warning,
+                    IssueKind::FarCollectionPoint,
+                    format!("Collection Point is {:.0} m from the Path", distance),
+                    Some(p.0),
+                );
+            }
+        }
+    }
+    log::debug!("Issues: {:?}", report.issues);
+
+    report
+}
+
+/// Check a path for problems before it is sent to the boat.
+#[tauri::command]
+pub fn validate_path(path: PathData, options: Option<ValidationOptions>) -> ValidationReport {
+    log::info!("Validating Path");
+    validate(&path, &options.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::MultiPoint;
+
+    use super::*;
+    use crate::{data::Layer, path::local::lake_path};
+
+    fn kinds(report: &ValidationReport) -> Vec<IssueKind> {
+        report.issues().iter().map(|i| i.kind()).collect()
+    }
+
+    #[test]
+    fn accepts_valid_path() {
+        let track = lake_path(&[(0.0, 0.0), (100.0, 0.0), (100.0, 100.0)], &[(50.0, 5.0)]).0;
+        let home = track.path().points().next();
+        let report = validate(&track.with_home(home, None), &ValidationOptions::default());
+        assert!(!report.has_errors());
+        assert_eq!(kinds(&report), vec![IssueKind::HomeNotSent]);
+    }
+
+    #[test]
+    fn warns_about_sampling_not_sent() {
+        let track = lake_path(&[(0.0, 0.0), (100.0, 0.0)], &[(10.0, 0.0), (90.0, 0.0)]).0;
+        let custom =
+            SamplingInstructions::new(vec![Layer::Surface], vec![], None, 1, None).unwrap();
+        let sampled = track
+            .clone()
+            .with_sampling(vec![SamplingInstructions::default(), custom])
+            .unwrap();
+
+        let kinds = |path: &PathData| {
+            kinds(&validate(path, &ValidationOptions::default()))
+                .into_iter()
+                .filter(|&k| k != IssueKind::MissingHome)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(kinds(&track), vec![]);
+        assert_eq!(kinds(&sampled), vec![IssueKind::SamplingNotSent]);
+    }
+
+    #[test]
+    fn checks_distance_from_home() {
+        let track = lake_path(&[(0.0, 0.0), (100.0, 0.0)], &[]).0;
+        let report = validate(&track, &ValidationOptions::default());
+        assert_eq!(kinds(&report), vec![IssueKind::MissingHome]);
+
+        // Home is 1.5 km before the start and the end is 2.4 km past the end of the path
+        let far = lake_path(&[(-1500.0, 0.0), (2500.0, 0.0)], &[]).0;
+        let mut points = far.path().points();
+        let report = validate(
+            &track.clone().with_home(points.next(), points.next()),
+            &ValidationOptions::default(),
+        );
+        assert_eq!(
+            kinds(&report),
+            vec![
+                IssueKind::FarFromHome,
+                IssueKind::HomeNotSent,
+                IssueKind::LongLeg,
+                IssueKind::LongLeg
+            ]
+        );
+
+        let report = validate(
+            &track.with_home(Some(Point::new(200.0, 0.0)), None),
+            &ValidationOptions::default(),
+        );
+        assert!(report.has_errors());
+    }
+
+    #[test]
+    fn rejects_missing_vertices() {
+        let report = validate(&lake_path(&[], &[]).0, &ValidationOptions::default());
+        assert_eq!(kinds(&report), vec![IssueKind::EmptyPath]);
+        assert!(report.has_errors());
+
+        let report = validate(
+            &lake_path(&[(0.0, 0.0)], &[]).0,
+            &ValidationOptions::default(),
+        );
+        assert_eq!(kinds(&report), vec![IssueKind::SingleVertex]);
+    }
+
+    #[test]
+    fn rejects_invalid_coordinates() {
+        let path = PathData::new(
+            LineString::from(vec![(0.0, 0.0), (181.0, 0.0), (0.0, -91.0)]),
+            MultiPoint(vec![]),
+        );
+        let report = validate(&path, &ValidationOptions::default());
+        assert_eq!(report.errors().count(), 2);
+        assert!(report
+            .issues()
+            .iter()
+            .all(|i| i.kind() == IssueKind::InvalidCoordinate));
+    }
+
+    #[test]
+    fn warns_about_path_problems() {
+        let report = validate(
+            &lake_path(
+                &[
+                    (0.0, 0.0),
+                    (100.0, 100.0),
+                    (100.0, 0.0),
+                    (0.0, 100.0),
+                    (0.0, 2000.0),
+                    (0.0, 2000.1),
+                ],
+                &[(80.0, 500.0), (0.0, 0.0), (0.1, 0.0)],
+            )
+            .0,
+            &ValidationOptions::default(),
+        );
+
+        assert!(!report.has_errors());
+        let kinds = kinds(&report);
+        assert!(kinds.contains(&IssueKind::SelfIntersection));
+        assert!(kinds.contains(&IssueKind::LongLeg));
+        assert_eq!(
+            kinds
+                .iter()
+                .filter(|&&k| k == IssueKind::DuplicatePoint)
+                .count(),
+            2
+        );
+        assert_eq!(
+            kinds
+                .iter()
+                .filter(|&&k| k == IssueKind::FarCollectionPoint)
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn warns_about_points_outside_map() {
+        let track = lake_path(&[(0.0, 0.0), (100.0, 0.0)], &[]).0;
+        let options =
+            ValidationOptions::new(50.0, 1000.0, 0.5, 2000.0, Some((101.0, 3.0, 101.7, 4.0)));
+        let report = validate(&track, &options);
+        assert_eq!(
+            kinds(&report),
+            vec![IssueKind::OutsideMap, IssueKind::MissingHome]
+        );
+    }
+}
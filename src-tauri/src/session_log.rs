@@ -0,0 +1,399 @@
+//! Per-boat log files recording the activity of each connection.
+//!
+//! Every connection to a boat writes a JSON Lines file to
+//! `sessions/<boat>/<connect time>.jsonl` in the application data directory.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::AppHandle;
+
+/// The name of the directory storing the session logs.
+const SESSIONS_DIR: &str = "sessions";
+
+/// Something that happened on the link with a boat.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SessionEvent {
+    /// The boat replied to the connection message.
+    Connected {
+        /// The name of the serial port connected to the boat.
+        port: String,
+        /// The protocol version reported by the boat.
+        version: String,
+    },
+    /// A path was sent to and acknowledged by the boat.
+    PathSent {
+        /// The SHA-256 hash of the encoded path.
+        hash: String,
+        /// The number of collection points in the path.
+        points: usize,
+    },
+    /// Data was received from the boat.
+    DataReceived {
+        /// The number of samples received.
+        samples: usize,
+    },
+    /// An error occurred while communicating with the boat.
+    Error {
+        /// The error message.
+        message: String,
+    },
+    /// The connection to the boat ended.
+    Disconnected {
+        /// Why the connection ended.
+        reason: String,
+    },
+}
+
+/// A line of a session log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionLogEntry {
+    /// When the event happened.
+    time: DateTime<Utc>,
+    /// The event that happened.
+    #[serde(flatten)]
+    event: SessionEvent,
+}
+
+impl SessionLogEntry {
+    /// Creates a new entry for an event that just happened.
+    pub fn new(event: SessionEvent) -> Self {
+        Self {
+            time: Utc::now(),
+            event,
+        }
+    }
+
+    /// Gets when the event happened.
+    pub fn time(&self) -> DateTime<Utc> {
+        self.time
+    }
+
+    /// Gets the event that happened.
+    pub fn event(&self) -> &SessionEvent {
+        &self.event
+    }
+}
+
+/// Log file of a single connection to a boat.
+#[derive(Debug)]
+pub struct SessionLog {
+    /// The file the log is written to.
+    file: File,
+    /// The location of the log file.
+    path: PathBuf,
+}
+
+impl SessionLog {
+    /// Creates a new session log in the application data directory.
+    ///
+    /// The first entry of the log is the `Connected` event.
+    pub fn create(app_handle: &AppHandle, port: &str, version: &str) -> Result<Self, String> {
+        let dir = sessions_dir(app_handle)?;
+        Self::create_in(&dir, port, version)
+    }
+
+    /// Creates a new session log in a sessions directory.
+    pub fn create_in(dir: &Path, port: &str, version: &str) -> Result<Self, String> {
+        let entry = SessionLogEntry::new(SessionEvent::Connected {
+            port: port.to_string(),
+            version: version.to_string(),
+        });
+
+        let mut path = dir.join(boat_dir_name(port));
+        fs::create_dir_all(&path).map_err(|e| e.to_string())?;
+        path.push(format!("{}.jsonl", entry.time.format("%Y%m%dT%H%M%S%.3fZ")));
+        log::info!("Creating Session Log: {}", path.display());
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| e.to_string())?;
+        let mut log = Self { file, path };
+        log.record(entry.event);
+        Ok(log)
+    }
+
+    /// Gets the location of the log file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends an event to the log.
+    ///
+    /// Failing to write the log is not fatal to the connection, so errors are only logged.
+    pub fn record(&mut self, event: SessionEvent) {
+        let entry = SessionLogEntry::new(event);
+        log::debug!("Session Event: {:?}", entry);
+        let result = serde_json::to_string(&entry)
+            .map_err(|e| e.to_string())
+            .and_then(|v| writeln!(self.file, "{v}").map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            log::warn!("Unable to Write Session Log {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Hashes encoded path data for the session log.
+pub fn hash(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Overview of a past session.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SessionLogSummary {
+    /// The identifier used to open the session log.
+    id: String,
+    /// The name of the serial port connected to the boat.
+    port: String,
+    /// The protocol version reported by the boat.
+    version: String,
+    /// When the boat connected.
+    connected: DateTime<Utc>,
+    /// When the boat disconnected, if the log recorded it.
+    disconnected: Option<DateTime<Utc>>,
+    /// Why the boat disconnected, if the log recorded it.
+    disconnect_reason: Option<String>,
+    /// The number of paths sent to the boat.
+    paths_sent: usize,
+    /// The number of samples received from the boat.
+    samples_received: usize,
+    /// The number of errors recorded.
+    errors: usize,
+}
+
+impl SessionLogSummary {
+    /// Summarises the entries of a session log.
+    ///
+    /// Returns `None` if the log does not start with a `Connected` event.
+    fn from_entries(id: String, entries: &[SessionLogEntry]) -> Option<Self> {
+        let (first, rest) = entries.split_first()?;
+        let (port, version) = match &first.event {
+            SessionEvent::Connected { port, version } => (port.clone(), version.clone()),
+            _ => return None,
+        };
+
+        let mut summary = Self {
+            id,
+            port,
+            version,
+            connected: first.time,
+            disconnected: None,
+            disconnect_reason: None,
+            paths_sent: 0,
+            samples_received: 0,
+            errors: 0,
+        };
+        for entry in rest {
+            match &entry.event {
+                SessionEvent::Connected { .. } => (),
+                SessionEvent::PathSent { .. } => summary.paths_sent += 1,
+                SessionEvent::DataReceived { samples } => summary.samples_received += samples,
+                SessionEvent::Error { .. } => summary.errors += 1,
+                SessionEvent::Disconnected { reason } => {
+                    summary.disconnected = Some(entry.time);
+                    summary.disconnect_reason = Some(reason.clone());
+                }
+            }
+        }
+        Some(summary)
+    }
+}
+
+/// Gets the directory storing the session logs.
+fn sessions_dir(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    let mut data_dir = app_handle
+        .path_resolver()
+        .app_data_dir()
+        .ok_or(String::from("Unable to Get App Data Directory"))?;
+    data_dir.push(SESSIONS_DIR);
+    Ok(data_dir)
+}
+
+/// Converts a port name into a directory name for its session logs.
+///
+/// Port names such as `/dev/ttyUSB0` contain path separators.
+fn boat_dir_name(port: &str) -> String {
+    let name: String = port
+        .trim_start_matches(['/', '\\', '.'])
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '.' => c,
+            _ => '_',
+        })
+        .collect();
+    if name.is_empty() {
+        String::from("unknown")
+    } else {
+        name
+    }
+}
+
+/// Resolves the file of a session log from its identifier.
+fn session_path(dir: &Path, id: &str) -> Result<PathBuf, String> {
+    let mut parts = id.split('/');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(boat), Some(session), None)
+            if boat == boat_dir_name(boat) && session == boat_dir_name(session) =>
+        {
+            Ok(dir.join(boat).join(format!("{session}.jsonl")))
+        }
+        _ => Err(format!("Invalid Session Log: {id}")),
+    }
+}
+
+/// Reads the entries of a session log file.
+///
+/// Lines that cannot be parsed, such as a line cut short by the application closing, are skipped.
+fn read_entries(path: &Path) -> Result<Vec<SessionLogEntry>, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let mut entries = vec![];
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(v) => entries.push(v),
+            Err(e) => log::warn!("Skipping Line {} of {}: {}", i + 1, path.display(), e),
+        }
+    }
+    Ok(entries)
+}
+
+/// Lists the session logs in a sessions directory, newest first.
+fn list_in(dir: &Path, port: Option<&str>) -> Result<Vec<SessionLogSummary>, String> {
+    let boats = match fs::read_dir(dir) {
+        Ok(v) => v,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let mut summaries = vec![];
+    for boat in boats {
+        let boat = boat.map_err(|e| e.to_string())?;
+        if !boat.path().is_dir() {
+            continue;
+        }
+        let boat_name = boat.file_name().to_string_lossy().to_string();
+        if matches!(port, Some(v) if boat_dir_name(v) != boat_name) {
+            continue;
+        }
+
+        for session in fs::read_dir(boat.path()).map_err(|e| e.to_string())? {
+            let session = session.map_err(|e| e.to_string())?.path();
+            if session.extension().and_then(|v| v.to_str()) != Some("jsonl") {
+                continue;
+            }
+            let stem = match session.file_stem().and_then(|v| v.to_str()) {
+                Some(v) => v,
+                None => continue,
+            };
+
+            let id = format!("{boat_name}/{stem}");
+            match SessionLogSummary::from_entries(id, &read_entries(&session)?) {
+                Some(v) => summaries.push(v),
+                None => log::warn!("Skipping Invalid Session Log: {}", session.display()),
+            }
+        }
+    }
+    summaries.sort_by_key(|v| std::cmp::Reverse(v.connected));
+    Ok(summaries)
+}
+
+/// List the past sessions, optionally only for a single boat.
+#[tauri::command]
+pub fn list_session_logs(
+    app_handle: AppHandle,
+    port: Option<String>,
+) -> Result<Vec<SessionLogSummary>, String> {
+    log::debug!("Listing Session Logs");
+    let dir = sessions_dir(&app_handle)?;
+    log::debug!("Sessions Directory: {}", dir.display());
+    list_in(&dir, port.as_deref())
+}
+
+/// Open a past session to read all its entries.
+#[tauri::command]
+pub fn open_session_log(app_handle: AppHandle, id: String) -> Result<Vec<SessionLogEntry>, String> {
+    log::debug!("Opening Session Log: {}", id);
+    let dir = sessions_dir(&app_handle)?;
+    read_entries(&session_path(&dir, &id)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitises_port_names() {
+        assert_eq!(boat_dir_name("/dev/ttyUSB0"), "dev_ttyUSB0");
+        assert_eq!(boat_dir_name("COM3"), "COM3");
+        assert_eq!(boat_dir_name(r"\\.\COM10"), "COM10");
+        assert_eq!(boat_dir_name("/"), "unknown");
+    }
+
+    #[test]
+    fn rejects_ids_outside_sessions_dir() {
+        let dir = Path::new("sessions");
+        assert_eq!(
+            session_path(dir, "COM3/20240101T000000.000Z"),
+            Ok(dir.join("COM3").join("20240101T000000.000Z.jsonl"))
+        );
+        assert!(session_path(dir, "../COM3").is_err());
+        assert!(session_path(dir, "COM3/../../secret").is_err());
+        assert!(session_path(dir, "COM3/..").is_err());
+        assert!(session_path(dir, "COM3").is_err());
+    }
+
+    #[test]
+    fn records_and_summarises_sessions() {
+        let dir = std::env::temp_dir().join(format!("session-log-{}", std::process::id()));
+        let mut log = SessionLog::create_in(&dir, "/dev/ttyUSB0", "0.1.0").unwrap();
+        log.record(SessionEvent::PathSent {
+            hash: hash(b"path"),
+            points: 4,
+        });
+        log.record(SessionEvent::DataReceived { samples: 3 });
+        log.record(SessionEvent::DataReceived { samples: 2 });
+        log.record(SessionEvent::Error {
+            message: String::from("Nothing is Received"),
+        });
+        log.record(SessionEvent::Disconnected {
+            reason: String::from("Port Closed"),
+        });
+        // A line cut short when the application closed
+        write!(log.file, "{{\"time\":").unwrap();
+
+        let summaries = list_in(&dir, Some("/dev/ttyUSB0")).unwrap();
+        assert_eq!(summaries.len(), 1);
+        let summary = &summaries[0];
+        assert_eq!(summary.port, "/dev/ttyUSB0");
+        assert_eq!(summary.version, "0.1.0");
+        assert_eq!(summary.paths_sent, 1);
+        assert_eq!(summary.samples_received, 5);
+        assert_eq!(summary.errors, 1);
+        assert_eq!(summary.disconnect_reason.as_deref(), Some("Port Closed"));
+        assert!(list_in(&dir, Some("COM3")).unwrap().is_empty());
+
+        let entries = read_entries(&session_path(&dir, &summary.id).unwrap()).unwrap();
+        assert_eq!(entries.len(), 6);
+        assert_eq!(
+            entries[1].event(),
+            &SessionEvent::PathSent {
+                hash: hash(b"path"),
+                points: 4
+            }
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
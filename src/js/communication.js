@@ -1,10 +1,12 @@
 /** Communication Logic with the Boat. */
 import { invoke } from "@tauri-apps/api";
+import { confirm } from "@tauri-apps/api/dialog";
 import { listen } from "@tauri-apps/api/event";
 import * as logging from "tauri-plugin-log-api";
 
 import * as boat_vars from "./data";
 import * as path_vars from "./map/add_point";
+import * as map_vars from "./mbtiles";
 
 const connected_status = `
             <div class="flex items-center justify-between bg-green-500 text-white p-2">
@@ -47,13 +49,35 @@ if (run_button === null) {
         }
 
         try {
+            const options = { bounds: map_vars.bounds };
+            const report = await invoke("validate_path", {
+                path: path_vars.path_data,
+                options: options,
+            });
+            const errors = report.issues.filter((issue) => issue.severity === "error");
+
+            // Paths with errors are only sent if the user confirms
+            if (errors.length > 0) {
+                const messages = errors.map((issue) => `- ${issue.message}`).join("\n");
+                logging.warn(`Invalid Path: ${errors.map((issue) => issue.message).join(", ")}`);
+                const force = await confirm(`${messages}\n\nSend the path anyway?`, {
+                    title: "Invalid Path",
+                    type: "warning",
+                });
+                if (!force) {
+                    return;
+                }
+            }
+
             logging.info(`Sending Path to Port ${port}`);
             await invoke("send_path", {
                 port: port,
                 data: path_vars.path_data,
+                options: options,
+                force: errors.length > 0,
             });
         } catch (e) {
-            logging.error(e);
+            logging.error(String(e));
         }
     });
 }
@@ -101,8 +125,19 @@ search_port();
 listen("received-data", async (event) => {
     if (event.payload.port === port) {
         logging.info("Received Data from Boat");
-        boat_vars.update_data(event.payload.data);
-        await invoke("save_data", { data: boat_vars.boat_data });
+        try {
+            const merged = await invoke("merge_data", {
+                data: boat_vars.boat_data,
+                sources: [event.payload.data],
+            });
+            logging.info(
+                `Added: ${merged.added}, Skipped: ${merged.skipped}, Conflicting: ${merged.conflicting}`,
+            );
+            boat_vars.update_data(merged.data);
+            await invoke("save_data", { data: boat_vars.boat_data });
+        } catch (e) {
+            logging.error(String(e));
+        }
     }
 });
 
@@ -13,6 +13,7 @@ import * as logging from "tauri-plugin-log-api";
  *      depth: Number,
  *      layer: ("surface" | "middle" | "sea bed"),
  *      time: Date,
+ *      session?: String,
  *  }
  *  geometry: {
  *      type: "Point",
@@ -21,10 +22,23 @@ import * as logging from "tauri-plugin-log-api";
  * }} BoatDataFeature
  */
 
+/** Survey Session Type
+ * @typedef {{
+ *  id: String,
+ *  name: String,
+ *  boat: String,
+ *  path_hash: (String | null),
+ *  start: Date,
+ *  end: (Date | null),
+ *  notes: String,
+ * }} Session
+ */
+
 /** Boat Data Type
  * @typedef {{
  *  type: "FeatureCollection",
  *  version: String,
+ *  sessions?: Array<Session>,
  *  features: Array<BoatDataFeature>,
  * }} BoatData
  */
@@ -37,7 +51,7 @@ async function read_data() {
         logging.error(String(e));
         boat_data = {
             type: "FeatureCollection",
-            version: "0.1.0",
+            version: "0.2.0",
             features: [],
         };
     }
@@ -165,6 +165,10 @@ async function import_path(file_path) {
             path_vars.point_coords.length,
             ...new_points.geometry.coordinates,
         );
+        find_feature(path_vars.path_data.features, "MultiPoint").sampling =
+            new_points.sampling;
+        path_vars.path_data.home = new_path.home;
+        path_vars.path_data.end = new_path.end;
 
         logging.info("Redrawing Map");
         path_vars.source.setData(path_vars.path_data);
@@ -19,6 +19,7 @@ import end_icon from "../../icons/end-point.png";
  * @typedef{{
  *  type: "Feature",
  *  geometry: PathDataGeometry,
+ *  sampling?: Array<Object>,
  * }} PathDataFeature
  */
 
@@ -26,6 +27,8 @@ import end_icon from "../../icons/end-point.png";
  * @typedef {{
  *  type: "FeatureCollection",
  *  version: String,
+ *  home?: [number, number],
+ *  end?: [number, number],
  *  features: Array<PathDataFeature>,
  * }} PathData
  */
@@ -51,7 +54,7 @@ async function read_path() {
         logging.error(String(e));
         path_data = {
             type: "FeatureCollection",
-            version: "0.1.0",
+            version: "0.3.0",
             features: [
                 {
                     type: "Feature",
@@ -228,4 +231,44 @@ export function redraw_markers() {
     }
 }
 
+/** Replaces the path shown on the map without saving it.
+ *
+ * @param {PathData} new_path The path to show.
+ */
+function replace_path(new_path) {
+    const new_lines = new_path.features[1].geometry.coordinates;
+    const new_points = new_path.features[0].geometry.coordinates;
+    line_coords.splice(0, line_coords.length, ...new_lines);
+    point_coords.splice(0, point_coords.length, ...new_points);
+    path_data.features[0].sampling = new_path.features[0].sampling;
+    path_data.home = new_path.home;
+    path_data.end = new_path.end;
+
+    source.setData(path_data);
+    redraw_markers();
+}
+
+// Undoing with Ctrl+Z and redoing with Ctrl+Shift+Z
+document.addEventListener("keydown", async (event) => {
+    if (!(event.ctrlKey || event.metaKey) || event.key.toLowerCase() !== "z") {
+        return;
+    }
+    if (event.target instanceof HTMLInputElement) {
+        // Leaving text inputs with their own undo
+        return;
+    }
+    event.preventDefault();
+
+    const command = event.shiftKey ? "redo_path" : "undo_path";
+    try {
+        logging.info(`Running ${command}`);
+        const new_path = await invoke(command);
+        if (new_path !== null) {
+            replace_path(new_path);
+        }
+    } catch (e) {
+        logging.error(String(e));
+    }
+});
+
 export default path_data;
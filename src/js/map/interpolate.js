@@ -1,5 +1,6 @@
 /** Functions to interpolate points between two locations. */
 
+import { invoke } from "@tauri-apps/api";
 import * as maplibregl from "maplibre-gl";
 import * as logging from "tauri-plugin-log-api";
 import * as path_vars from "./add_point";
@@ -7,135 +8,6 @@ import * as path_vars from "./add_point";
 /** Current Distance for Path Interpolation. */
 export let current_distance = 3;
 
-/** Interates through elements pairwise.
- *
- * @see{https://stackoverflow.com/questions/31973278/iterate-an-array-as-a-pair-current-next-in-javascript}
- * */
-function* pairwise(iterable) {
-    const iterator = iterable[Symbol.iterator]();
-    let a = iterator.next();
-    if (a.done) return;
-    let b = iterator.next();
-    while (!b.done) {
-        yield [a.value, b.value];
-        a = b;
-        b = iterator.next();
-    }
-}
-
-/** Converts a number from degrees to radians.
- *
- * @param {Number} deg The angle in degrees.
- * @returns{Number} The angle in radians.
- */
-function deg_to_rad(deg) {
-    return (deg * Math.PI) / 180;
-}
-
-/** Converts a number from radians to degrees.
- *
- * @param {Number} rad The angle in radians.
- * @returns{Number} The angle in degrees.
- */
-function rad_to_deg(rad) {
-    return (rad * 180) / Math.PI;
-}
-
-/** Fill in the points with fixed distance between them.
- *
- * @param {Array<[number, number]>} points The points to interpolate.
- * @param {number} distance The distance between the points.
- * @returns {Array<[number, number]>} An array with interpolated points.
- * */
-function interpolate(distance, points) {
-    // Radius of Earth
-    logging.debug(`Interpolating points for: ${JSON.stringify(points)}`);
-    const points_mapped = points.map(maplibregl.LngLat.convert);
-    let from_point = points_mapped[0];
-
-    const lat1 = deg_to_rad(from_point.lat);
-    const lng1 = deg_to_rad(from_point.lng);
-
-    logging.info("Calculating Distance Between the Points");
-    const point_distance = points_mapped[0].distanceTo(points_mapped[1]);
-    logging.debug(`Point Distance: ${point_distance}`);
-
-    logging.info("Calculating Number of Points");
-    const count = Math.ceil(Math.floor(point_distance / distance) - 1, 0);
-    logging.debug(`Adding ${count} new points`);
-
-    const result = [];
-    result.length = count + 1;
-
-    for (const i of result.slice(0, count).keys()) {
-        logging.info(`Interpolating Point ${i}`);
-
-        // https://www.movable-type.co.uk/scripts/latlong.html
-        const lat2 = deg_to_rad(points_mapped[1].lat);
-        const lng2 = deg_to_rad(points_mapped[1].lng);
-
-        logging.info("Calculating Bearing");
-        const y = Math.sin(lng2 - lng1) * Math.cos(lat2);
-        const x =
-            Math.cos(lat1) * Math.sin(lat2) -
-            Math.sin(lat1) * Math.cos(lat2) * Math.cos(lng2 - lng1);
-        const brng = Math.atan2(y, x);
-        logging.debug(`Bearing: ${brng}`);
-
-        result[i] = calculate_destination(from_point, brng, distance);
-        logging.debug(`Inerpolated Point ${i}: ${JSON.stringify(result[i])}`);
-        from_point = maplibregl.LngLat.convert(result[i]);
-    }
-    result[result.length - 1] = points[1];
-
-    logging.debug(`Interpolated Points: ${JSON.stringify(result)}`);
-    return result;
-}
-
-/** Calculate a destination point given distances and bearing from a starting point.
- *
- * @param {maplibregl.LngLatLike} from The starting point.
- * @param {Number} brng The bearing from starting point to destination.
- * @param {Number} distance The distance of the destination.
- * @returns{maplibregl.LngLatLike} The destination point.
- */
-function calculate_destination(from, brng, distance) {
-    const R = 6371e3;
-
-    const lat1 = deg_to_rad(from.lat);
-    const lng1 = deg_to_rad(from.lng);
-
-    // https://www.movable-type.co.uk/scripts/latlong.html
-    const new_lat = Math.asin(
-        Math.sin(lat1) * Math.cos(distance / R) +
-            Math.cos(lat1) * Math.sin(distance / R) * Math.cos(brng),
-    );
-    let new_lng =
-        lng1 +
-        Math.atan2(
-            Math.sin(brng) * Math.sin(distance / R) * Math.cos(lat1),
-            Math.cos(distance / R) - Math.sin(lat1) * Math.sin(new_lat),
-        );
-    new_lng = ((new_lng + 3 * Math.PI) % (2 * Math.PI)) - Math.PI;
-
-    return [new_lng, new_lat].map(rad_to_deg);
-}
-
-/** Fill in the points with fixed distance between them.
- *
- * @param {Array<[number, number]>} points The points to interpolate.
- * @param {number} distance The distance between the points.
- * @returns {Array<[number, number]>} An array with interpolated points.
- * */
-export function interpolate_points(points, distance) {
-    const array = Array.from(pairwise(points)).flatMap(
-        interpolate.bind(null, distance),
-    );
-    logging.info("Adding Back Initial Point");
-    array.unshift(points[0]);
-    return array;
-}
-
 /** Find the coordinate that is nearest to a target coordinate.
  *
  * @param {maplibregl.LngLat} target_point The reference point to measure the distance from .
@@ -214,7 +86,7 @@ const generate_button = document.getElementById("interpolate-button");
 if (generate_button === null) {
     logging.error("Unable to Find Generate Path Button");
 } else {
-    generate_button.addEventListener("click", () => {
+    generate_button.addEventListener("click", async () => {
         logging.info("Generating Path");
         const points = path_vars.markers.map((v) => v.getLngLat());
         const new_path = generate_path(points).map((v) => v.toArray());
@@ -226,13 +98,23 @@ if (generate_button === null) {
         );
 
         logging.info("Interpolating Points");
-        const new_values = interpolate_points(
-            path_vars.line_coords,
-            current_distance,
-        );
-        path_vars.point_coords.length = new_values.length;
-        for (const i in new_values) {
-            path_vars.point_coords[i] = new_values[i];
+        try {
+            const interpolated = await invoke("interpolate_path", {
+                path: path_vars.path_data,
+                spacing: Number(current_distance),
+                resetAtVertices: true,
+            });
+            const new_values = interpolated.features[0].geometry.coordinates;
+            path_vars.point_coords.splice(
+                0,
+                path_vars.point_coords.length,
+                ...new_values,
+            );
+            path_vars.path_data.features[0].sampling =
+                interpolated.features[0].sampling;
+        } catch (e) {
+            logging.error(`Unable to Interpolate Points: ${e}`);
+            return;
         }
 
         logging.info("Updating UI");
@@ -244,4 +126,4 @@ if (generate_button === null) {
     });
 }
 
-export default { interpolate_points, generate_path };
+export default { generate_path };
@@ -2,6 +2,12 @@
 import { invoke, path } from "@tauri-apps/api";
 import * as logging from "tauri-plugin-log-api";
 
+/** The bounds of the loaded map as west, south, east and north, if it has them.
+ *
+ * @type{Array<number> | null}
+ */
+export let bounds = null;
+
 /** MBTiles Protocol for Maplibre JS.
  *
  * @param {import("maplibre-gl").RequestParameters} params - Request parameters for the tiles.
@@ -21,6 +27,9 @@ export default async function mbtiles_protocol(params) {
         try {
             const tiles_json = await invoke("mbtiles_metadata", { db: db_file });
             tiles_json.tiles = tiles;
+            if (Array.isArray(tiles_json.bounds) && tiles_json.bounds.length === 4) {
+                bounds = tiles_json.bounds;
+            }
             logging.debug("Fetched Metadata: " + JSON.stringify(tiles_json));
             return {
                 data: tiles_json